@@ -0,0 +1,4 @@
+drop table follows;
+drop table events;
+drop table auth_tokens;
+drop table users;
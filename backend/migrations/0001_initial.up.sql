@@ -32,4 +32,4 @@ CREATE TABLE follows (
   followed_id uuid not null references users (id),
   created_at timestamp with time zone not null,
   updated_at timestamp with time zone not null
-);
\ No newline at end of file
+);
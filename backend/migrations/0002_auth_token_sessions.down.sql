@@ -0,0 +1,6 @@
+drop index auth_tokens_user_id;
+
+alter table auth_tokens
+  drop column device,
+  drop column last_used_at,
+  drop column expires_at;
@@ -0,0 +1,17 @@
+-- Tokens become sessions that record the device they were issued to, when they were last used
+-- and when they expire.
+alter table auth_tokens
+  add column device varchar,
+  add column last_used_at timestamp with time zone,
+  add column expires_at timestamp with time zone;
+
+-- Tokens issued before this never expired, so they get a full lifetime from now.
+update auth_tokens set
+  last_used_at = updated_at,
+  expires_at = now() + make_interval(days => current_setting('app.auth_token_ttl_days')::integer);
+
+alter table auth_tokens
+  alter column last_used_at set not null,
+  alter column expires_at set not null;
+
+create index auth_tokens_user_id on auth_tokens(user_id);
@@ -0,0 +1,10 @@
+-- The raw tokens can't be recovered from their hashes, so everyone has to log in again.
+delete from auth_tokens;
+
+drop index auth_tokens_token_hash;
+
+alter table auth_tokens drop column token_hash;
+
+alter table auth_tokens add column token varchar not null;
+
+create unique index auth_tokens_token on auth_tokens(token);
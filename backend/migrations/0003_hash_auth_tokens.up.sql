@@ -0,0 +1,15 @@
+-- Replaces the plaintext auth_tokens.token column with a keyed hash of the token, the same one
+-- `hash_token` computes, so existing sessions keep working.
+create extension if not exists pgcrypto;
+
+alter table auth_tokens add column token_hash varchar;
+
+update auth_tokens set token_hash = encode(hmac(token, current_setting('app.secret_key'), 'sha256'), 'hex');
+
+alter table auth_tokens alter column token_hash set not null;
+
+drop index auth_tokens_token;
+
+alter table auth_tokens drop column token;
+
+create unique index auth_tokens_token_hash on auth_tokens(token_hash);
@@ -0,0 +1,3 @@
+drop index events_in_reply_to;
+
+alter table events drop column in_reply_to;
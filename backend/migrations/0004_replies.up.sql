@@ -0,0 +1,3 @@
+alter table events add column in_reply_to uuid references events (id) on delete set null;
+
+create index events_in_reply_to on events(in_reply_to);
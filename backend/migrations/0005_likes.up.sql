@@ -0,0 +1,10 @@
+CREATE TABLE likes (
+  id UUID PRIMARY KEY,
+  user_id uuid not null references users (id),
+  event_id uuid not null references events (id) on delete cascade,
+  created_at timestamp with time zone not null,
+  updated_at timestamp with time zone not null
+);
+
+create unique index likes_user_id_event_id on likes(user_id, event_id);
+create index likes_event_id on likes(event_id);
@@ -0,0 +1,3 @@
+drop table reposts;
+
+alter table events drop column quote_of;
@@ -0,0 +1,12 @@
+alter table events add column quote_of uuid references events (id) on delete set null;
+
+CREATE TABLE reposts (
+  id UUID PRIMARY KEY,
+  user_id uuid not null references users (id),
+  event_id uuid not null references events (id) on delete cascade,
+  created_at timestamp with time zone not null,
+  updated_at timestamp with time zone not null
+);
+
+create unique index reposts_user_id_event_id on reposts(user_id, event_id);
+create index reposts_event_id on reposts(event_id);
@@ -0,0 +1,3 @@
+drop table event_revisions;
+
+alter table events drop column edited_at;
@@ -0,0 +1,11 @@
+alter table events add column edited_at timestamp with time zone;
+
+CREATE TABLE event_revisions (
+  id UUID PRIMARY KEY,
+  event_id uuid not null references events (id) on delete cascade,
+  content text not null,
+  created_at timestamp with time zone not null,
+  updated_at timestamp with time zone not null
+);
+
+create index event_revisions_event_id on event_revisions(event_id);
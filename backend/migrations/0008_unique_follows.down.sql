@@ -0,0 +1 @@
+drop index follows_follower_id_followed_id;
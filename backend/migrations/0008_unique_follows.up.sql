@@ -0,0 +1,11 @@
+-- Following used to insert a row every time, so keep only the first of any duplicates.
+delete from follows
+where id in (
+  select id from (
+    select id, row_number() over (partition by follower_id, followed_id order by created_at, id) as n
+    from follows
+  ) numbered
+  where n > 1
+);
+
+create unique index follows_follower_id_followed_id on follows(follower_id, followed_id);
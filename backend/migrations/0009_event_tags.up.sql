@@ -0,0 +1,10 @@
+CREATE TABLE event_tags (
+  id UUID PRIMARY KEY,
+  event_id uuid not null references events (id) on delete cascade,
+  tag varchar not null,
+  created_at timestamp with time zone not null,
+  updated_at timestamp with time zone not null
+);
+
+create unique index event_tags_event_id_tag on event_tags(event_id, tag);
+create index event_tags_tag_created_at on event_tags(tag, created_at);
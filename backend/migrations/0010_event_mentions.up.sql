@@ -0,0 +1,10 @@
+CREATE TABLE event_mentions (
+  id UUID PRIMARY KEY,
+  event_id uuid not null references events (id) on delete cascade,
+  user_id uuid not null references users (id),
+  created_at timestamp with time zone not null,
+  updated_at timestamp with time zone not null
+);
+
+create unique index event_mentions_event_id_user_id on event_mentions(event_id, user_id);
+create index event_mentions_user_id_created_at on event_mentions(user_id, created_at);
@@ -0,0 +1,12 @@
+CREATE TABLE notifications (
+  id UUID PRIMARY KEY,
+  user_id uuid not null references users (id),
+  actor_id uuid not null references users (id),
+  kind varchar not null,
+  event_id uuid references events (id) on delete cascade,
+  read_at timestamp with time zone,
+  created_at timestamp with time zone not null,
+  updated_at timestamp with time zone not null
+);
+
+create index notifications_user_id_created_at on notifications(user_id, created_at);
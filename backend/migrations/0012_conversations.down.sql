@@ -0,0 +1,3 @@
+drop table messages;
+drop table conversation_members;
+drop table conversations;
@@ -0,0 +1,28 @@
+CREATE TABLE conversations (
+  id UUID PRIMARY KEY,
+  last_message_at timestamp with time zone not null,
+  created_at timestamp with time zone not null,
+  updated_at timestamp with time zone not null
+);
+
+CREATE TABLE conversation_members (
+  id UUID PRIMARY KEY,
+  conversation_id uuid not null references conversations (id) on delete cascade,
+  user_id uuid not null references users (id),
+  created_at timestamp with time zone not null,
+  updated_at timestamp with time zone not null
+);
+
+create unique index conversation_members_conversation_id_user_id on conversation_members(conversation_id, user_id);
+create index conversation_members_user_id on conversation_members(user_id);
+
+CREATE TABLE messages (
+  id UUID PRIMARY KEY,
+  conversation_id uuid not null references conversations (id) on delete cascade,
+  user_id uuid not null references users (id),
+  content varchar not null,
+  created_at timestamp with time zone not null,
+  updated_at timestamp with time zone not null
+);
+
+create index messages_conversation_id_created_at on messages(conversation_id, created_at);
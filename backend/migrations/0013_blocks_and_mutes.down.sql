@@ -0,0 +1,2 @@
+drop table mutes;
+drop table blocks;
@@ -0,0 +1,19 @@
+CREATE TABLE blocks (
+  id UUID PRIMARY KEY,
+  blocker_id uuid not null references users (id),
+  blocked_id uuid not null references users (id),
+  created_at timestamp with time zone not null,
+  updated_at timestamp with time zone not null
+);
+
+create unique index blocks_blocker_id_blocked_id on blocks(blocker_id, blocked_id);
+
+CREATE TABLE mutes (
+  id UUID PRIMARY KEY,
+  muter_id uuid not null references users (id),
+  muted_id uuid not null references users (id),
+  created_at timestamp with time zone not null,
+  updated_at timestamp with time zone not null
+);
+
+create unique index mutes_muter_id_muted_id on mutes(muter_id, muted_id);
@@ -0,0 +1,11 @@
+alter table users add column is_private boolean not null default false;
+
+CREATE TABLE follow_requests (
+  id UUID PRIMARY KEY,
+  requester_id uuid not null references users (id),
+  requested_id uuid not null references users (id),
+  created_at timestamp with time zone not null,
+  updated_at timestamp with time zone not null
+);
+
+create unique index follow_requests_requester_id_requested_id on follow_requests(requester_id, requested_id);
@@ -0,0 +1,4 @@
+alter table users
+  drop column display_name,
+  drop column bio,
+  drop column avatar_url;
@@ -0,0 +1,4 @@
+alter table users
+  add column display_name varchar,
+  add column bio varchar,
+  add column avatar_url varchar;
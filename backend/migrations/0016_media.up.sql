@@ -0,0 +1,15 @@
+CREATE TABLE media (
+  id UUID PRIMARY KEY,
+  user_id uuid not null references users (id),
+  event_id uuid references events (id) on delete cascade,
+  position integer,
+  content_type varchar not null,
+  extension varchar not null,
+  byte_size integer not null,
+  width integer not null,
+  height integer not null,
+  created_at timestamp with time zone not null,
+  updated_at timestamp with time zone not null
+);
+
+create index media_event_id on media(event_id);
@@ -0,0 +1,5 @@
+drop index users_lower_display_name;
+drop index users_lower_username;
+drop index events_search_vector;
+
+alter table events drop column search_vector;
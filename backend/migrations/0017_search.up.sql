@@ -0,0 +1,5 @@
+alter table events add column search_vector tsvector generated always as (to_tsvector('english', content)) stored;
+
+create index events_search_vector on events using gin(search_vector);
+create index users_lower_username on users(lower(username) varchar_pattern_ops);
+create index users_lower_display_name on users(lower(display_name) varchar_pattern_ops);
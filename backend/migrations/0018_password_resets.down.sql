@@ -0,0 +1,5 @@
+drop table password_resets;
+
+drop index users_lower_email;
+
+alter table users drop column email;
@@ -0,0 +1,16 @@
+alter table users add column email varchar;
+
+create unique index users_lower_email on users(lower(email));
+
+CREATE TABLE password_resets (
+  id UUID PRIMARY KEY,
+  user_id uuid not null references users (id) on delete cascade,
+  token_hash varchar not null,
+  expires_at timestamp with time zone not null,
+  used_at timestamp with time zone,
+  created_at timestamp with time zone not null,
+  updated_at timestamp with time zone not null
+);
+
+create unique index password_resets_token_hash on password_resets(token_hash);
+create index password_resets_user_id on password_resets(user_id);
@@ -0,0 +1 @@
+drop index users_lower_username_unique;
@@ -0,0 +1,2 @@
+-- Usernames are unique regardless of case, so "Tim" and "tim" can't both sign up.
+create unique index users_lower_username_unique on users(lower(username));
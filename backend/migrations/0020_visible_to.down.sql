@@ -0,0 +1 @@
+drop function visible_to(uuid, uuid);
@@ -0,0 +1,7 @@
+-- Whether `viewer` may see what `author` posts: a private account's events are only visible to
+-- the account itself and its followers. `viewer` is null for requests that aren't signed in.
+create function visible_to(viewer uuid, author uuid) returns boolean as $$
+  select not exists(select 1 from users where users.id = author and users.is_private)
+    or author = viewer
+    or exists(select 1 from follows where follows.follower_id = viewer and follows.followed_id = author)
+$$ language sql stable;
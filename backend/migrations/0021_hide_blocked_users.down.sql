@@ -0,0 +1,9 @@
+drop function hidden_from(uuid, uuid);
+
+create or replace function visible_to(viewer uuid, author uuid) returns boolean as $$
+  select not exists(select 1 from users where users.id = author and users.is_private)
+    or author = viewer
+    or exists(select 1 from follows where follows.follower_id = viewer and follows.followed_id = author)
+$$ language sql stable;
+
+drop function blocked_between(uuid, uuid);
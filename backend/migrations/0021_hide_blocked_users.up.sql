@@ -0,0 +1,25 @@
+-- Whether either user has blocked the other.
+create function blocked_between(a uuid, b uuid) returns boolean as $$
+  select exists(
+    select 1 from blocks
+    where (blocks.blocker_id = a and blocks.blocked_id = b)
+      or (blocks.blocker_id = b and blocks.blocked_id = a)
+  )
+$$ language sql stable;
+
+-- Blocking either way also hides each account's events from the other.
+create or replace function visible_to(viewer uuid, author uuid) returns boolean as $$
+  select (
+      not exists(select 1 from users where users.id = author and users.is_private)
+      or author is not distinct from viewer
+      or exists(select 1 from follows where follows.follower_id = viewer and follows.followed_id = author)
+    )
+    and not blocked_between(viewer, author)
+$$ language sql stable;
+
+-- Whether `user` is left out of what `viewer` is shown, such as their timeline, tags and
+-- notifications: anyone they muted, and anyone blocked either way.
+create function hidden_from(viewer uuid, "user" uuid) returns boolean as $$
+  select exists(select 1 from mutes where mutes.muter_id = viewer and mutes.muted_id = "user")
+    or blocked_between(viewer, "user")
+$$ language sql stable;
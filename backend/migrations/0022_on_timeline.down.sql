@@ -0,0 +1 @@
+drop function on_timeline(uuid, uuid, uuid);
@@ -0,0 +1,12 @@
+-- Whether an event by `author`, reposted by `reposted_by` unless that's null, belongs on
+-- `viewer`'s timeline: it has to come from the viewer or someone they follow, and neither the
+-- author nor the reposter may be hidden from them.
+create function on_timeline(viewer uuid, author uuid, reposted_by uuid) returns boolean as $$
+  select (
+      coalesce(reposted_by, author) = viewer
+      or exists(select 1 from follows where follows.follower_id = viewer and follows.followed_id = coalesce(reposted_by, author))
+    )
+    and visible_to(viewer, author)
+    and not hidden_from(viewer, author)
+    and (reposted_by is null or not hidden_from(viewer, reposted_by))
+$$ language sql stable;
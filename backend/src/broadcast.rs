@@ -0,0 +1,74 @@
+use async_std::channel::{bounded, Receiver, Sender, TrySendError};
+use shared::responses::UserResponse;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+/// How many messages a subscriber may fall behind before it starts missing them.
+const SUBSCRIBER_BUFFER: usize = 64;
+
+/// Fans messages out to every live subscriber, in-process.
+///
+/// Sending never waits on subscribers: a subscriber whose buffer is full misses the message,
+/// and one that has gone away is forgotten on the next send.
+#[derive(Debug, Clone)]
+pub(crate) struct Broadcaster<T> {
+    subscribers: Arc<Mutex<Vec<Sender<T>>>>,
+}
+
+impl<T: Clone> Broadcaster<T> {
+    pub fn new() -> Self {
+        Broadcaster {
+            subscribers: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn subscribe(&self) -> Receiver<T> {
+        let (sender, receiver) = bounded(SUBSCRIBER_BUFFER);
+        self.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    pub fn send(&self, message: T) {
+        self.subscribers.lock().unwrap().retain(|subscriber| {
+            !matches!(subscriber.try_send(message.clone()), Err(TrySendError::Closed(_)))
+        });
+    }
+}
+
+/// Published once an event has been committed by `PostEvent`, or reposted by `Repost`.
+#[derive(Debug, Clone)]
+pub(crate) struct NewEvent {
+    pub event_id: Uuid,
+    /// The event's author.
+    pub user_id: Uuid,
+    pub reposted_by: Option<UserResponse>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[async_std::test]
+    async fn every_subscriber_receives_the_message() {
+        let broadcaster = Broadcaster::new();
+        let first = broadcaster.subscribe();
+        let second = broadcaster.subscribe();
+
+        broadcaster.send(1);
+
+        assert_eq!(first.recv().await.unwrap(), 1);
+        assert_eq!(second.recv().await.unwrap(), 1);
+    }
+
+    #[async_std::test]
+    async fn dropped_subscribers_are_forgotten() {
+        let broadcaster = Broadcaster::new();
+        let first = broadcaster.subscribe();
+        drop(broadcaster.subscribe());
+
+        broadcaster.send(1);
+
+        assert_eq!(broadcaster.subscribers.lock().unwrap().len(), 1);
+        assert_eq!(first.recv().await.unwrap(), 1);
+    }
+}
@@ -0,0 +1,404 @@
+//! Everything the server can be configured with, loaded once at startup. Each setting can
+//! come from, in increasing order of precedence:
+//!
+//! - a TOML file named by `CONFIG_FILE` or `--config`, e.g. `db_pool_size = 20`,
+//! - an environment variable named after it in upper case, e.g. `DB_POOL_SIZE=20`,
+//! - a command line flag, e.g. `--db-pool-size 20` or `--db-pool-size=20`.
+//!
+//! Lists, such as `cors_origins`, are comma separated outside of the TOML file.
+
+use crate::env::Env;
+use crate::rate_limit::{self, Limit};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::net::SocketAddr;
+
+/// A value that's kept out of logs and `Debug` output.
+#[derive(Clone, PartialEq, Deserialize)]
+#[serde(transparent)]
+pub(crate) struct Secret(String);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Secret(..)")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Config {
+    pub app_env: Env,
+    pub database_url: String,
+    /// Keys password hashes and auth token hashes, so changing it signs everyone out.
+    pub secret_key: Secret,
+    pub bind_address: SocketAddr,
+    pub db_pool_size: u32,
+    /// Argon2 passes per password hash; more is slower for attackers and for logins alike.
+    pub hash_iterations: u32,
+    pub auth_token_ttl_days: i64,
+    /// Origins whose pages may call the API, such as `https://example.com`, or `*` for any,
+    /// though only origins listed by name may send credentials. Any origin is allowed by default
+    /// outside of production, and none in production.
+    pub cors_origins: Vec<String>,
+    pub max_event_length: usize,
+    pub max_message_length: usize,
+    pub max_upload_bytes: usize,
+    pub media_root: String,
+    /// Where mail is written when set; otherwise it's only logged.
+    pub mail_dir: Option<String>,
+    /// Off by default in tests, which sign up more users than a real client could.
+    pub rate_limiting: bool,
+    /// Changes to the built in limits, such as `login.ip` to `20/1m`; see `rate_limit`.
+    pub rate_limits: HashMap<String, Limit>,
+    /// Whether to take the client's IP from `Forwarded` or `X-Forwarded-For`, which is only
+    /// safe behind a proxy that sets them.
+    pub trust_forwarded_for: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum ConfigError {
+    #[error("{key} is not set; set {} or pass --{}", .key.to_uppercase(), .key.replace('_', "-"))]
+    Missing { key: &'static str },
+    #[error("invalid {key}: {message}")]
+    Invalid { key: String, message: String },
+    #[error("unknown setting {0}")]
+    Unknown(String),
+    #[error("--{0} needs a value")]
+    MissingFlagValue(String),
+    #[error("couldn't read config file {path}: {message}")]
+    File { path: String, message: String },
+}
+
+/// Turns the text of an environment variable or flag into a setting's value.
+trait Setting: Sized {
+    fn parse(value: &str) -> Result<Self, String>;
+}
+
+macro_rules! impl_setting_from_str {
+    ($($ty:ty),*) => {
+        $(
+            impl Setting for $ty {
+                fn parse(value: &str) -> Result<Self, String> {
+                    value.parse().map_err(|err| format!("{:?}: {}", value, err))
+                }
+            }
+        )*
+    };
+}
+
+impl_setting_from_str!(String, bool, u32, i64, usize, SocketAddr, Env);
+
+impl Setting for Secret {
+    fn parse(value: &str) -> Result<Self, String> {
+        Ok(Secret(value.to_string()))
+    }
+}
+
+impl Setting for Vec<String> {
+    fn parse(value: &str) -> Result<Self, String> {
+        Ok(value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+}
+
+macro_rules! settings {
+    ($($name:ident: $ty:ty,)*) => {
+        /// The settings from one source, with anything it doesn't mention left as `None`.
+        #[derive(Debug, Default, Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Settings {
+            $($name: Option<$ty>,)*
+        }
+
+        impl Settings {
+            const KEYS: &'static [&'static str] = &[$(stringify!($name)),*];
+
+            fn set(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+                let invalid = |message| ConfigError::Invalid { key: key.to_string(), message };
+                match key {
+                    $(stringify!($name) => self.$name = Some(Setting::parse(value).map_err(invalid)?),)*
+                    _ => return Err(ConfigError::Unknown(key.to_string())),
+                }
+                Ok(())
+            }
+
+            /// These settings, with anything that `other` sets taking precedence.
+            fn overridden_by(self, other: Settings) -> Settings {
+                Settings {
+                    $($name: other.$name.or(self.$name),)*
+                }
+            }
+        }
+    };
+}
+
+settings! {
+    app_env: Env,
+    database_url: String,
+    secret_key: Secret,
+    bind_address: SocketAddr,
+    db_pool_size: u32,
+    hash_iterations: u32,
+    auth_token_ttl_days: i64,
+    cors_origins: Vec<String>,
+    max_event_length: usize,
+    max_message_length: usize,
+    max_upload_bytes: usize,
+    media_root: String,
+    mail_dir: String,
+    rate_limiting: bool,
+    rate_limits: Vec<String>,
+    trust_forwarded_for: bool,
+}
+
+/// Splits `--key value` and `--key=value` flags from the rest of the arguments, returning
+/// the config file flag separately since it decides where the other settings come from.
+fn parse_flags(args: Vec<String>) -> Result<(Settings, Option<String>, Vec<String>), ConfigError> {
+    let mut settings = Settings::default();
+    let mut config_file = None;
+    let mut rest = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let flag = match arg.strip_prefix("--") {
+            Some(flag) => flag,
+            None => {
+                rest.push(arg);
+                continue;
+            }
+        };
+
+        let (key, value) = match flag.split_once('=') {
+            Some((key, value)) => (key.to_string(), value.to_string()),
+            None => {
+                let value = args.next().ok_or_else(|| ConfigError::MissingFlagValue(flag.to_string()))?;
+                (flag.to_string(), value)
+            }
+        };
+
+        match key.replace('-', "_").as_str() {
+            "config" => config_file = Some(value),
+            key => settings.set(key, &value)?,
+        }
+    }
+
+    Ok((settings, config_file, rest))
+}
+
+fn read_file(path: &str) -> Result<Settings, ConfigError> {
+    let file_error = |message: String| ConfigError::File { path: path.to_string(), message };
+    let contents = std::fs::read_to_string(path).map_err(|err| file_error(err.to_string()))?;
+    toml::from_str(&contents).map_err(|err| file_error(err.to_string()))
+}
+
+/// An origin is a scheme and host, with an optional port, and nothing after.
+fn is_valid_origin(origin: &str) -> bool {
+    if origin == "*" {
+        return true;
+    }
+
+    match origin.strip_prefix("https://").or_else(|| origin.strip_prefix("http://")) {
+        Some(host) => !host.is_empty() && !host.contains('/'),
+        None => false,
+    }
+}
+
+/// Reads overrides written like `login.ip=20/1m`.
+fn parse_rate_limits(overrides: Vec<String>) -> Result<HashMap<String, Limit>, ConfigError> {
+    let names = rate_limit::override_names(&rate_limit::default_rules());
+    let invalid = |message: String| ConfigError::Invalid { key: "rate_limits".to_string(), message };
+
+    overrides
+        .iter()
+        .map(|item| {
+            let (name, limit) = item
+                .split_once('=')
+                .ok_or_else(|| invalid(format!("{:?} should look like login.ip=5/1m", item)))?;
+            let name = name.trim();
+            if !names.iter().any(|known| known == name) {
+                return Err(invalid(format!("unknown limit {:?}, expected one of {}", name, names.join(", "))));
+            }
+            Ok((name.to_string(), limit.parse().map_err(invalid)?))
+        })
+        .collect()
+}
+
+impl Config {
+    /// Loads the configuration from the process's environment and `args`, the command line
+    /// without the program name. Returns the arguments that weren't flags.
+    pub fn load(args: Vec<String>) -> Result<(Config, Vec<String>), ConfigError> {
+        Config::from_sources(args, |name| std::env::var(name).ok())
+    }
+
+    fn from_sources(args: Vec<String>, env_var: impl Fn(&str) -> Option<String>) -> Result<(Config, Vec<String>), ConfigError> {
+        let (flags, config_file, rest) = parse_flags(args)?;
+
+        let file = match config_file.or_else(|| env_var("CONFIG_FILE")) {
+            Some(path) => read_file(&path)?,
+            None => Settings::default(),
+        };
+
+        let mut env = Settings::default();
+        for key in Settings::KEYS {
+            if let Some(value) = env_var(&key.to_uppercase()) {
+                env.set(key, &value)?;
+            }
+        }
+
+        let config = Config::validate(file.overridden_by(env).overridden_by(flags))?;
+        Ok((config, rest))
+    }
+
+    fn validate(settings: Settings) -> Result<Config, ConfigError> {
+        let app_env = settings.app_env.unwrap_or(Env::Development);
+
+        let config = Config {
+            app_env,
+            database_url: settings.database_url.ok_or(ConfigError::Missing { key: "database_url" })?,
+            secret_key: settings.secret_key.ok_or(ConfigError::Missing { key: "secret_key" })?,
+            bind_address: settings.bind_address.unwrap_or_else(|| SocketAddr::from(([127, 0, 0, 1], 8080))),
+            db_pool_size: settings.db_pool_size.unwrap_or(10),
+            // Hashing at full strength would make the tests crawl.
+            hash_iterations: settings.hash_iterations.unwrap_or(if app_env.is_test() { 10 } else { 192 }),
+            auth_token_ttl_days: settings.auth_token_ttl_days.unwrap_or(30),
+            cors_origins: settings.cors_origins.unwrap_or_else(|| match app_env {
+                Env::Production => Vec::new(),
+                Env::Development | Env::Test => vec!["*".to_string()],
+            }),
+            max_event_length: settings.max_event_length.unwrap_or(200),
+            max_message_length: settings.max_message_length.unwrap_or(1000),
+            max_upload_bytes: settings.max_upload_bytes.unwrap_or(5 * 1024 * 1024),
+            media_root: settings.media_root.unwrap_or_else(|| "media".to_string()),
+            mail_dir: settings.mail_dir,
+            rate_limiting: settings.rate_limiting.unwrap_or(!app_env.is_test()),
+            rate_limits: parse_rate_limits(settings.rate_limits.unwrap_or_default())?,
+            trust_forwarded_for: settings.trust_forwarded_for.unwrap_or(false),
+        };
+
+        let invalid = |key: &str, message: &str| Err(ConfigError::Invalid { key: key.to_string(), message: message.to_string() });
+
+        if config.secret_key.expose().is_empty() {
+            return invalid("secret_key", "cannot be empty");
+        }
+        // Migrating holds one connection for a lock while working on another.
+        if config.db_pool_size < 2 {
+            return invalid("db_pool_size", "must be at least 2");
+        }
+        if config.hash_iterations == 0 {
+            return invalid("hash_iterations", "must be at least 1");
+        }
+        if config.auth_token_ttl_days < 1 {
+            return invalid("auth_token_ttl_days", "must be at least 1");
+        }
+        if config.max_event_length == 0 || config.max_message_length == 0 || config.max_upload_bytes == 0 {
+            return invalid("content limits", "must be at least 1");
+        }
+        if let Some(origin) = config.cors_origins.iter().find(|origin| !is_valid_origin(origin)) {
+            return invalid("cors_origins", &format!("{:?} should look like https://example.com", origin));
+        }
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn load(args: &[&str], env: &[(&str, &str)]) -> Result<(Config, Vec<String>), ConfigError> {
+        let env: HashMap<String, String> = env.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        Config::from_sources(
+            args.iter().map(|arg| arg.to_string()).collect(),
+            |name| env.get(name).cloned(),
+        )
+    }
+
+    const REQUIRED: &[(&str, &str)] = &[("DATABASE_URL", "postgres://localhost/app"), ("SECRET_KEY", "secret")];
+
+    #[test]
+    fn defaults() {
+        let (config, rest) = load(&[], REQUIRED).unwrap();
+
+        assert_eq!(config.app_env, Env::Development);
+        assert_eq!(config.bind_address.to_string(), "127.0.0.1:8080");
+        assert_eq!(config.hash_iterations, 192);
+        assert_eq!(config.cors_origins, vec!["*"]);
+        assert!(rest.is_empty());
+        assert!(!format!("{:?}", config).contains("secret\""));
+    }
+
+    #[test]
+    fn flags_override_env_which_overrides_the_file() {
+        let path = std::env::temp_dir().join(format!("config-test-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "db_pool_size = 4\nmax_event_length = 300\ncors_origins = [\"https://a.example\"]\n").unwrap();
+
+        let mut env = REQUIRED.to_vec();
+        env.push(("DB_POOL_SIZE", "8"));
+        env.push(("CORS_ORIGINS", "https://b.example, https://c.example"));
+
+        let args = ["migrate", "--config", path.to_str().unwrap(), "--db-pool-size=16", "down"];
+        let (config, rest) = load(&args, &env).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(config.db_pool_size, 16);
+        assert_eq!(config.max_event_length, 300);
+        assert_eq!(config.cors_origins, vec!["https://b.example", "https://c.example"]);
+        assert_eq!(rest, vec!["migrate", "down"]);
+    }
+
+    #[test]
+    fn invalid_configs() {
+        let message = |args: &[&str], env: &[(&str, &str)]| load(args, env).unwrap_err().to_string();
+
+        assert_eq!(message(&[], &[("SECRET_KEY", "secret")]), "database_url is not set; set DATABASE_URL or pass --database-url");
+        assert_eq!(message(&["--app-env", "staging"], REQUIRED), "invalid app_env: \"staging\": expected production, development or test");
+        assert!(message(&["--bind-address", "8080"], REQUIRED).starts_with("invalid bind_address"));
+        assert_eq!(message(&["--db-pool-size", "1"], REQUIRED), "invalid db_pool_size: must be at least 2");
+        assert_eq!(message(&["--cors-origins", "example.com"], REQUIRED), "invalid cors_origins: \"example.com\" should look like https://example.com");
+        assert!(message(&["--cors-origins", "https://example.com/app"], REQUIRED).starts_with("invalid cors_origins"));
+        assert_eq!(message(&["--colour", "blue"], REQUIRED), "unknown setting colour");
+        assert_eq!(message(&["--db-pool-size"], REQUIRED), "--db-pool-size needs a value");
+        assert!(message(&["--config", "/nonexistent.toml"], REQUIRED).starts_with("couldn't read config file /nonexistent.toml"));
+    }
+
+    #[test]
+    fn production_allows_no_origins_by_default() {
+        let mut env = REQUIRED.to_vec();
+        env.push(("APP_ENV", "production"));
+
+        let (config, _) = load(&[], &env).unwrap();
+        assert!(config.cors_origins.is_empty());
+    }
+
+    #[test]
+    fn tests_hash_cheaply() {
+        let mut env = REQUIRED.to_vec();
+        env.push(("APP_ENV", "test"));
+
+        let (config, _) = load(&[], &env).unwrap();
+        assert_eq!(config.hash_iterations, 10);
+        assert!(!config.rate_limiting);
+    }
+
+    #[test]
+    fn rate_limit_overrides() {
+        let (config, _) = load(&["--rate-limits", "login.ip=20/1m, post_event.user=5/h"], REQUIRED).unwrap();
+        assert!(config.rate_limiting);
+        assert_eq!(config.rate_limits["login.ip"], Limit::new(20, std::time::Duration::from_secs(60)));
+        assert_eq!(config.rate_limits["post_event.user"], Limit::new(5, std::time::Duration::from_secs(3600)));
+
+        let message = load(&["--rate-limits", "logon.ip=20/1m"], REQUIRED).unwrap_err().to_string();
+        assert!(message.starts_with("invalid rate_limits: unknown limit \"logon.ip\""), "{}", message);
+        assert!(load(&["--rate-limits", "login.ip=lots"], REQUIRED).is_err());
+    }
+}
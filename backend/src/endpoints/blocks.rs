@@ -0,0 +1,200 @@
+use crate::BackendApiEndpoint;
+use tide::Request;
+use tide::http::StatusCode;
+use shared::{ApiEndpoint, BlockUser, UnblockUser, MuteUser, UnmuteUser, ListBlocks, ListMutes, NoPayload};
+use shared::errors::ApiError;
+use shared::responses::UserResponse;
+use crate::State;
+use crate::endpoints::{api_error, authenticate};
+use crate::endpoints::users::{relationship, user_id_for_username};
+use chrono::Utc;
+use uuid::Uuid;
+use sqlx::{query, query_as};
+use async_trait::async_trait;
+
+#[async_trait]
+impl BackendApiEndpoint for BlockUser {
+    async fn handler(req: Request<State>, _: NoPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+        let current_user = authenticate(&req).await?;
+        let username = req.param("username")?;
+
+        let blocked_id = user_id_for_username(username, db_pool).await?;
+
+        if current_user.id == blocked_id {
+            return Err(api_error(ApiError::CannotTargetSelf { action: "block".to_string() }));
+        }
+
+        let now = Utc::now();
+        let mut tx = db_pool.begin().await?;
+
+        let pg_res = query!(
+            r#"
+                insert into blocks (id, blocker_id, blocked_id, created_at, updated_at)
+                values ($1, $2, $3, $4, $5)
+                on conflict (blocker_id, blocked_id) do nothing
+            "#,
+            Uuid::new_v4(),
+            current_user.id,
+            blocked_id,
+            now,
+            now,
+        ).execute(&mut tx).await?;
+
+        // Blocking cuts ties both ways.
+        query!(
+            r#"
+                delete from follows
+                where (follower_id = $1 and followed_id = $2)
+                    or (follower_id = $2 and followed_id = $1)
+            "#,
+            current_user.id,
+            blocked_id,
+        ).execute(&mut tx).await?;
+
+        query!(
+            r#"
+                delete from follow_requests
+                where (requester_id = $1 and requested_id = $2)
+                    or (requester_id = $2 and requested_id = $1)
+            "#,
+            current_user.id,
+            blocked_id,
+        ).execute(&mut tx).await?;
+
+        tx.commit().await?;
+
+        let status = if pg_res.rows_affected() == 1 {
+            StatusCode::Created
+        } else {
+            StatusCode::Ok
+        };
+
+        let relationship = relationship(current_user.id, blocked_id, username, db_pool).await?;
+        Ok((relationship, status))
+    }
+}
+
+#[async_trait]
+impl BackendApiEndpoint for UnblockUser {
+    async fn handler(req: Request<State>, _: NoPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+        let current_user = authenticate(&req).await?;
+        let username = req.param("username")?;
+
+        let blocked_id = user_id_for_username(username, db_pool).await?;
+
+        query!(
+            "delete from blocks where blocker_id = $1 and blocked_id = $2",
+            current_user.id,
+            blocked_id,
+        ).execute(db_pool).await?;
+
+        let relationship = relationship(current_user.id, blocked_id, username, db_pool).await?;
+        Ok((relationship, StatusCode::Ok))
+    }
+}
+
+#[async_trait]
+impl BackendApiEndpoint for MuteUser {
+    async fn handler(req: Request<State>, _: NoPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+        let current_user = authenticate(&req).await?;
+        let username = req.param("username")?;
+
+        let muted_id = user_id_for_username(username, db_pool).await?;
+
+        if current_user.id == muted_id {
+            return Err(api_error(ApiError::CannotTargetSelf { action: "mute".to_string() }));
+        }
+
+        let now = Utc::now();
+        let pg_res = query!(
+            r#"
+                insert into mutes (id, muter_id, muted_id, created_at, updated_at)
+                values ($1, $2, $3, $4, $5)
+                on conflict (muter_id, muted_id) do nothing
+            "#,
+            Uuid::new_v4(),
+            current_user.id,
+            muted_id,
+            now,
+            now,
+        ).execute(db_pool).await?;
+
+        let status = if pg_res.rows_affected() == 1 {
+            StatusCode::Created
+        } else {
+            StatusCode::Ok
+        };
+
+        let relationship = relationship(current_user.id, muted_id, username, db_pool).await?;
+        Ok((relationship, status))
+    }
+}
+
+#[async_trait]
+impl BackendApiEndpoint for UnmuteUser {
+    async fn handler(req: Request<State>, _: NoPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+        let current_user = authenticate(&req).await?;
+        let username = req.param("username")?;
+
+        let muted_id = user_id_for_username(username, db_pool).await?;
+
+        query!(
+            "delete from mutes where muter_id = $1 and muted_id = $2",
+            current_user.id,
+            muted_id,
+        ).execute(db_pool).await?;
+
+        let relationship = relationship(current_user.id, muted_id, username, db_pool).await?;
+        Ok((relationship, StatusCode::Ok))
+    }
+}
+
+#[async_trait]
+impl BackendApiEndpoint for ListBlocks {
+    async fn handler(req: Request<State>, _: NoPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+        let current_user = authenticate(&req).await?;
+
+        let users = query_as!(UserResponse,
+            r#"
+                select users.id, users.username
+                from blocks
+                inner join users on users.id = blocks.blocked_id
+                where blocks.blocker_id = $1
+                order by blocks.created_at desc
+            "#,
+            current_user.id,
+        )
+        .fetch_all(db_pool)
+        .await?;
+
+        Ok((users, StatusCode::Ok))
+    }
+}
+
+#[async_trait]
+impl BackendApiEndpoint for ListMutes {
+    async fn handler(req: Request<State>, _: NoPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+        let current_user = authenticate(&req).await?;
+
+        let users = query_as!(UserResponse,
+            r#"
+                select users.id, users.username
+                from mutes
+                inner join users on users.id = mutes.muted_id
+                where mutes.muter_id = $1
+                order by mutes.created_at desc
+            "#,
+            current_user.id,
+        )
+        .fetch_all(db_pool)
+        .await?;
+
+        Ok((users, StatusCode::Ok))
+    }
+}
@@ -0,0 +1,417 @@
+use crate::BackendApiEndpoint;
+use tide::Request;
+use tide::http::StatusCode;
+use shared::{ApiEndpoint, ListConversations, CreateConversation, ListMessages, SendMessage, NoPayload};
+use shared::payloads::{CreateConversationPayload, SendMessagePayload};
+use shared::errors::{ApiError, Resource};
+use shared::responses::{ConversationResponse, MessageResponse, PaginatedResponse, UserResponse};
+use crate::State;
+use crate::endpoints::{api_error, authenticate};
+use crate::endpoints::pagination::{Cursor, Pagination};
+use crate::endpoints::users::{blocked_between, user_id_for_username};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+use sqlx::{query, query_as, PgPool};
+use async_trait::async_trait;
+use std::collections::HashMap;
+
+/// The most people, including whoever starts it, that can be in one conversation.
+const MAX_CONVERSATION_MEMBERS: usize = 10;
+
+struct MessageRow {
+    id: Uuid,
+    conversation_id: Uuid,
+    content: String,
+    created_at: DateTime<Utc>,
+    user_id: Uuid,
+    user_username: String,
+}
+
+impl From<MessageRow> for MessageResponse {
+    fn from(row: MessageRow) -> Self {
+        MessageResponse {
+            id: row.id,
+            conversation_id: row.conversation_id,
+            user: UserResponse {
+                id: row.user_id,
+                username: row.user_username,
+            },
+            content: row.content,
+            created_at: row.created_at,
+        }
+    }
+}
+
+#[async_trait]
+impl BackendApiEndpoint for ListConversations {
+    async fn handler(req: Request<State>, _: NoPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+        let current_user = authenticate(&req).await?;
+
+        let pagination = req.query::<Pagination>()?;
+        let before = pagination.before()?;
+        let after = pagination.after()?;
+
+        let rows = query!(
+            r#"
+                select conversations.id, conversations.last_message_at
+                from conversations
+                inner join conversation_members
+                    on conversation_members.conversation_id = conversations.id
+                    and conversation_members.user_id = $1
+                where ($4::timestamptz is null or (conversations.last_message_at, conversations.id) < ($4, $5))
+                    and ($6::timestamptz is null or (conversations.last_message_at, conversations.id) > ($6, $7))
+                order by
+                    case when $6::timestamptz is not null then conversations.last_message_at end asc
+                    , case when $6::timestamptz is not null then conversations.id end asc
+                    , conversations.last_message_at desc
+                    , conversations.id desc
+                limit $2
+                offset $3
+            "#,
+            current_user.id,
+            pagination.limit(),
+            pagination.offset(),
+            before.map(|cursor| cursor.at),
+            before.map(|cursor| cursor.id),
+            after.map(|cursor| cursor.at),
+            after.map(|cursor| cursor.id),
+        )
+        .fetch_all(db_pool)
+        .await?;
+
+        let (rows, next_cursor) = pagination.paginate(rows, |row| Cursor { at: row.last_message_at, id: row.id });
+        let ids = rows.iter().map(|row| row.id).collect::<Vec<_>>();
+
+        let mut members = conversation_members(&ids, db_pool).await?;
+        let mut last_messages = query_as!(MessageRow,
+            r#"
+                select distinct on (messages.conversation_id)
+                    messages.id
+                    , messages.conversation_id
+                    , messages.content
+                    , messages.created_at
+                    , users.id as user_id
+                    , users.username as user_username
+                from messages
+                inner join users on users.id = messages.user_id
+                where messages.conversation_id = any($1)
+                    and not blocked_between($2, messages.user_id)
+                order by messages.conversation_id, messages.created_at desc, messages.id desc
+            "#,
+            &ids,
+            current_user.id,
+        )
+        .fetch_all(db_pool)
+        .await?
+        .into_iter()
+        .map(|row| (row.conversation_id, MessageResponse::from(row)))
+        .collect::<HashMap<_, _>>();
+
+        let items = rows
+            .into_iter()
+            .map(|row| ConversationResponse {
+                id: row.id,
+                members: members.remove(&row.id).unwrap_or_default(),
+                last_message: last_messages.remove(&row.id),
+                last_message_at: row.last_message_at,
+            })
+            .collect();
+
+        Ok((PaginatedResponse { items, next_cursor }, StatusCode::Ok))
+    }
+}
+
+#[async_trait]
+impl BackendApiEndpoint for CreateConversation {
+    async fn handler(req: Request<State>, payload: CreateConversationPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+        let current_user = authenticate(&req).await?;
+
+        let mut other_ids: Vec<Uuid> = Vec::new();
+        for username in &payload.usernames {
+            let user_id = user_id_for_username(username, db_pool).await?;
+            if user_id != current_user.id && !other_ids.contains(&user_id) {
+                other_ids.push(user_id);
+            }
+        }
+
+        if other_ids.is_empty() {
+            return Err(api_error(ApiError::TooFewMembers));
+        }
+        if other_ids.len() + 1 > MAX_CONVERSATION_MEMBERS {
+            return Err(api_error(ApiError::TooManyMembers { max: MAX_CONVERSATION_MEMBERS }));
+        }
+        if blocked_between(current_user.id, &other_ids, db_pool).await? {
+            return Err(api_error(ApiError::Blocked { action: "start a conversation with this user".to_string() }));
+        }
+
+        let mut member_ids = other_ids;
+        member_ids.push(current_user.id);
+
+        // Starting a conversation with exactly the same people again picks up where it left off.
+        let existing = query!(
+            r#"
+                select conversation_id
+                from conversation_members
+                group by conversation_id
+                having array_agg(user_id) @> $1 and array_agg(user_id) <@ $1
+            "#,
+            &member_ids,
+        )
+        .fetch_optional(db_pool)
+        .await?;
+
+        if let Some(existing) = existing {
+            let conversation = fetch_conversation(existing.conversation_id, current_user.id, db_pool).await?;
+            return Ok((conversation, StatusCode::Ok));
+        }
+
+        let now = Utc::now();
+        let conversation_id = Uuid::new_v4();
+        let mut tx = db_pool.begin().await?;
+
+        query!(
+            r#"
+                insert into conversations (id, last_message_at, created_at, updated_at)
+                values ($1, $2, $3, $4)
+            "#,
+            conversation_id,
+            now,
+            now,
+            now,
+        ).execute(&mut tx).await?;
+
+        for user_id in &member_ids {
+            query!(
+                r#"
+                    insert into conversation_members (id, conversation_id, user_id, created_at, updated_at)
+                    values ($1, $2, $3, $4, $5)
+                "#,
+                Uuid::new_v4(),
+                conversation_id,
+                user_id,
+                now,
+                now,
+            ).execute(&mut tx).await?;
+        }
+
+        tx.commit().await?;
+
+        let conversation = fetch_conversation(conversation_id, current_user.id, db_pool).await?;
+        Ok((conversation, StatusCode::Created))
+    }
+}
+
+#[async_trait]
+impl BackendApiEndpoint for ListMessages {
+    async fn handler(req: Request<State>, _: NoPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+        let current_user = authenticate(&req).await?;
+        let conversation_id = conversation_id_param(&req)?;
+
+        ensure_member(conversation_id, current_user.id, db_pool).await?;
+
+        let pagination = req.query::<Pagination>()?;
+        let before = pagination.before()?;
+        let after = pagination.after()?;
+
+        let rows = query_as!(MessageRow,
+            r#"
+                select
+                    messages.id
+                    , messages.conversation_id
+                    , messages.content
+                    , messages.created_at
+                    , users.id as user_id
+                    , users.username as user_username
+                from messages
+                inner join users on users.id = messages.user_id
+                where messages.conversation_id = $1
+                    and not blocked_between($8, messages.user_id)
+                    and ($4::timestamptz is null or (messages.created_at, messages.id) < ($4, $5))
+                    and ($6::timestamptz is null or (messages.created_at, messages.id) > ($6, $7))
+                order by
+                    case when $6::timestamptz is not null then messages.created_at end asc
+                    , case when $6::timestamptz is not null then messages.id end asc
+                    , messages.created_at desc
+                    , messages.id desc
+                limit $2
+                offset $3
+            "#,
+            conversation_id,
+            pagination.limit(),
+            pagination.offset(),
+            before.map(|cursor| cursor.at),
+            before.map(|cursor| cursor.id),
+            after.map(|cursor| cursor.at),
+            after.map(|cursor| cursor.id),
+            current_user.id,
+        )
+        .fetch_all(db_pool)
+        .await?;
+
+        let (rows, next_cursor) = pagination.paginate(rows, |row| Cursor { at: row.created_at, id: row.id });
+        let items = rows.into_iter().map(MessageResponse::from).collect();
+
+        Ok((PaginatedResponse { items, next_cursor }, StatusCode::Ok))
+    }
+}
+
+#[async_trait]
+impl BackendApiEndpoint for SendMessage {
+    async fn handler(req: Request<State>, payload: SendMessagePayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+
+        if payload.content.chars().count() > req.state().config.max_message_length {
+            return Err(api_error(ApiError::ContentTooLong { field: "content".to_string(), max_length: req.state().config.max_message_length }));
+        }
+
+        let current_user = authenticate(&req).await?;
+        let conversation_id = conversation_id_param(&req)?;
+
+        ensure_member(conversation_id, current_user.id, db_pool).await?;
+
+        // Blocking someone after the conversation started also stops messages to them.
+        let other_ids = query!(
+            "select user_id from conversation_members where conversation_id = $1 and user_id <> $2",
+            conversation_id,
+            current_user.id,
+        )
+        .fetch_all(db_pool)
+        .await?
+        .into_iter()
+        .map(|row| row.user_id)
+        .collect::<Vec<_>>();
+
+        if blocked_between(current_user.id, &other_ids, db_pool).await? {
+            return Err(api_error(ApiError::Blocked { action: "send messages to this conversation".to_string() }));
+        }
+
+        let now = Utc::now();
+        let mut tx = db_pool.begin().await?;
+
+        let row = query!(
+            r#"
+                insert into messages (id, conversation_id, user_id, content, created_at, updated_at)
+                values ($1, $2, $3, $4, $5, $6)
+                returning id, content, created_at
+            "#,
+            Uuid::new_v4(),
+            conversation_id,
+            current_user.id,
+            payload.content,
+            now,
+            now,
+        ).fetch_one(&mut tx).await?;
+
+        query!(
+            "update conversations set last_message_at = $1, updated_at = $1 where id = $2",
+            now,
+            conversation_id,
+        ).execute(&mut tx).await?;
+
+        tx.commit().await?;
+
+        let message = MessageResponse {
+            id: row.id,
+            conversation_id,
+            user: current_user,
+            content: row.content,
+            created_at: row.created_at,
+        };
+        Ok((message, StatusCode::Created))
+    }
+}
+
+/// The conversation as `viewer_id` sees it, leaving out messages from anyone blocked either way.
+async fn fetch_conversation(conversation_id: Uuid, viewer_id: Uuid, db_pool: &PgPool) -> tide::Result<ConversationResponse> {
+    let row = query!("select id, last_message_at from conversations where id = $1", conversation_id)
+        .fetch_one(db_pool)
+        .await?;
+
+    let last_message = query_as!(MessageRow,
+        r#"
+            select
+                messages.id
+                , messages.conversation_id
+                , messages.content
+                , messages.created_at
+                , users.id as user_id
+                , users.username as user_username
+            from messages
+            inner join users on users.id = messages.user_id
+            where messages.conversation_id = $1
+                and not blocked_between($2, messages.user_id)
+            order by messages.created_at desc, messages.id desc
+            limit 1
+        "#,
+        conversation_id,
+        viewer_id,
+    )
+    .fetch_optional(db_pool)
+    .await?;
+
+    Ok(ConversationResponse {
+        id: row.id,
+        members: conversation_members(&[conversation_id], db_pool)
+            .await?
+            .remove(&conversation_id)
+            .unwrap_or_default(),
+        last_message: last_message.map(MessageResponse::from),
+        last_message_at: row.last_message_at,
+    })
+}
+
+async fn conversation_members(conversation_ids: &[Uuid], db_pool: &PgPool) -> tide::Result<HashMap<Uuid, Vec<UserResponse>>> {
+    let rows = query!(
+        r#"
+            select conversation_members.conversation_id, users.id, users.username
+            from conversation_members
+            inner join users on users.id = conversation_members.user_id
+            where conversation_members.conversation_id = any($1)
+            order by users.username
+        "#,
+        conversation_ids,
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    let mut members: HashMap<Uuid, Vec<UserResponse>> = HashMap::new();
+    for row in rows {
+        members
+            .entry(row.conversation_id)
+            .or_default()
+            .push(UserResponse { id: row.id, username: row.username });
+    }
+    Ok(members)
+}
+
+/// Only members may read or post to a conversation.
+async fn ensure_member(conversation_id: Uuid, user_id: Uuid, db_pool: &PgPool) -> tide::Result<()> {
+    let row = query!(
+        r#"
+            select exists(
+                select 1 from conversation_members where conversation_id = $1 and user_id = $2
+            ) as "member!"
+            from conversations
+            where id = $1
+        "#,
+        conversation_id,
+        user_id,
+    )
+    .fetch_optional(db_pool)
+    .await?;
+
+    match row {
+        Some(row) if row.member => Ok(()),
+        Some(_) => Err(api_error(ApiError::NotConversationMember)),
+        None => Err(api_error(ApiError::NotFound { resource: Resource::Conversation })),
+    }
+}
+
+fn conversation_id_param(req: &Request<State>) -> tide::Result<Uuid> {
+    req.param("id")?
+        .parse()
+        .map_err(|_| api_error(ApiError::NotFound { resource: Resource::Conversation }))
+}
@@ -1,46 +1,830 @@
 use crate::BackendApiEndpoint;
 use tide::Request;
-use shared::payloads::CreateEventPayload;
-use shared::responses::PostEventResponse;
-use shared::{ApiEndpoint, PostEvent};
+use shared::errors::{ApiError, Resource};
+use shared::payloads::{CreateEventPayload, EditEventPayload};
+use shared::responses::{NotificationKind, PostEventResponse, EventResponse, UserResponse, ThreadResponse, ThreadReplyResponse, LikeResponse, RepostResponse, EventRevisionResponse};
+use shared::{ApiEndpoint, PostEvent, EditEvent, DeleteEvent, GetEventRevisions, GetThread, LikeEvent, UnlikeEvent, Repost, Unrepost, NoPayload};
 
 
 use crate::State;
-use crate::endpoints::authenticate;
+use crate::broadcast::NewEvent;
+use crate::endpoints::{api_error, authenticate, authenticate_optional};
+use crate::endpoints::notifications::{notify, notify_author};
+use crate::endpoints::media::{attach_media, delete_files, delete_media, media_for_events, MAX_MEDIA_PER_EVENT};
 use tide::http::StatusCode;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use uuid::Uuid;
-use sqlx::query;
+use sqlx::{query, query_as, PgPool, Postgres, Transaction};
 use async_trait::async_trait;
+use std::collections::{HashMap, HashSet};
+use lazy_static::lazy_static;
+use regex::Regex;
 
+/// An event joined with its author, as selected by `event_rows`.
+#[derive(Debug)]
+struct EventRow {
+    pub id: Uuid,
+    pub content: String,
+    pub tags: Vec<String>,
+    /// Ids and usernames of the mentioned users, in the same order.
+    pub mention_ids: Vec<Uuid>,
+    pub mention_usernames: Vec<String>,
+    pub in_reply_to: Option<Uuid>,
+    pub quote_of: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub edited_at: Option<DateTime<Utc>>,
+    pub reply_count: i64,
+    pub like_count: i64,
+    pub liked_by_me: bool,
+    pub repost_count: i64,
+    pub reposted_by_me: bool,
+    pub user_id: Uuid,
+    pub user_username: String,
+}
+
+/// An event's place in a listing. Listings only select these, and leave loading the events
+/// themselves to `listed_event_responses`.
+#[derive(Debug)]
+pub(crate) struct ListedEvent {
+    pub id: Uuid,
+    /// When the event appeared in the listing: when it was posted, or when the repost that
+    /// surfaced it was made. Listings are ordered and paginated on this.
+    pub shown_at: DateTime<Utc>,
+    pub reposted_by_id: Option<Uuid>,
+    pub reposted_by_username: Option<String>,
+}
+
+impl From<EventRow> for EventResponse {
+    fn from(row: EventRow) -> Self {
+        EventResponse {
+            id: row.id,
+            content: row.content,
+            tags: row.tags,
+            mentions: row
+                .mention_ids
+                .into_iter()
+                .zip(row.mention_usernames)
+                .map(|(id, username)| UserResponse { id, username })
+                .collect(),
+            created_at: row.created_at,
+            edited_at: row.edited_at,
+            user: UserResponse {
+                id: row.user_id,
+                username: row.user_username,
+            },
+            in_reply_to: row.in_reply_to,
+            reply_count: row.reply_count,
+            like_count: row.like_count,
+            liked_by_me: row.liked_by_me,
+            repost_count: row.repost_count,
+            reposted_by_me: row.reposted_by_me,
+            reposted_by: None,
+            quoted_event: None,
+            media: vec![],
+        }
+    }
+}
+
+/// Converts rows into responses, filling in the event each quote-post refers to and the media
+/// attached to both.
+async fn event_responses(rows: Vec<EventRow>, viewer_id: Option<Uuid>, db_pool: &PgPool) -> tide::Result<Vec<EventResponse>> {
+    let quoted_ids = rows.iter().filter_map(|row| row.quote_of).collect::<Vec<_>>();
+    let event_ids = rows
+        .iter()
+        .map(|row| row.id)
+        .chain(quoted_ids.iter().copied())
+        .collect::<Vec<_>>();
+    let media = media_for_events(&event_ids, db_pool).await?;
+
+    let mut quoted: HashMap<Uuid, EventResponse> = HashMap::new();
+    if !quoted_ids.is_empty() {
+        let quoted_rows = event_rows(&quoted_ids, viewer_id, db_pool).await?;
+
+        for row in quoted_rows {
+            let mut event = EventResponse::from(row);
+            event.media = media.get(&event.id).cloned().unwrap_or_default();
+            quoted.insert(event.id, event);
+        }
+    }
+
+    let responses = rows
+        .into_iter()
+        .map(|row| {
+            let quote_of = row.quote_of;
+            let mut event = EventResponse::from(row);
+            event.media = media.get(&event.id).cloned().unwrap_or_default();
+            event.quoted_event = quote_of
+                .and_then(|id| quoted.get(&id).cloned())
+                .map(Box::new);
+            event
+        })
+        .collect();
+
+    Ok(responses)
+}
 
 #[async_trait]
 impl BackendApiEndpoint for PostEvent {
     async fn handler(req: Request<State>, create_event: CreateEventPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
         let db_pool = &req.state().db_pool;
 
-        if create_event.content.len() > 200 {
-            return Err(tide::Error::from_str(StatusCode::Conflict, "content too long"));
+        if create_event.content.chars().count() > req.state().config.max_event_length {
+            return Err(api_error(ApiError::ContentTooLong { field: "content".to_string(), max_length: req.state().config.max_event_length }));
+        }
+
+        let mut media_ids = create_event.media_ids.clone();
+        let mut seen = HashSet::new();
+        media_ids.retain(|id| seen.insert(*id));
+        if media_ids.len() > MAX_MEDIA_PER_EVENT {
+            return Err(api_error(ApiError::TooManyAttachments { max: MAX_MEDIA_PER_EVENT }));
         }
 
         let user = authenticate(&req).await?;
 
+        if let Some(in_reply_to) = create_event.in_reply_to {
+            ensure_event_visible(in_reply_to, Some(user.id), db_pool).await?;
+        }
+
+        if let Some(quote_of) = create_event.quote_of {
+            let quoted = query!(
+                r#"
+                    select users.id as user_id, users.is_private
+                    from events
+                    inner join users on users.id = events.user_id
+                    where events.id = $1
+                        and visible_to($2, users.id)
+                "#,
+                quote_of,
+                user.id,
+            )
+            .fetch_optional(db_pool)
+            .await?
+            .ok_or_else(|| api_error(ApiError::NotFound { resource: Resource::Event }))?;
+
+            // Quoting would show a private account's event to everyone who can see the quote.
+            if quoted.is_private && quoted.user_id != user.id {
+                return Err(api_error(ApiError::PrivateEvent { action: "quote".to_string() }));
+            }
+        }
+
         let now = Utc::now();
+        let mut tx = db_pool.begin().await?;
         let row = query!(
             r#"
-                insert into events (id, user_id, content, created_at, updated_at)
-                values ($1, $2, $3, $4, $5) returning id, content 
+                insert into events (id, user_id, content, in_reply_to, quote_of, created_at, updated_at)
+                values ($1, $2, $3, $4, $5, $6, $7) returning id, content 
             "#,
             Uuid::new_v4(),
             user.id,
             create_event.content,
+            create_event.in_reply_to,
+            create_event.quote_of,
             now,
             now,
-        ).fetch_one(db_pool).await?;
+        ).fetch_one(&mut tx).await?;
+
+        save_tags(row.id, &row.content, &mut tx).await?;
+        save_mentions(row.id, user.id, &row.content, &mut tx).await?;
+        attach_media(row.id, user.id, &media_ids, &mut tx).await?;
+        if let Some(in_reply_to) = create_event.in_reply_to {
+            notify_author(&mut tx, in_reply_to, user.id, NotificationKind::Reply, row.id).await?;
+        }
+        tx.commit().await?;
+
+        req.state().new_events.send(NewEvent { event_id: row.id, user_id: user.id, reposted_by: None });
 
         Ok((PostEventResponse{
             id: Some(row.id),
             content: Some(row.content)
         }, StatusCode::Created))
     }
-}
\ No newline at end of file
+}
+
+#[async_trait]
+impl BackendApiEndpoint for EditEvent {
+    async fn handler(req: Request<State>, edit_event: EditEventPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+
+        if edit_event.content.chars().count() > req.state().config.max_event_length {
+            return Err(api_error(ApiError::ContentTooLong { field: "content".to_string(), max_length: req.state().config.max_event_length }));
+        }
+
+        let current_user = authenticate(&req).await?;
+        let event_id = event_id_param(&req)?;
+
+        ensure_event_owner(event_id, current_user.id, db_pool).await?;
+
+        let mut tx = db_pool.begin().await?;
+
+        // Keep the version being replaced, timestamped with when it was written.
+        query!(
+            r#"
+                insert into event_revisions (id, event_id, content, created_at, updated_at)
+                select $1, id, content, coalesce(edited_at, created_at), now()
+                from events
+                where id = $2
+            "#,
+            Uuid::new_v4(),
+            event_id,
+        ).execute(&mut tx).await?;
+
+        let now = Utc::now();
+        query!(
+            "update events set content = $1, edited_at = $2, updated_at = $2 where id = $3",
+            edit_event.content,
+            now,
+            event_id,
+        ).execute(&mut tx).await?;
+
+        query!("delete from event_tags where event_id = $1", event_id)
+            .execute(&mut tx)
+            .await?;
+        save_tags(event_id, &edit_event.content, &mut tx).await?;
+        save_mentions(event_id, current_user.id, &edit_event.content, &mut tx).await?;
+
+        tx.commit().await?;
+
+        let event = fetch_event(event_id, Some(current_user.id), db_pool).await?;
+        Ok((event, StatusCode::Ok))
+    }
+}
+
+#[async_trait]
+impl BackendApiEndpoint for DeleteEvent {
+    async fn handler(req: Request<State>, _: NoPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+        let current_user = authenticate(&req).await?;
+        let event_id = event_id_param(&req)?;
+
+        ensure_event_owner(event_id, current_user.id, db_pool).await?;
+
+        let mut tx = db_pool.begin().await?;
+        let files = delete_media(event_id, &mut tx).await?;
+        query!("delete from events where id = $1", event_id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+
+        delete_files(&files, &*req.state().storage).await;
+
+        Ok(((), StatusCode::Ok))
+    }
+}
+
+#[async_trait]
+impl BackendApiEndpoint for GetEventRevisions {
+    async fn handler(req: Request<State>, _: NoPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+        let event_id = event_id_param(&req)?;
+        let viewer_id = authenticate_optional(&req).await?.map(|user| user.id);
+
+        ensure_event_visible(event_id, viewer_id, db_pool).await?;
+
+        let revisions = query_as!(EventRevisionResponse,
+            r#"
+                select content, created_at
+                from event_revisions
+                where event_id = $1
+                order by created_at desc
+            "#,
+            event_id,
+        )
+        .fetch_all(db_pool)
+        .await?;
+
+        Ok((revisions, StatusCode::Ok))
+    }
+}
+
+#[async_trait]
+impl BackendApiEndpoint for GetThread {
+    async fn handler(req: Request<State>, _: NoPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+        let event_id = event_id_param(&req)?;
+        let viewer_id = authenticate_optional(&req).await?.map(|user| user.id);
+
+        // The chain from the root of the conversation down to the event itself.
+        let ancestors = query_as!(ListedEvent,
+            r#"
+                with recursive ancestors (id, in_reply_to, depth) as (
+                    select id, in_reply_to, 0
+                    from events
+                    where id = $1
+                    union all
+                    select events.id, events.in_reply_to, ancestors.depth + 1
+                    from events
+                    inner join ancestors on events.id = ancestors.in_reply_to
+                )
+                select
+                    events.id as "id!"
+                    , events.created_at as "shown_at!"
+                    , null::uuid as "reposted_by_id?"
+                    , null::varchar as "reposted_by_username?"
+                from ancestors
+                inner join events on events.id = ancestors.id
+                where visible_to($2, events.user_id)
+                order by ancestors.depth desc
+            "#,
+            event_id,
+            viewer_id,
+        )
+        .fetch_all(db_pool)
+        .await?;
+        let mut ancestors = listed_event_responses(ancestors, viewer_id, db_pool).await?;
+
+        // Without the event itself, the last visible ancestor would be taken for it.
+        let event = ancestors.pop()
+            .filter(|event| event.id == event_id)
+            .ok_or_else(|| api_error(ApiError::NotFound { resource: Resource::Event }))?;
+
+        let descendants = query_as!(ListedEvent,
+            r#"
+                with recursive descendants (id) as (
+                    select id
+                    from events
+                    where in_reply_to = $1
+                    union all
+                    select events.id
+                    from events
+                    inner join descendants on events.in_reply_to = descendants.id
+                )
+                select
+                    events.id as "id!"
+                    , events.created_at as "shown_at!"
+                    , null::uuid as "reposted_by_id?"
+                    , null::varchar as "reposted_by_username?"
+                from descendants
+                inner join events on events.id = descendants.id
+                where visible_to($2, events.user_id)
+                order by events.created_at asc, events.id asc
+            "#,
+            event_id,
+            viewer_id,
+        )
+        .fetch_all(db_pool)
+        .await?;
+
+        let mut children: HashMap<Uuid, Vec<EventResponse>> = HashMap::new();
+        for event in listed_event_responses(descendants, viewer_id, db_pool).await? {
+            if let Some(parent_id) = event.in_reply_to {
+                children.entry(parent_id).or_default().push(event);
+            }
+        }
+
+        let replies = build_replies(event.id, &mut children);
+
+        Ok((ThreadResponse { ancestors, event, replies }, StatusCode::Ok))
+    }
+}
+
+#[async_trait]
+impl BackendApiEndpoint for LikeEvent {
+    async fn handler(req: Request<State>, _: NoPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+        let current_user = authenticate(&req).await?;
+        let event_id = event_id_param(&req)?;
+
+        ensure_event_visible(event_id, Some(current_user.id), db_pool).await?;
+
+        let existing = query!(
+            "select 1 as one from likes where user_id = $1 and event_id = $2",
+            current_user.id,
+            event_id,
+        )
+        .fetch_optional(db_pool)
+        .await?;
+
+        if existing.is_some() {
+            return Err(api_error(ApiError::AlreadyLiked));
+        }
+
+        let now = Utc::now();
+        query!(
+            r#"
+                insert into likes (id, user_id, event_id, created_at, updated_at)
+                values ($1, $2, $3, $4, $5)
+            "#,
+            Uuid::new_v4(),
+            current_user.id,
+            event_id,
+            now,
+            now,
+        ).execute(db_pool).await?;
+
+        notify_author(db_pool, event_id, current_user.id, NotificationKind::Like, event_id).await?;
+
+        let resp = like_state(event_id, current_user.id, db_pool).await?;
+        Ok((resp, StatusCode::Created))
+    }
+}
+
+#[async_trait]
+impl BackendApiEndpoint for UnlikeEvent {
+    async fn handler(req: Request<State>, _: NoPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+        let current_user = authenticate(&req).await?;
+        let event_id = event_id_param(&req)?;
+
+        ensure_event_visible(event_id, Some(current_user.id), db_pool).await?;
+
+        let pg_res = query!(
+            "delete from likes where user_id = $1 and event_id = $2",
+            current_user.id,
+            event_id,
+        )
+        .execute(db_pool)
+        .await?;
+
+        if pg_res.rows_affected() == 0 {
+            return Err(api_error(ApiError::NotLiked));
+        }
+
+        let resp = like_state(event_id, current_user.id, db_pool).await?;
+        Ok((resp, StatusCode::Ok))
+    }
+}
+
+#[async_trait]
+impl BackendApiEndpoint for Repost {
+    async fn handler(req: Request<State>, _: NoPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+        let current_user = authenticate(&req).await?;
+        let event_id = event_id_param(&req)?;
+
+        let author = query!(
+            r#"
+                select users.id, users.is_private
+                from events
+                inner join users on users.id = events.user_id
+                where events.id = $1
+                    and visible_to($2, users.id)
+            "#,
+            event_id,
+            current_user.id,
+        )
+        .fetch_optional(db_pool)
+        .await?
+        .ok_or_else(|| api_error(ApiError::NotFound { resource: Resource::Event }))?;
+
+        // A repost would show a private account's event to the reposter's followers.
+        if author.is_private && author.id != current_user.id {
+            return Err(api_error(ApiError::PrivateEvent { action: "repost".to_string() }));
+        }
+
+        let existing = query!(
+            "select 1 as one from reposts where user_id = $1 and event_id = $2",
+            current_user.id,
+            event_id,
+        )
+        .fetch_optional(db_pool)
+        .await?;
+
+        if existing.is_some() {
+            return Err(api_error(ApiError::AlreadyReposted));
+        }
+
+        let now = Utc::now();
+        query!(
+            r#"
+                insert into reposts (id, user_id, event_id, created_at, updated_at)
+                values ($1, $2, $3, $4, $5)
+            "#,
+            Uuid::new_v4(),
+            current_user.id,
+            event_id,
+            now,
+            now,
+        ).execute(db_pool).await?;
+
+        req.state().new_events.send(NewEvent { event_id, user_id: author.id, reposted_by: Some(current_user.clone()) });
+
+        let resp = repost_state(event_id, current_user.id, db_pool).await?;
+        Ok((resp, StatusCode::Created))
+    }
+}
+
+#[async_trait]
+impl BackendApiEndpoint for Unrepost {
+    async fn handler(req: Request<State>, _: NoPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+        let current_user = authenticate(&req).await?;
+        let event_id = event_id_param(&req)?;
+
+        ensure_event_visible(event_id, Some(current_user.id), db_pool).await?;
+
+        let pg_res = query!(
+            "delete from reposts where user_id = $1 and event_id = $2",
+            current_user.id,
+            event_id,
+        )
+        .execute(db_pool)
+        .await?;
+
+        if pg_res.rows_affected() == 0 {
+            return Err(api_error(ApiError::NotReposted));
+        }
+
+        let resp = repost_state(event_id, current_user.id, db_pool).await?;
+        Ok((resp, StatusCode::Ok))
+    }
+}
+
+async fn repost_state(event_id: Uuid, user_id: Uuid, db_pool: &PgPool) -> tide::Result<RepostResponse> {
+    let row = query!(
+        r#"
+            select
+                (select count(*) from reposts where event_id = $1) as "repost_count!"
+                , exists(select 1 from reposts where event_id = $1 and user_id = $2) as "reposted_by_me!"
+        "#,
+        event_id,
+        user_id,
+    )
+    .fetch_one(db_pool)
+    .await?;
+
+    Ok(RepostResponse {
+        event_id,
+        repost_count: row.repost_count,
+        reposted_by_me: row.reposted_by_me,
+    })
+}
+
+async fn like_state(event_id: Uuid, user_id: Uuid, db_pool: &PgPool) -> tide::Result<LikeResponse> {
+    let row = query!(
+        r#"
+            select
+                (select count(*) from likes where event_id = $1) as "like_count!"
+                , exists(select 1 from likes where event_id = $1 and user_id = $2) as "liked_by_me!"
+        "#,
+        event_id,
+        user_id,
+    )
+    .fetch_one(db_pool)
+    .await?;
+
+    Ok(LikeResponse {
+        event_id,
+        like_count: row.like_count,
+        liked_by_me: row.liked_by_me,
+    })
+}
+
+/// Loads a single event as `viewer_id` would see it.
+pub(crate) async fn fetch_event(event_id: Uuid, viewer_id: Option<Uuid>, db_pool: &PgPool) -> tide::Result<EventResponse> {
+    fetch_events(&[event_id], viewer_id, db_pool)
+        .await?
+        .pop()
+        .ok_or_else(|| api_error(ApiError::NotFound { resource: Resource::Event }))
+}
+
+/// The events with the given ids that still exist and `viewer_id` may see, in no particular order.
+pub(crate) async fn fetch_events(event_ids: &[Uuid], viewer_id: Option<Uuid>, db_pool: &PgPool) -> tide::Result<Vec<EventResponse>> {
+    let rows = event_rows(event_ids, viewer_id, db_pool).await?;
+    event_responses(rows, viewer_id, db_pool).await
+}
+
+/// Loads the events a listing selected, in the listing's order, leaving out any that have been
+/// deleted since or that `viewer_id` may not see.
+pub(crate) async fn listed_event_responses(listed: Vec<ListedEvent>, viewer_id: Option<Uuid>, db_pool: &PgPool) -> tide::Result<Vec<EventResponse>> {
+    let event_ids = listed.iter().map(|item| item.id).collect::<Vec<_>>();
+    let events = fetch_events(&event_ids, viewer_id, db_pool)
+        .await?
+        .into_iter()
+        .map(|event| (event.id, event))
+        .collect::<HashMap<_, _>>();
+
+    let responses = listed
+        .into_iter()
+        .filter_map(|item| {
+            let mut event = events.get(&item.id)?.clone();
+            event.reposted_by = match (item.reposted_by_id, item.reposted_by_username) {
+                (Some(id), Some(username)) => Some(UserResponse { id, username }),
+                _ => None,
+            };
+            Some(event)
+        })
+        .collect();
+
+    Ok(responses)
+}
+
+async fn event_rows(event_ids: &[Uuid], viewer_id: Option<Uuid>, db_pool: &PgPool) -> tide::Result<Vec<EventRow>> {
+    let rows = query_as!(EventRow,
+        r#"
+            select
+                events.id as "id!"
+                , events.content as "content!"
+                , events.in_reply_to
+                , events.quote_of
+                , events.created_at as "created_at!"
+                , events.edited_at
+                , (select count(*) from events replies where replies.in_reply_to = events.id) as "reply_count!"
+                , (select count(*) from likes where likes.event_id = events.id) as "like_count!"
+                , exists(select 1 from likes where likes.event_id = events.id and likes.user_id = $2) as "liked_by_me!"
+                , (select count(*) from reposts where reposts.event_id = events.id) as "repost_count!"
+                , exists(select 1 from reposts where reposts.event_id = events.id and reposts.user_id = $2) as "reposted_by_me!"
+                , array(select tag from event_tags where event_tags.event_id = events.id order by tag) as "tags!"
+                , array(select mentioned.id from event_mentions inner join users mentioned on mentioned.id = event_mentions.user_id where event_mentions.event_id = events.id order by mentioned.username) as "mention_ids!"
+                , array(select mentioned.username from event_mentions inner join users mentioned on mentioned.id = event_mentions.user_id where event_mentions.event_id = events.id order by mentioned.username) as "mention_usernames!"
+                , users.id as "user_id!"
+                , users.username as "user_username!"
+            from events
+            inner join users on users.id = events.user_id
+            where events.id = any($1)
+                and visible_to($2, users.id)
+        "#,
+        event_ids,
+        viewer_id,
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(rows)
+}
+
+/// The distinct hashtags in `content`, lowercased and in order of first appearance.
+pub(crate) fn extract_tags(content: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for caps in HASHTAG_REGEX.captures_iter(content) {
+        let tag = caps[1].to_lowercase();
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+/// The distinct usernames `@`-mentioned in `content`, as written.
+pub(crate) fn extract_mentions(content: &str) -> Vec<String> {
+    let mut usernames: Vec<String> = Vec::new();
+    for caps in MENTION_REGEX.captures_iter(content) {
+        let username = caps[1].to_string();
+        if !usernames.contains(&username) {
+            usernames.push(username);
+        }
+    }
+    usernames
+}
+
+/// Tags the event with the hashtags in `content`. Tags date from when the event was posted,
+/// even when it's edited, so that editing an old event doesn't make its tags trend again.
+async fn save_tags(event_id: Uuid, content: &str, tx: &mut Transaction<'_, Postgres>) -> tide::Result<()> {
+    let now = Utc::now();
+    for tag in extract_tags(content) {
+        query!(
+            r#"
+                insert into event_tags (id, event_id, tag, created_at, updated_at)
+                select $1, events.id, $3, events.created_at, $4
+                from events
+                where events.id = $2
+            "#,
+            Uuid::new_v4(),
+            event_id,
+            tag,
+            now,
+        ).execute(&mut *tx).await?;
+    }
+    Ok(())
+}
+
+/// Brings the event's mentions in line with `content`, notifying anyone newly mentioned.
+/// Usernames that don't exist, the author mentioning themselves, and anyone blocked either way
+/// are ignored.
+async fn save_mentions(event_id: Uuid, author_id: Uuid, content: &str, tx: &mut Transaction<'_, Postgres>) -> tide::Result<()> {
+    // Usernames are unique regardless of case, so "@Tim" mentions tim.
+    let usernames = extract_mentions(content)
+        .iter()
+        .map(|username| username.to_lowercase())
+        .collect::<Vec<_>>();
+
+    let mentioned = query!(
+        r#"
+            select id from users
+            where lower(username) = any($1)
+                and id <> $2
+                and not exists (
+                    select 1 from blocks
+                    where (blocker_id = users.id and blocked_id = $2)
+                        or (blocked_id = users.id and blocker_id = $2)
+                )
+        "#,
+        &usernames,
+        author_id,
+    )
+    .fetch_all(&mut *tx)
+    .await?
+    .into_iter()
+    .map(|row| row.id)
+    .collect::<Vec<_>>();
+
+    query!(
+        "delete from event_mentions where event_id = $1 and user_id <> all($2)",
+        event_id,
+        &mentioned,
+    ).execute(&mut *tx).await?;
+
+    let now = Utc::now();
+    for user_id in mentioned {
+        let pg_res = query!(
+            r#"
+                insert into event_mentions (id, event_id, user_id, created_at, updated_at)
+                values ($1, $2, $3, $4, $5)
+                on conflict (event_id, user_id) do nothing
+            "#,
+            Uuid::new_v4(),
+            event_id,
+            user_id,
+            now,
+            now,
+        ).execute(&mut *tx).await?;
+
+        if pg_res.rows_affected() == 1 {
+            notify(&mut *tx, user_id, author_id, NotificationKind::Mention, Some(event_id)).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn ensure_event_owner(event_id: Uuid, user_id: Uuid, db_pool: &PgPool) -> tide::Result<()> {
+    let row = query!("select user_id from events where id = $1", event_id)
+        .fetch_optional(db_pool)
+        .await?;
+
+    match row {
+        Some(row) if row.user_id == user_id => Ok(()),
+        Some(_) => Err(api_error(ApiError::NotEventAuthor)),
+        None => Err(api_error(ApiError::NotFound { resource: Resource::Event })),
+    }
+}
+
+/// Fails with not found unless the event exists and `viewer_id` may see it; a private account's
+/// events only exist for the account itself and its followers.
+pub(crate) async fn ensure_event_visible(event_id: Uuid, viewer_id: Option<Uuid>, db_pool: &PgPool) -> tide::Result<()> {
+    let row = query!(
+        r#"
+            select 1 as one
+            from events
+            where events.id = $1
+                and visible_to($2, events.user_id)
+        "#,
+        event_id,
+        viewer_id,
+    )
+    .fetch_optional(db_pool)
+    .await?;
+
+    match row {
+        Some(_) => Ok(()),
+        None => Err(api_error(ApiError::NotFound { resource: Resource::Event })),
+    }
+}
+
+fn build_replies(parent_id: Uuid, children: &mut HashMap<Uuid, Vec<EventResponse>>) -> Vec<ThreadReplyResponse> {
+    children
+        .remove(&parent_id)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|event| {
+            let replies = build_replies(event.id, children);
+            ThreadReplyResponse { event, replies }
+        })
+        .collect()
+}
+
+pub(crate) fn event_id_param(req: &Request<State>) -> tide::Result<Uuid> {
+    req.param("id")?
+        .parse()
+        .map_err(|_| api_error(ApiError::NotFound { resource: Resource::Event }))
+}
+
+lazy_static! {
+    // A `#` that doesn't follow a word character, so "a#b" and "##" aren't tags.
+    static ref HASHTAG_REGEX: Regex = Regex::new(r"(?:^|[^\w#])#(\w+)").unwrap();
+    // Likewise for `@`, which also keeps email addresses from counting as mentions.
+    static ref MENTION_REGEX: Regex = Regex::new(r"(?:^|[^\w@])@(\w+)").unwrap();
+}
+
+#[cfg(test)]
+mod test {
+    use super::{extract_mentions, extract_tags};
+
+    #[test]
+    fn extracts_distinct_lowercased_tags() {
+        assert_eq!(extract_tags("#Rust and #rust, #async_std!"), vec!["rust", "async_std"]);
+    }
+
+    #[test]
+    fn ignores_hashes_inside_words() {
+        assert_eq!(extract_tags("issue#12 ##double #ok"), vec!["ok"]);
+    }
+
+    #[test]
+    fn extracts_distinct_mentions() {
+        assert_eq!(extract_mentions("@tim, @Jim and @tim again"), vec!["tim", "Jim"]);
+    }
+
+    #[test]
+    fn ignores_email_addresses() {
+        assert_eq!(extract_mentions("mail tim@example.com or @@jim, cc @bob"), vec!["bob"]);
+    }
+}
@@ -0,0 +1,148 @@
+use crate::BackendApiEndpoint;
+use tide::Request;
+use tide::http::StatusCode;
+use shared::{ApiEndpoint, FollowRequests, ApproveFollowRequest, RejectFollowRequest, NoPayload};
+use shared::errors::{ApiError, Resource};
+use shared::responses::{FollowRequestResponse, PaginatedResponse, UserResponse};
+use crate::State;
+use crate::endpoints::{api_error, authenticate};
+use crate::endpoints::pagination::{Cursor, Pagination};
+use crate::endpoints::users::relationship;
+use chrono::Utc;
+use uuid::Uuid;
+use sqlx::{query, PgPool};
+use async_trait::async_trait;
+
+#[async_trait]
+impl BackendApiEndpoint for FollowRequests {
+    async fn handler(req: Request<State>, _: NoPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+        let current_user = authenticate(&req).await?;
+
+        let pagination = req.query::<Pagination>()?;
+        let before = pagination.before()?;
+        let after = pagination.after()?;
+
+        let rows = query!(
+            r#"
+                select
+                    follow_requests.id
+                    , follow_requests.created_at
+                    , users.id as user_id
+                    , users.username
+                from follow_requests
+                inner join users on users.id = follow_requests.requester_id
+                where follow_requests.requested_id = $1
+                    and ($4::timestamptz is null or (follow_requests.created_at, follow_requests.id) < ($4, $5))
+                    and ($6::timestamptz is null or (follow_requests.created_at, follow_requests.id) > ($6, $7))
+                order by
+                    case when $6::timestamptz is not null then follow_requests.created_at end asc
+                    , case when $6::timestamptz is not null then follow_requests.id end asc
+                    , follow_requests.created_at desc
+                    , follow_requests.id desc
+                limit $2
+                offset $3
+            "#,
+            current_user.id,
+            pagination.limit(),
+            pagination.offset(),
+            before.map(|cursor| cursor.at),
+            before.map(|cursor| cursor.id),
+            after.map(|cursor| cursor.at),
+            after.map(|cursor| cursor.id),
+        )
+        .fetch_all(db_pool)
+        .await?;
+
+        let (rows, next_cursor) = pagination.paginate(rows, |row| Cursor { at: row.created_at, id: row.id });
+        let items = rows
+            .into_iter()
+            .map(|row| FollowRequestResponse {
+                id: row.id,
+                user: UserResponse { id: row.user_id, username: row.username },
+                created_at: row.created_at,
+            })
+            .collect();
+
+        Ok((PaginatedResponse { items, next_cursor }, StatusCode::Ok))
+    }
+}
+
+#[async_trait]
+impl BackendApiEndpoint for ApproveFollowRequest {
+    async fn handler(req: Request<State>, _: NoPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+        let current_user = authenticate(&req).await?;
+        let request_id = follow_request_id_param(&req)?;
+
+        let requester = find_follow_request(request_id, current_user.id, db_pool).await?;
+
+        let now = Utc::now();
+        let mut tx = db_pool.begin().await?;
+
+        query!(
+            r#"
+                insert into follows (id, follower_id, followed_id, created_at, updated_at)
+                values ($1, $2, $3, $4, $5)
+                on conflict (follower_id, followed_id) do nothing
+            "#,
+            Uuid::new_v4(),
+            requester.id,
+            current_user.id,
+            now,
+            now,
+        ).execute(&mut tx).await?;
+
+        query!("delete from follow_requests where id = $1", request_id)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+
+        let relationship = relationship(current_user.id, requester.id, &requester.username, db_pool).await?;
+        Ok((relationship, StatusCode::Ok))
+    }
+}
+
+#[async_trait]
+impl BackendApiEndpoint for RejectFollowRequest {
+    async fn handler(req: Request<State>, _: NoPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+        let current_user = authenticate(&req).await?;
+        let request_id = follow_request_id_param(&req)?;
+
+        let requester = find_follow_request(request_id, current_user.id, db_pool).await?;
+
+        query!("delete from follow_requests where id = $1", request_id)
+            .execute(db_pool)
+            .await?;
+
+        let relationship = relationship(current_user.id, requester.id, &requester.username, db_pool).await?;
+        Ok((relationship, StatusCode::Ok))
+    }
+}
+
+/// Who sent the request, if it was sent to `user_id`.
+async fn find_follow_request(request_id: Uuid, user_id: Uuid, db_pool: &PgPool) -> tide::Result<UserResponse> {
+    let row = query!(
+        r#"
+            select users.id, users.username
+            from follow_requests
+            inner join users on users.id = follow_requests.requester_id
+            where follow_requests.id = $1 and follow_requests.requested_id = $2
+        "#,
+        request_id,
+        user_id,
+    )
+    .fetch_optional(db_pool)
+    .await?;
+
+    row.map(|row| UserResponse { id: row.id, username: row.username })
+        .ok_or_else(|| api_error(ApiError::NotFound { resource: Resource::FollowRequest }))
+}
+
+fn follow_request_id_param(req: &Request<State>) -> tide::Result<Uuid> {
+    req.param("id")?
+        .parse()
+        .map_err(|_| api_error(ApiError::NotFound { resource: Resource::FollowRequest }))
+}
@@ -1,30 +1,166 @@
-use shared::{Timeline,Me};
-use shared::responses::UserResponse;
+use shared::{Timeline, Me, UpdateMe, Mentions, ListSessions, DeleteSession};
+use shared::errors::{ApiError, Resource};
+use shared::payloads::UpdateMePayload;
+use shared::responses::{MeResponse, SessionResponse, PaginatedResponse};
 use shared::NoPayload;
 use shared::ApiEndpoint;
 use tide::Request;
 use tide::http::StatusCode;
 use crate::State;
-use crate::endpoints::authenticate;
+use crate::endpoints::{api_error, authenticate, authenticate_session, authenticate_token};
 use crate::BackendApiEndpoint;
-use shared::responses::EventResponse;
-use sqlx::query;
-use serde::Deserialize;
+use crate::endpoints::events::{ListedEvent, listed_event_responses, fetch_event};
+use crate::endpoints::pagination::{Cursor, Pagination};
+use crate::endpoints::notifications::unread_count;
+use crate::endpoints::users::profile;
+use chrono::Utc;
+use sqlx::{query, query_as, PgPool};
+use uuid::Uuid;
 use async_trait::async_trait;
+use serde::Deserialize;
+use lazy_static::lazy_static;
+use regex::Regex;
 
 // pub(crate) async fn get(req: Request<State>) -> tide::Result {
 //     let user = authenticate(&req).await?;
 //     Ok(user.to_response(StatusCode::Ok))
 // }
 
+const MAX_DISPLAY_NAME_LENGTH: usize = 50;
+const MAX_BIO_LENGTH: usize = 160;
+const MAX_AVATAR_URL_LENGTH: usize = 500;
+const MAX_EMAIL_LENGTH: usize = 254;
+
+lazy_static! {
+    /// Loose on purpose: the only real check of an address is mailing it.
+    static ref EMAIL_REGEX: Regex = Regex::new(r"^[^@\s/]+@[^@\s/]+\.[^@\s/]+$").unwrap();
+}
+
 #[async_trait]
 impl BackendApiEndpoint for Me {
     async fn handler(req: Request<State>, _: NoPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
         let user = authenticate(&req).await?;
-        Ok((user, StatusCode::Ok))
+        let resp = me_response(user.id, &req.state().db_pool).await?;
+        Ok((resp, StatusCode::Ok))
     }
 }
 
+#[async_trait]
+impl BackendApiEndpoint for UpdateMe {
+    async fn handler(
+        req: Request<State>,
+        payload: UpdateMePayload,
+    ) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+        let user = authenticate(&req).await?;
+
+        let display_name = profile_field(payload.display_name, "display_name", MAX_DISPLAY_NAME_LENGTH)?;
+        let bio = profile_field(payload.bio, "bio", MAX_BIO_LENGTH)?;
+        let avatar_url = profile_field(payload.avatar_url, "avatar_url", MAX_AVATAR_URL_LENGTH)?;
+        let email = profile_field(payload.email, "email", MAX_EMAIL_LENGTH)?;
+
+        if let Some(Some(url)) = &avatar_url {
+            if !url.starts_with("https://") && !url.starts_with("http://") {
+                return Err(api_error(ApiError::InvalidAvatarUrl));
+            }
+        }
+
+        if let Some(Some(email)) = &email {
+            if !EMAIL_REGEX.is_match(email) {
+                return Err(api_error(ApiError::InvalidEmail));
+            }
+
+            let taken = query!(
+                r#"select exists(select 1 from users where lower(email) = lower($1) and id <> $2) as "taken!""#,
+                email,
+                user.id,
+            )
+            .fetch_one(db_pool)
+            .await?
+            .taken;
+            if taken {
+                return Err(api_error(ApiError::EmailTaken));
+            }
+        }
+
+        let now = Utc::now();
+        let mut tx = db_pool.begin().await?;
+
+        query!(
+            r#"
+                update users set
+                    display_name = case when $2 then $3 else display_name end
+                    , bio = case when $4 then $5 else bio end
+                    , avatar_url = case when $6 then $7 else avatar_url end
+                    , is_private = coalesce($8, is_private)
+                    , updated_at = $9
+                    , email = case when $10 then $11 else email end
+                where id = $1
+            "#,
+            user.id,
+            display_name.is_some(),
+            display_name.flatten(),
+            bio.is_some(),
+            bio.flatten(),
+            avatar_url.is_some(),
+            avatar_url.flatten(),
+            payload.is_private,
+            now,
+            email.is_some(),
+            email.flatten(),
+        ).execute(&mut tx).await?;
+
+        // Going public lets everyone who asked follow right away. Each follow takes over the
+        // id of the request it came from.
+        if payload.is_private == Some(false) {
+            query!(
+                r#"
+                    insert into follows (id, follower_id, followed_id, created_at, updated_at)
+                    select id, requester_id, requested_id, $2, $2
+                    from follow_requests
+                    where requested_id = $1
+                    on conflict (follower_id, followed_id) do nothing
+                "#,
+                user.id,
+                now,
+            ).execute(&mut tx).await?;
+
+            query!("delete from follow_requests where requested_id = $1", user.id)
+                .execute(&mut tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+
+        let resp = me_response(user.id, db_pool).await?;
+        Ok((resp, StatusCode::Ok))
+    }
+}
+
+/// `None` when the field is to be left alone, `Some(None)` when it is to be cleared.
+fn profile_field(value: Option<String>, name: &str, max_length: usize) -> tide::Result<Option<Option<String>>> {
+    let value = match value {
+        Some(value) => value.trim().to_string(),
+        None => return Ok(None),
+    };
+
+    if value.chars().count() > max_length {
+        return Err(api_error(ApiError::ContentTooLong { field: name.to_string(), max_length }));
+    }
+
+    Ok(Some(Some(value).filter(|value| !value.is_empty())))
+}
+
+async fn me_response(user_id: Uuid, db_pool: &PgPool) -> tide::Result<MeResponse> {
+    let profile = profile(user_id, db_pool).await?;
+    let email = query!("select email from users where id = $1", user_id)
+        .fetch_one(db_pool)
+        .await?
+        .email;
+    let unread_count = unread_count(user_id, db_pool).await?;
+    Ok(MeResponse { profile, email, unread_count })
+}
+
 #[async_trait]
 impl BackendApiEndpoint for Timeline {
     async fn handler(req: Request<State>, _: NoPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
@@ -32,63 +168,238 @@ impl BackendApiEndpoint for Timeline {
         let current_user = authenticate(&req).await?;
 
         let pagination = req.query::<Pagination>()?;
-        let page_size = pagination.page_size.unwrap_or(20).min(20) as i64;
-
-        let page = pagination.page.unwrap_or(1) as i64;
-        let offset = (page - 1) * page_size;
+        let before = pagination.before()?;
+        let after = pagination.after()?;
 
-        let events = query!(
+        // Everything the user or the people they follow posted or reposted. An event that shows
+        // up more than once (posted by one followee, reposted by another) is kept only at its
+        // most recent appearance, attributed to whoever reposted it there.
+        // Posts and reposts by anyone muted or blocked either way are left out, the same way
+        // `timeline_stream` leaves them out.
+        let events = query_as!(ListedEvent,
             r#"
             select
-                events.id as event_id
-                , events.content as event_content
-                , events.created_at as event_created_at
-                , users.id as user_id
-                , users.username as user_username
+                events.id as "id!"
+                , timeline.shown_at as "shown_at!"
+                , reposters.id as "reposted_by_id?"
+                , reposters.username as "reposted_by_username?"
             from (
-                select id, content, created_at, user_id
-                from events
-                where user_id = $1
-                union all
-                select events.id, events.content, events.created_at, events.user_id
-                from users
-                inner join follows on
-                    follows.follower_id = $1
-                    and follows.followed_id = users.id
-                inner join events on
-                    events.user_id = users.id
-            ) events
-            inner join users on users.id = events.user_id
-            order by events.created_at desc
+                select distinct on (timeline.event_id) timeline.event_id, timeline.shown_at, timeline.reposted_by_id
+                from (
+                    select id as event_id, created_at as shown_at, null::uuid as reposted_by_id
+                    from events
+                    where user_id = $1
+                    union all
+                    select events.id, events.created_at, null::uuid
+                    from follows
+                    inner join events on
+                        events.user_id = follows.followed_id
+                    where follows.follower_id = $1
+                    union all
+                    select event_id, created_at, user_id
+                    from reposts
+                    where user_id = $1
+                    union all
+                    select reposts.event_id, reposts.created_at, reposts.user_id
+                    from follows
+                    inner join reposts on
+                        reposts.user_id = follows.followed_id
+                    where follows.follower_id = $1
+                ) timeline
+                inner join events posted on posted.id = timeline.event_id
+                where on_timeline($1, posted.user_id, timeline.reposted_by_id)
+                order by timeline.event_id, timeline.shown_at desc
+            ) timeline
+            inner join events on events.id = timeline.event_id
+            left join users reposters on reposters.id = timeline.reposted_by_id
+            where ($4::timestamptz is null or (timeline.shown_at, events.id) < ($4, $5))
+                and ($6::timestamptz is null or (timeline.shown_at, events.id) > ($6, $7))
+            order by
+                case when $6::timestamptz is not null then timeline.shown_at end asc
+                , case when $6::timestamptz is not null then events.id end asc
+                , timeline.shown_at desc
+                , events.id desc
+            limit $2
+            offset $3
+        "#,
+            current_user.id,
+            pagination.limit(),
+            pagination.offset(),
+            before.map(|cursor| cursor.at),
+            before.map(|cursor| cursor.id),
+            after.map(|cursor| cursor.at),
+            after.map(|cursor| cursor.id),
+        )
+        .fetch_all(db_pool)
+        .await?;
+
+        let (events, next_cursor) = pagination.paginate(events, |event| Cursor { at: event.shown_at, id: event.id });
+        let items = listed_event_responses(events, Some(current_user.id), db_pool).await?;
+        let event_responses = PaginatedResponse { items, next_cursor };
+
+        Ok((event_responses, StatusCode::Ok))
+    }
+}
+
+#[async_trait]
+impl BackendApiEndpoint for Mentions {
+    async fn handler(req: Request<State>, _: NoPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+        let current_user = authenticate(&req).await?;
+
+        let pagination = req.query::<Pagination>()?;
+        let before = pagination.before()?;
+        let after = pagination.after()?;
+
+        let events = query_as!(ListedEvent,
+            r#"
+            select
+                events.id as "id!"
+                , events.created_at as "shown_at!"
+                , null::uuid as "reposted_by_id?"
+                , null::varchar as "reposted_by_username?"
+            from event_mentions
+            inner join events on events.id = event_mentions.event_id
+            where event_mentions.user_id = $1
+                and visible_to($1, events.user_id)
+                and ($4::timestamptz is null or (events.created_at, events.id) < ($4, $5))
+                and ($6::timestamptz is null or (events.created_at, events.id) > ($6, $7))
+            order by
+                case when $6::timestamptz is not null then events.created_at end asc
+                , case when $6::timestamptz is not null then events.id end asc
+                , events.created_at desc
+                , events.id desc
             limit $2
             offset $3
         "#,
             current_user.id,
-            page_size,
-            offset,
+            pagination.limit(),
+            pagination.offset(),
+            before.map(|cursor| cursor.at),
+            before.map(|cursor| cursor.id),
+            after.map(|cursor| cursor.at),
+            after.map(|cursor| cursor.id),
         )
         .fetch_all(db_pool)
         .await?;
 
-        let event_responses = events
+        let (events, next_cursor) = pagination.paginate(events, |event| Cursor { at: event.shown_at, id: event.id });
+        let items = listed_event_responses(events, Some(current_user.id), db_pool).await?;
+
+        Ok((PaginatedResponse { items, next_cursor }, StatusCode::Ok))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamQuery {
+    access_token: Option<String>,
+}
+
+/// Streams events posted or reposted by the user, or by anyone they follow, as server-sent events
+/// as soon as that happens, leaving out the same ones `Timeline` does. Browsers can't set headers
+/// on an `EventSource`, so the auth token may also be passed as the `access_token` query parameter.
+pub(crate) async fn timeline_stream(req: Request<State>) -> tide::Result {
+    let query = req.query::<StreamQuery>()?;
+    let (current_user, _) = match (req.header("Authorization"), query.access_token) {
+        (None, Some(token)) => authenticate_token(&token, req.state()).await?,
+        _ => authenticate_session(&req).await?,
+    };
+    let viewer_id = current_user.id;
+
+    // Subscribe before responding, so nothing posted once the client is connected is missed.
+    let new_events = req.state().new_events.subscribe();
+
+    Ok(tide::sse::upgrade(req, move |req, sender| {
+        let new_events = new_events.clone();
+
+        async move {
+            let db_pool = &req.state().db_pool;
+
+            while let Ok(new_event) = new_events.recv().await {
+                let row = query!(
+                    r#"select on_timeline($1, $2, $3) as "shown!""#,
+                    viewer_id,
+                    new_event.user_id,
+                    new_event.reposted_by.as_ref().map(|user| user.id),
+                ).fetch_one(db_pool).await?;
+
+                if !row.shown {
+                    continue;
+                }
+
+                // The event may already have been deleted again.
+                let mut event = match fetch_event(new_event.event_id, Some(viewer_id), db_pool).await {
+                    Ok(event) => event,
+                    Err(err) if err.status() == StatusCode::NotFound => continue,
+                    Err(err) => return Err(err),
+                };
+                event.reposted_by = new_event.reposted_by.clone();
+
+                let id = event.id.to_string();
+                sender.send("event", serde_json::to_string(&event)?, Some(&id)).await?;
+            }
+
+            Ok(())
+        }
+    }))
+}
+
+#[async_trait]
+impl BackendApiEndpoint for ListSessions {
+    async fn handler(req: Request<State>, _: NoPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+        let (current_user, current_token_id) = authenticate_session(&req).await?;
+
+        let rows = query!(
+            r#"
+                select id, device, created_at, last_used_at, expires_at
+                from auth_tokens
+                where user_id = $1 and expires_at > now()
+                order by last_used_at desc
+            "#,
+            current_user.id,
+        )
+        .fetch_all(db_pool)
+        .await?;
+
+        let sessions = rows
             .into_iter()
-            .map(|event| EventResponse {
-                id: event.event_id.unwrap(),
-                content: event.event_content.unwrap(),
-                created_at: event.event_created_at.unwrap(),
-                user: UserResponse {
-                    id: event.user_id,
-                    username: event.user_username,
-                },
+            .map(|row| SessionResponse {
+                id: row.id,
+                device: row.device,
+                created_at: row.created_at,
+                last_used_at: row.last_used_at,
+                expires_at: row.expires_at,
+                current: row.id == current_token_id,
             })
             .collect::<Vec<_>>();
 
-        Ok((event_responses, StatusCode::Ok))
+        Ok((sessions, StatusCode::Ok))
     }
 }
 
-#[derive(Debug, Deserialize)]
-struct Pagination {
-    page: Option<usize>,
-    page_size: Option<usize>,
-}
\ No newline at end of file
+#[async_trait]
+impl BackendApiEndpoint for DeleteSession {
+    async fn handler(req: Request<State>, _: NoPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+        let current_user = authenticate(&req).await?;
+
+        let session_id: Uuid = req.param("id")?
+            .parse()
+            .map_err(|_| api_error(ApiError::NotFound { resource: Resource::Session }))?;
+
+        let pg_res = query!(
+            "delete from auth_tokens where id = $1 and user_id = $2",
+            session_id,
+            current_user.id,
+        )
+        .execute(db_pool)
+        .await?;
+
+        if pg_res.rows_affected() == 0 {
+            return Err(api_error(ApiError::NotFound { resource: Resource::Session }));
+        }
+
+        Ok(((), StatusCode::Ok))
+    }
+}
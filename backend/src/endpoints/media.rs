@@ -0,0 +1,317 @@
+use crate::{BackendApiEndpoint, GetRequestPayload};
+use tide::{Request, Response};
+use tide::http::StatusCode;
+use shared::{ApiEndpoint, UploadMedia};
+use shared::errors::{ApiError, Resource};
+use shared::payloads::UploadMediaPayload;
+use shared::responses::MediaResponse;
+use crate::State;
+use crate::endpoints::{api_error, authenticate, authenticate_optional};
+use crate::storage::Storage;
+use async_std::io::ReadExt;
+use chrono::Utc;
+use image::{GenericImageView, ImageFormat, ImageOutputFormat};
+use uuid::Uuid;
+use sqlx::{query, query_as, PgPool, Postgres, Transaction};
+use schemars::gen::SchemaGenerator;
+use serde_json::json;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::io::Cursor;
+
+pub(crate) const MAX_MEDIA_PER_EVENT: usize = 4;
+/// Room for the multipart boundaries and headers around the file.
+const MAX_UPLOAD_OVERHEAD_BYTES: usize = 16 * 1024;
+/// Checked against the file's header before decoding, since a small file can declare an image far
+/// too big to hold in memory.
+const MAX_IMAGE_DIMENSION: u32 = 8192;
+const THUMBNAIL_SIZE: u32 = 320;
+const THUMBNAIL_SUFFIX: &str = "thumb.png";
+
+/// The image types accepted, with the extension they're stored under.
+fn image_format(content_type: &str) -> Option<(ImageFormat, &'static str)> {
+    match content_type {
+        "image/png" => Some((ImageFormat::Png, "png")),
+        "image/jpeg" => Some((ImageFormat::Jpeg, "jpg")),
+        "image/gif" => Some((ImageFormat::Gif, "gif")),
+        _ => None,
+    }
+}
+
+/// The content type of a stored file, from everything after the id in its name.
+fn content_type_for(suffix: &str) -> Option<&'static str> {
+    match suffix {
+        ".png" => Some("image/png"),
+        ".jpg" => Some("image/jpeg"),
+        ".gif" => Some("image/gif"),
+        ".thumb.png" => Some("image/png"),
+        _ => None,
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct MediaRow {
+    pub id: Uuid,
+    pub event_id: Option<Uuid>,
+    pub content_type: String,
+    pub extension: String,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl From<MediaRow> for MediaResponse {
+    fn from(row: MediaRow) -> Self {
+        MediaResponse {
+            id: row.id,
+            content_type: row.content_type,
+            url: format!("/media/{}.{}", row.id, row.extension),
+            thumbnail_url: format!("/media/{}.{}", row.id, THUMBNAIL_SUFFIX),
+            width: row.width,
+            height: row.height,
+        }
+    }
+}
+
+#[async_trait]
+impl GetRequestPayload for UploadMediaPayload {
+    async fn get_payload(req: &mut Request<State>) -> tide::Result<Self> {
+        // Before reading the body, so that only signed-in users can make the server buffer one.
+        authenticate(req).await?;
+
+        let boundary = req
+            .content_type()
+            .and_then(|mime| multer::parse_boundary(mime.to_string()).ok())
+            .ok_or_else(|| api_error(ApiError::InvalidUpload { reason: "Expected a multipart/form-data body".to_string() }))?;
+
+        let max_bytes = req.state().config.max_upload_bytes + MAX_UPLOAD_OVERHEAD_BYTES;
+        let mut body = Vec::new();
+        req.take_body()
+            .take(max_bytes as u64 + 1)
+            .read_to_end(&mut body)
+            .await?;
+
+        if body.len() > max_bytes {
+            return Err(api_error(ApiError::FileTooLarge));
+        }
+
+        let malformed = |_| api_error(ApiError::InvalidUpload { reason: "Malformed multipart body".to_string() });
+        let stream = futures::stream::once(async move { Ok::<_, std::io::Error>(bytes::Bytes::from(body)) });
+        let mut multipart = multer::Multipart::new(stream, boundary);
+
+        while let Some(field) = multipart.next_field().await.map_err(malformed)? {
+            if field.name() == Some("file") {
+                let content_type = field.content_type().map(|mime| mime.essence_str().to_string());
+                let bytes = field.bytes().await.map_err(malformed)?;
+                return Ok(UploadMediaPayload { content_type, bytes: bytes.to_vec() });
+            }
+        }
+
+        Err(api_error(ApiError::InvalidUpload { reason: "Missing file field".to_string() }))
+    }
+
+    fn request_body(_: &mut SchemaGenerator) -> Option<serde_json::Value> {
+        Some(json!({
+            "required": true,
+            "content": {
+                "multipart/form-data": {
+                    "schema": {
+                        "type": "object",
+                        "properties": { "file": { "type": "string", "format": "binary" } },
+                        "required": ["file"],
+                    },
+                },
+            },
+        }))
+    }
+}
+
+#[async_trait]
+impl BackendApiEndpoint for UploadMedia {
+    async fn handler(req: Request<State>, upload: UploadMediaPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+        let storage = &req.state().storage;
+        let current_user = authenticate(&req).await?;
+
+        if upload.bytes.len() > req.state().config.max_upload_bytes {
+            return Err(api_error(ApiError::FileTooLarge));
+        }
+
+        let content_type = upload.content_type.unwrap_or_default();
+        let (format, extension) = image_format(&content_type)
+            .ok_or_else(|| api_error(ApiError::UnsupportedImageType))?;
+
+        let (width, height) = image::io::Reader::with_format(Cursor::new(&upload.bytes), format)
+            .into_dimensions()
+            .map_err(|_| api_error(ApiError::InvalidImage))?;
+        if width > MAX_IMAGE_DIMENSION || height > MAX_IMAGE_DIMENSION {
+            return Err(api_error(ApiError::ImageTooLarge { max: MAX_IMAGE_DIMENSION }));
+        }
+
+        // Decoding makes sure the file is the image it claims to be.
+        let bytes = upload.bytes;
+        let (bytes, width, height, thumbnail) = async_std::task::spawn_blocking(move || {
+            let image = image::load_from_memory_with_format(&bytes, format)?;
+            let mut thumbnail = Vec::new();
+            image
+                .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+                .write_to(&mut thumbnail, ImageOutputFormat::Png)?;
+            Ok::<_, image::ImageError>((bytes, image.width(), image.height(), thumbnail))
+        })
+        .await
+        .map_err(|_| api_error(ApiError::InvalidImage))?;
+
+        let id = Uuid::new_v4();
+        let byte_size = bytes.len() as i32;
+        storage.put(&format!("{}.{}", id, extension), bytes).await?;
+        storage.put(&format!("{}.{}", id, THUMBNAIL_SUFFIX), thumbnail).await?;
+
+        let now = Utc::now();
+        let row = query_as!(MediaRow,
+            r#"
+                insert into media (id, user_id, content_type, extension, byte_size, width, height, created_at, updated_at)
+                values ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                returning id, event_id, content_type, extension, width, height
+            "#,
+            id,
+            current_user.id,
+            content_type,
+            extension,
+            byte_size,
+            width as i32,
+            height as i32,
+            now,
+            now,
+        ).fetch_one(db_pool).await?;
+
+        Ok((row.into(), StatusCode::Created))
+    }
+}
+
+/// Serves an uploaded file or its thumbnail from storage, to whoever may see the event it's
+/// attached to. Files never change once uploaded, so they can be cached for good, though only
+/// by the viewer's browser if the event isn't public. Unattached uploads are never cached, as
+/// they're about to either be attached or deleted.
+pub(crate) async fn serve(req: Request<State>) -> tide::Result {
+    let not_found = || api_error(ApiError::NotFound { resource: Resource::Media });
+    let file = req.param("file")?;
+
+    // Only names the server hands out, so nothing outside the store can be read.
+    let (id, suffix) = file.split_at(file.find('.').ok_or_else(not_found)?);
+    let (id, content_type) = match (id.parse::<Uuid>(), content_type_for(suffix)) {
+        (Ok(id), Some(content_type)) => (id, content_type),
+        _ => return Err(not_found()),
+    };
+
+    // Media that isn't attached yet is only served to its uploader.
+    let viewer_id = authenticate_optional(&req).await?.map(|user| user.id);
+    let row = query!(
+        r#"
+            select
+                media.event_id is not null as "attached!"
+                , case
+                    when media.event_id is null then media.user_id is not distinct from $2
+                    else visible_to($2, events.user_id)
+                end as "visible!"
+                , media.event_id is not null and visible_to(null, events.user_id) as "public!"
+            from media
+            left join events on events.id = media.event_id
+            where media.id = $1
+        "#,
+        id,
+        viewer_id,
+    )
+    .fetch_optional(&req.state().db_pool)
+    .await?
+    .filter(|row| row.visible)
+    .ok_or_else(not_found)?;
+
+    let bytes = req.state().storage.get(file).await?.ok_or_else(not_found)?;
+
+    let mut resp = Response::new(StatusCode::Ok);
+    resp.set_body(bytes);
+    resp.set_content_type(content_type);
+    let cache_control = match (row.attached, row.public) {
+        (false, _) => "private, no-store",
+        (true, false) => "private, max-age=31536000, immutable",
+        (true, true) => "public, max-age=31536000, immutable",
+    };
+    resp.insert_header("Cache-Control", cache_control);
+    Ok(resp)
+}
+
+/// Attaches uploads to a new event, in the order given. Only the uploader's own, still unattached
+/// media can be used.
+pub(crate) async fn attach_media(event_id: Uuid, user_id: Uuid, media_ids: &[Uuid], tx: &mut Transaction<'_, Postgres>) -> tide::Result<()> {
+    if media_ids.is_empty() {
+        return Ok(());
+    }
+
+    let pg_res = query!(
+        r#"
+            update media
+            set event_id = $1, position = array_position($2, id), updated_at = now()
+            where id = any($2) and user_id = $3 and event_id is null
+        "#,
+        event_id,
+        media_ids,
+        user_id,
+    ).execute(&mut *tx).await?;
+
+    if pg_res.rows_affected() != media_ids.len() as u64 {
+        return Err(api_error(ApiError::NotFound { resource: Resource::Media }));
+    }
+
+    Ok(())
+}
+
+/// The media attached to each of the events, in order.
+pub(crate) async fn media_for_events(event_ids: &[Uuid], db_pool: &PgPool) -> tide::Result<HashMap<Uuid, Vec<MediaResponse>>> {
+    let rows = query_as!(MediaRow,
+        r#"
+            select id, event_id, content_type, extension, width, height
+            from media
+            where event_id = any($1)
+            order by position asc
+        "#,
+        event_ids,
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    let mut media: HashMap<Uuid, Vec<MediaResponse>> = HashMap::new();
+    for row in rows {
+        if let Some(event_id) = row.event_id {
+            media.entry(event_id).or_default().push(row.into());
+        }
+    }
+
+    Ok(media)
+}
+
+/// Deletes the rows of the event's media, returning the names of their files, which are only
+/// removed from storage by `delete_files` once the transaction has committed.
+pub(crate) async fn delete_media(event_id: Uuid, tx: &mut Transaction<'_, Postgres>) -> tide::Result<Vec<String>> {
+    let rows = query!("delete from media where event_id = $1 returning id, extension", event_id)
+        .fetch_all(&mut *tx)
+        .await?;
+
+    let files = rows
+        .into_iter()
+        .flat_map(|row| vec![
+            format!("{}.{}", row.id, row.extension),
+            format!("{}.{}", row.id, THUMBNAIL_SUFFIX),
+        ])
+        .collect();
+
+    Ok(files)
+}
+
+/// Removes files whose rows are already gone. A file that can't be removed is only logged, since
+/// nothing refers to it any more.
+pub(crate) async fn delete_files(files: &[String], storage: &dyn Storage) {
+    for file in files {
+        if let Err(err) = storage.delete(file).await {
+            tide::log::error!("Could not delete media file {}: {:?}", file, err);
+        }
+    }
+}
@@ -1,10 +1,20 @@
 use tide::{Request, Response};
 use crate::State;
-use sqlx::query_as;
+use crate::config::Config;
+use sqlx::query;
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+use rand::Rng;
+use rand::rngs::OsRng;
+use rand::distributions::Alphanumeric;
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Sha256;
 use tide::http::{StatusCode, Error};
 use lazy_static::lazy_static;
 use regex::Regex;
 use shared::responses::UserResponse;
+use shared::errors::ApiError;
+use shared::validation::ValidationErrors;
 use tide::http::headers::HeaderName;
 use serde_json::Value;
 use crate::responses::BuildApiResponse;
@@ -12,6 +22,15 @@ use crate::responses::BuildApiResponse;
 pub mod me;
 pub mod users;
 pub mod events;
+pub mod pagination;
+pub mod notifications;
+pub mod conversations;
+pub mod blocks;
+pub mod follow_requests;
+pub mod tags;
+pub mod media;
+pub mod search;
+pub mod passwords;
 
 fn get_header<'a>(header_key: &str, req: &'a Request<State>) -> Result<&'a str, Error> {
     let auth_header_key: HeaderName = header_key.parse()?;
@@ -23,30 +42,111 @@ fn get_header<'a>(header_key: &str, req: &'a Request<State>) -> Result<&'a str,
     match header_value {
         Some(value) => Ok(value),
         None => {
-            return Err(Error::from_str(
-                StatusCode::BadRequest,
-                format!("Missing value for '{}' header", header_key),
-            ))
+            return Err(api_error(ApiError::MissingHeader { header: header_key.to_string() }))
         }
     }
 }
 
 pub(crate) async fn authenticate(req: &Request<State>) -> Result<UserResponse, Error> {
-    let auth_token = get_auth_token(req)?;
+    let (user, _) = authenticate_session(req).await?;
+    Ok(user)
+}
+
+/// Authenticates the request if it carries an `Authorization` header, for endpoints that are
+/// public but personalise their response for a signed in user.
+pub(crate) async fn authenticate_optional(req: &Request<State>) -> Result<Option<UserResponse>, Error> {
+    if req.header("Authorization").is_none() {
+        return Ok(None);
+    }
+
+    authenticate(req).await.map(Some)
+}
+
+/// Like `authenticate`, but also returns the id of the session the request was made with.
+pub(crate) async fn authenticate_session(req: &Request<State>) -> Result<(UserResponse, Uuid), Error> {
+    if let Some(AuthenticatedSession(user, token_id)) = req.ext::<AuthenticatedSession>() {
+        return Ok((user.clone(), *token_id));
+    }
 
-    let db_pool = &req.state().db_pool;
-    let user = query_as!(UserResponse, r#"
-        select users.id, users.username
+    authenticate_token(get_auth_token(req)?, req.state()).await
+}
+
+/// A session a middleware has already looked up for this request, so that the handler doesn't
+/// look it up again.
+#[derive(Clone)]
+pub(crate) struct AuthenticatedSession(pub UserResponse, pub Uuid);
+
+/// Looks up the session for a raw auth token, for callers that don't take it from the
+/// `Authorization` header.
+pub(crate) async fn authenticate_token(token: &str, state: &State) -> Result<(UserResponse, Uuid), Error> {
+    let db_pool = &state.db_pool;
+    let token_hash = hash_token(token, &state.config)?;
+
+    let row = query!(r#"
+        select users.id as user_id, users.username, auth_tokens.id as token_id, auth_tokens.expires_at
         from users
         inner join auth_tokens 
             on auth_tokens.user_id = users.id
-            and auth_tokens.token = $1
+            and auth_tokens.token_hash = $1
         "#,
-        auth_token)
+        token_hash)
     .fetch_optional(db_pool)
     .await?;
 
-    user.ok_or_else(|| Error::from_str(StatusCode::Unauthorized, "Invalid auth token"))
+    let row = row.ok_or_else(|| api_error(ApiError::InvalidToken))?;
+
+    let now = Utc::now();
+    if row.expires_at <= now {
+        return Err(api_error(ApiError::TokenExpired));
+    }
+
+    query!("update auth_tokens set last_used_at = $1 where id = $2", now, row.token_id)
+        .execute(db_pool)
+        .await?;
+
+    let user = UserResponse {
+        id: row.user_id,
+        username: row.username,
+    };
+    Ok((user, row.token_id))
+}
+
+/// Starts a new session for `user_id`, returning the raw token to hand back to the client.
+/// Only the token's hash is persisted, so this is the one time the raw value is available.
+pub(crate) async fn create_auth_token(user_id: Uuid, device: Option<String>, state: &State) -> Result<String, Error> {
+    let raw_token = random_token();
+
+    let now = Utc::now();
+    query!(
+        r#"
+            insert into auth_tokens (id, user_id, token_hash, device, created_at, updated_at, last_used_at, expires_at)
+            values ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#,
+        Uuid::new_v4(),
+        user_id,
+        hash_token(&raw_token, &state.config)?,
+        device,
+        now,
+        now,
+        now,
+        now + Duration::days(state.config.auth_token_ttl_days),
+    ).execute(&state.db_pool).await?;
+
+    Ok(raw_token)
+}
+
+/// A fresh random secret, such as an auth or password reset token.
+pub(crate) fn random_token() -> String {
+    OsRng.sample_iter(&Alphanumeric).take(32).map(char::from).collect()
+}
+
+/// Keyed hash of an auth token, as stored in `auth_tokens.token_hash`.
+pub(crate) fn hash_token(token: &str, config: &Config) -> Result<String, Error> {
+    let mut mac = Hmac::<Sha256>::new_varkey(config.secret_key.expose().as_bytes())
+        .map_err(|_| api_error(ApiError::Internal))?;
+    mac.update(token.as_bytes());
+
+    Ok(hex::encode(mac.finalize().into_bytes()))
 }
 
 pub(crate) fn get_auth_token(req: &Request<State>) -> Result<&str, Error> {
@@ -55,10 +155,7 @@ pub(crate) fn get_auth_token(req: &Request<State>) -> Result<&str, Error> {
     let caps = match BEARER_TOKEN_REGEX.captures(header_value) {
         Some(caps) => caps,
         None => {
-            return Err(Error::from_str(
-                StatusCode::BadRequest,
-                "Unable to parse Authorization header value",
-            ))
+            return Err(api_error(ApiError::MalformedAuthHeader))
         }
     };
 
@@ -69,8 +166,15 @@ pub(crate) fn empty_response() -> Result<Response, Error> {
     Ok(Value::Null.to_response(StatusCode::Ok))
 }
 
-pub fn something_went_wrong(status_code: StatusCode) -> tide::Error {
-    tide::Error::from_str(status_code, "Something went wrong")
+/// Fails the request with `error`'s status, and its code in the body. Don't use `?` on an
+/// `ApiError` directly: that converts it into a plain 500.
+pub(crate) fn api_error(error: ApiError) -> tide::Error {
+    tide::Error::new(error.status(), error)
+}
+
+/// A 422 listing everything wrong with the request, field by field.
+pub(crate) fn validation_failed(errors: ValidationErrors) -> tide::Error {
+    api_error(ApiError::ValidationFailed { fields: errors.errors })
 }
 
 lazy_static! {
@@ -0,0 +1,221 @@
+use crate::BackendApiEndpoint;
+use tide::Request;
+use tide::http::StatusCode;
+use shared::{ApiEndpoint, Notifications, MarkNotificationsRead, NoPayload};
+use shared::errors::{ApiError, Resource};
+use shared::payloads::MarkNotificationsReadPayload;
+use shared::responses::{NotificationKind, NotificationResponse, PaginatedResponse, UnreadCountResponse, UserResponse};
+use crate::State;
+use crate::endpoints::{api_error, authenticate};
+use crate::endpoints::events::fetch_events;
+use crate::endpoints::pagination::{Cursor, Pagination};
+use chrono::Utc;
+use uuid::Uuid;
+use sqlx::{query, Executor, PgPool, Postgres};
+use async_trait::async_trait;
+use std::collections::HashMap;
+
+fn kind_name(kind: NotificationKind) -> &'static str {
+    match kind {
+        NotificationKind::Follow => "follow",
+        NotificationKind::Like => "like",
+        NotificationKind::Reply => "reply",
+        NotificationKind::Mention => "mention",
+        NotificationKind::FollowRequest => "follow_request",
+    }
+}
+
+fn kind_from_name(name: &str) -> tide::Result<NotificationKind> {
+    match name {
+        "follow" => Ok(NotificationKind::Follow),
+        "like" => Ok(NotificationKind::Like),
+        "reply" => Ok(NotificationKind::Reply),
+        "mention" => Ok(NotificationKind::Mention),
+        "follow_request" => Ok(NotificationKind::FollowRequest),
+        _ => {
+            tide::log::error!("Unknown notification kind '{}'", name);
+            Err(api_error(ApiError::Internal))
+        }
+    }
+}
+
+/// Tells `user_id` that `actor_id` did something involving them. Acting on your own things
+/// doesn't notify you.
+pub(crate) async fn notify<'c, E>(db: E, user_id: Uuid, actor_id: Uuid, kind: NotificationKind, event_id: Option<Uuid>) -> tide::Result<()>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    if user_id == actor_id {
+        return Ok(());
+    }
+
+    let now = Utc::now();
+    query!(
+        r#"
+            insert into notifications (id, user_id, actor_id, kind, event_id, created_at, updated_at)
+            values ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+        Uuid::new_v4(),
+        user_id,
+        actor_id,
+        kind_name(kind),
+        event_id,
+        now,
+        now,
+    ).execute(db).await?;
+
+    Ok(())
+}
+
+/// Like `notify`, for the author of `target_id`, such as the event being liked or replied to.
+pub(crate) async fn notify_author<'c, E>(db: E, target_id: Uuid, actor_id: Uuid, kind: NotificationKind, event_id: Uuid) -> tide::Result<()>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let now = Utc::now();
+    query!(
+        r#"
+            insert into notifications (id, user_id, actor_id, kind, event_id, created_at, updated_at)
+            select $1, events.user_id, $2, $3, $4, $5, $6
+            from events
+            where events.id = $7 and events.user_id <> $2
+        "#,
+        Uuid::new_v4(),
+        actor_id,
+        kind_name(kind),
+        event_id,
+        now,
+        now,
+        target_id,
+    ).execute(db).await?;
+
+    Ok(())
+}
+
+pub(crate) async fn unread_count(user_id: Uuid, db_pool: &PgPool) -> tide::Result<i64> {
+    let row = query!(
+        r#"
+            select count(*) as "count!"
+            from notifications
+            where user_id = $1 and read_at is null and not hidden_from($1, actor_id)
+        "#,
+        user_id,
+    )
+    .fetch_one(db_pool)
+    .await?;
+
+    Ok(row.count)
+}
+
+#[async_trait]
+impl BackendApiEndpoint for Notifications {
+    async fn handler(req: Request<State>, _: NoPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+        let current_user = authenticate(&req).await?;
+
+        let pagination = req.query::<Pagination>()?;
+        let before = pagination.before()?;
+        let after = pagination.after()?;
+
+        let rows = query!(
+            r#"
+                select
+                    notifications.id
+                    , notifications.kind
+                    , notifications.event_id
+                    , notifications.read_at
+                    , notifications.created_at
+                    , actors.id as actor_id
+                    , actors.username as actor_username
+                from notifications
+                inner join users actors on actors.id = notifications.actor_id
+                where notifications.user_id = $1
+                    and not hidden_from($1, notifications.actor_id)
+                    and ($4::timestamptz is null or (notifications.created_at, notifications.id) < ($4, $5))
+                    and ($6::timestamptz is null or (notifications.created_at, notifications.id) > ($6, $7))
+                order by
+                    case when $6::timestamptz is not null then notifications.created_at end asc
+                    , case when $6::timestamptz is not null then notifications.id end asc
+                    , notifications.created_at desc
+                    , notifications.id desc
+                limit $2
+                offset $3
+            "#,
+            current_user.id,
+            pagination.limit(),
+            pagination.offset(),
+            before.map(|cursor| cursor.at),
+            before.map(|cursor| cursor.id),
+            after.map(|cursor| cursor.at),
+            after.map(|cursor| cursor.id),
+        )
+        .fetch_all(db_pool)
+        .await?;
+
+        let (rows, next_cursor) = pagination.paginate(rows, |row| Cursor { at: row.created_at, id: row.id });
+
+        let event_ids = rows.iter().filter_map(|row| row.event_id).collect::<Vec<_>>();
+        let mut events = fetch_events(&event_ids, Some(current_user.id), db_pool)
+            .await?
+            .into_iter()
+            .map(|event| (event.id, event))
+            .collect::<HashMap<_, _>>();
+
+        let items = rows
+            .into_iter()
+            .map(|row| {
+                Ok(NotificationResponse {
+                    id: row.id,
+                    kind: kind_from_name(&row.kind)?,
+                    actor: UserResponse {
+                        id: row.actor_id,
+                        username: row.actor_username,
+                    },
+                    event: row.event_id.and_then(|id| events.remove(&id)),
+                    read: row.read_at.is_some(),
+                    created_at: row.created_at,
+                })
+            })
+            .collect::<tide::Result<Vec<_>>>()?;
+
+        Ok((PaginatedResponse { items, next_cursor }, StatusCode::Ok))
+    }
+}
+
+#[async_trait]
+impl BackendApiEndpoint for MarkNotificationsRead {
+    async fn handler(req: Request<State>, payload: MarkNotificationsReadPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+        let current_user = authenticate(&req).await?;
+
+        let up_to = query!(
+            "select id, created_at from notifications where id = $1 and user_id = $2 and not hidden_from($2, actor_id)",
+            payload.up_to,
+            current_user.id,
+        )
+        .fetch_optional(db_pool)
+        .await?
+        .ok_or_else(|| api_error(ApiError::NotFound { resource: Resource::Notification }))?;
+
+        let now = Utc::now();
+        query!(
+            r#"
+                update notifications
+                set read_at = $4, updated_at = $4
+                where user_id = $1
+                    and read_at is null
+                    and not hidden_from($1, actor_id)
+                    and (created_at, id) <= ($2, $3)
+            "#,
+            current_user.id,
+            up_to.created_at,
+            up_to.id,
+            now,
+        )
+        .execute(db_pool)
+        .await?;
+
+        let unread_count = unread_count(current_user.id, db_pool).await?;
+        Ok((UnreadCountResponse { unread_count }, StatusCode::Ok))
+    }
+}
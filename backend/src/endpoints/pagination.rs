@@ -0,0 +1,117 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use shared::errors::ApiError;
+use crate::endpoints::api_error;
+use uuid::Uuid;
+
+const MAX_PAGE_SIZE: usize = 20;
+
+/// Query string parameters accepted by paginated endpoints.
+///
+/// `before` and `after` take an opaque cursor from a previous response's `next_cursor` and
+/// select items older or newer than it. `page` is the older offset based scheme, kept for
+/// existing clients.
+#[derive(Debug, Deserialize)]
+pub(crate) struct Pagination {
+    page: Option<usize>,
+    page_size: Option<usize>,
+    before: Option<String>,
+    after: Option<String>,
+}
+
+impl Pagination {
+    pub fn page_size(&self) -> i64 {
+        self.page_size.unwrap_or(MAX_PAGE_SIZE).min(MAX_PAGE_SIZE) as i64
+    }
+
+    /// One more than the page size, so `paginate` can tell whether another page follows.
+    pub fn limit(&self) -> i64 {
+        self.page_size() + 1
+    }
+
+    pub fn page(&self) -> usize {
+        self.page.unwrap_or(1).max(1)
+    }
+
+    pub fn offset(&self) -> i64 {
+        (self.page() as i64 - 1) * self.page_size()
+    }
+
+    pub fn before(&self) -> tide::Result<Option<Cursor>> {
+        self.before.as_deref().map(Cursor::decode).transpose()
+    }
+
+    pub fn after(&self) -> tide::Result<Option<Cursor>> {
+        self.after.as_deref().map(Cursor::decode).transpose()
+    }
+
+    /// Trims rows fetched with `limit()` down to a page and works out the cursor for the next one.
+    ///
+    /// Queries order rows newest first, except when paging with `after`, where they are ordered
+    /// oldest first so the limit keeps the rows closest to the cursor. Either way the page
+    /// returned is newest first.
+    pub fn paginate<T>(&self, mut rows: Vec<T>, cursor_of: impl Fn(&T) -> Cursor) -> (Vec<T>, Option<String>) {
+        let page_size = self.page_size() as usize;
+        let has_more = rows.len() > page_size;
+        rows.truncate(page_size);
+
+        let next = if self.after.is_some() {
+            rows.reverse();
+            rows.first()
+        } else {
+            rows.last()
+        };
+
+        let next_cursor = if has_more { next.map(|row| cursor_of(row).encode()) } else { None };
+        (rows, next_cursor)
+    }
+}
+
+/// A position in a listing ordered by `(at, id)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Cursor {
+    pub at: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        let raw = format!("{}|{}", self.at.to_rfc3339(), self.id);
+        base64::encode_config(raw, base64::URL_SAFE_NO_PAD)
+    }
+
+    pub fn decode(value: &str) -> tide::Result<Cursor> {
+        let invalid = || api_error(ApiError::InvalidCursor);
+
+        let raw = base64::decode_config(value, base64::URL_SAFE_NO_PAD).map_err(|_| invalid())?;
+        let raw = String::from_utf8(raw).map_err(|_| invalid())?;
+        let (at, id) = raw.split_once('|').ok_or_else(invalid)?;
+
+        Ok(Cursor {
+            at: DateTime::parse_from_rfc3339(at).map_err(|_| invalid())?.with_timezone(&Utc),
+            id: id.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cursor_round_trips() {
+        let cursor = Cursor {
+            at: Utc::now(),
+            id: Uuid::new_v4(),
+        };
+
+        assert_eq!(Cursor::decode(&cursor.encode()).unwrap(), cursor);
+    }
+
+    #[test]
+    fn garbage_cursor_is_rejected() {
+        let err = Cursor::decode("not a cursor").unwrap_err();
+
+        assert_eq!(err.downcast_ref::<ApiError>(), Some(&ApiError::InvalidCursor));
+    }
+}
@@ -0,0 +1,167 @@
+use crate::BackendApiEndpoint;
+use tide::Request;
+use tide::http::StatusCode;
+use shared::{ApiEndpoint, ChangePassword, RequestPasswordReset, ResetPassword};
+use shared::payloads::{ChangePasswordPayload, RequestPasswordResetPayload, ResetPasswordPayload};
+use crate::State;
+use crate::endpoints::{api_error, authenticate_session, hash_token, random_token, validation_failed};
+use crate::endpoints::users::{hash_password, verify_password};
+use crate::mailer::Mail;
+use chrono::{Duration, Utc};
+use sqlx::query;
+use uuid::Uuid;
+use shared::errors::ApiError;
+use shared::validation::{validate_password, ValidationErrors};
+use async_trait::async_trait;
+
+const PASSWORD_RESET_TTL_HOURS: i64 = 1;
+
+fn validate_new_password(password: &str, username: &str) -> tide::Result<()> {
+    let mut errors = ValidationErrors::default();
+    if let Err(message) = validate_password(password, username) {
+        errors.add("new_password", message);
+    }
+    errors.into_result().map_err(validation_failed)
+}
+
+#[async_trait]
+impl BackendApiEndpoint for ChangePassword {
+    async fn handler(req: Request<State>, payload: ChangePasswordPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+        let (current_user, current_token_id) = authenticate_session(&req).await?;
+
+        validate_new_password(&payload.new_password, &current_user.username)?;
+
+        let row = query!("select hashed_password from users where id = $1", current_user.id)
+            .fetch_one(db_pool)
+            .await?;
+
+        if !verify_password(row.hashed_password, payload.current_password, &req.state().config).await? {
+            return Err(api_error(ApiError::IncorrectPassword));
+        }
+
+        let hashed_password = hash_password(payload.new_password, &req.state().config).await?;
+
+        let mut tx = db_pool.begin().await?;
+        query!(
+            "update users set hashed_password = $1, updated_at = now() where id = $2",
+            hashed_password,
+            current_user.id,
+        ).execute(&mut tx).await?;
+
+        // Whoever knew the old password is signed out everywhere but here.
+        query!(
+            "delete from auth_tokens where user_id = $1 and id <> $2",
+            current_user.id,
+            current_token_id,
+        ).execute(&mut tx).await?;
+
+        query!(
+            "update password_resets set used_at = now(), updated_at = now() where user_id = $1 and used_at is null",
+            current_user.id,
+        ).execute(&mut tx).await?;
+        tx.commit().await?;
+
+        Ok(((), StatusCode::Ok))
+    }
+}
+
+#[async_trait]
+impl BackendApiEndpoint for RequestPasswordReset {
+    async fn handler(req: Request<State>, payload: RequestPasswordResetPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+
+        let user = query!("select id, username, email from users where lower(username) = lower($1)", payload.username)
+            .fetch_optional(db_pool)
+            .await?;
+
+        // The response is the same whether or not a mail went out, so it can't be used to find
+        // out which accounts exist or have an email address.
+        if let Some((user_id, username, Some(email))) = user.map(|user| (user.id, user.username, user.email)) {
+            let token = random_token();
+            let now = Utc::now();
+
+            query!(
+                r#"
+                    insert into password_resets (id, user_id, token_hash, expires_at, created_at, updated_at)
+                    values ($1, $2, $3, $4, $5, $6)
+                "#,
+                Uuid::new_v4(),
+                user_id,
+                hash_token(&token, &req.state().config)?,
+                now + Duration::hours(PASSWORD_RESET_TTL_HOURS),
+                now,
+                now,
+            ).execute(db_pool).await?;
+
+            req.state().mailer.send(Mail {
+                to: email,
+                subject: "Reset your password".to_string(),
+                body: format!(
+                    "Someone asked to reset the password for @{}. If it was you, use this token within an hour:\n\n{}\n\nIf not, you can ignore this email.\n",
+                    username,
+                    token,
+                ),
+            }).await?;
+        }
+
+        Ok(((), StatusCode::Accepted))
+    }
+}
+
+#[async_trait]
+impl BackendApiEndpoint for ResetPassword {
+    async fn handler(req: Request<State>, payload: ResetPasswordPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+        let invalid = || api_error(ApiError::InvalidResetToken);
+
+        let reset = query!(
+            r#"
+                select password_resets.id, password_resets.user_id, users.username
+                from password_resets
+                inner join users on users.id = password_resets.user_id
+                where password_resets.token_hash = $1
+                    and password_resets.used_at is null
+                    and password_resets.expires_at > now()
+            "#,
+            hash_token(&payload.token, &req.state().config)?,
+        )
+        .fetch_optional(db_pool)
+        .await?
+        .ok_or_else(invalid)?;
+
+        validate_new_password(&payload.new_password, &reset.username)?;
+
+        let hashed_password = hash_password(payload.new_password, &req.state().config).await?;
+
+        let mut tx = db_pool.begin().await?;
+
+        // Claiming the token here, rather than trusting the lookup above, keeps it single use
+        // when two resets race.
+        let pg_res = query!(
+            "update password_resets set used_at = now(), updated_at = now() where id = $1 and used_at is null",
+            reset.id,
+        ).execute(&mut tx).await?;
+        if pg_res.rows_affected() != 1 {
+            return Err(invalid());
+        }
+
+        query!(
+            "update password_resets set used_at = now(), updated_at = now() where user_id = $1 and used_at is null",
+            reset.user_id,
+        ).execute(&mut tx).await?;
+
+        query!(
+            "update users set hashed_password = $1, updated_at = now() where id = $2",
+            hashed_password,
+            reset.user_id,
+        ).execute(&mut tx).await?;
+
+        query!("delete from auth_tokens where user_id = $1", reset.user_id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(((), StatusCode::Ok))
+    }
+}
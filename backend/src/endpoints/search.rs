@@ -0,0 +1,140 @@
+use crate::BackendApiEndpoint;
+use tide::Request;
+use tide::http::StatusCode;
+use shared::{ApiEndpoint, Search, NoPayload};
+use shared::errors::ApiError;
+use shared::responses::{ProfileResponse, SearchResponse, UserResponse};
+use crate::State;
+use crate::endpoints::{api_error, authenticate_optional};
+use crate::endpoints::events::{ListedEvent, listed_event_responses};
+use crate::endpoints::pagination::Pagination;
+use serde::Deserialize;
+use sqlx::{query, query_as};
+use async_trait::async_trait;
+
+/// How many accounts are listed above the matching events.
+const USER_RESULT_LIMIT: i64 = 5;
+
+#[derive(Debug, Deserialize)]
+struct SearchQuery {
+    #[serde(default)]
+    q: String,
+}
+
+/// Escapes `LIKE` wildcards so the query only ever matches as a literal prefix.
+fn like_prefix(q: &str) -> String {
+    let escaped = q
+        .to_lowercase()
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("{}%", escaped)
+}
+
+#[async_trait]
+impl BackendApiEndpoint for Search {
+    async fn handler(req: Request<State>, _: NoPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+        let viewer_id = authenticate_optional(&req).await?.map(|user| user.id);
+
+        let q = req.query::<SearchQuery>()?.q.trim().to_string();
+        if q.is_empty() {
+            return Err(api_error(ApiError::EmptySearchQuery));
+        }
+
+        let pagination = req.query::<Pagination>()?;
+
+        // Private accounts are still listed, just like their profiles can still be looked up.
+        let users = if pagination.page() == 1 {
+            query!(
+                r#"
+                    select
+                        id
+                        , username
+                        , display_name
+                        , bio
+                        , avatar_url
+                        , is_private
+                        , (select count(*) from follows where follows.followed_id = users.id) as "follower_count!"
+                        , (select count(*) from follows where follows.follower_id = users.id) as "following_count!"
+                        , (select count(*) from events where events.user_id = users.id) as "event_count!"
+                    from users
+                    where (lower(username) like $1 escape '\' or lower(display_name) like $1 escape '\')
+                        and not exists(
+                            select 1 from blocks
+                            where (blocker_id = $2 and blocked_id = users.id)
+                                or (blocked_id = $2 and blocker_id = users.id)
+                        )
+                    order by lower(username) = lower($3) desc, username
+                    limit $4
+                "#,
+                like_prefix(&q),
+                viewer_id,
+                q,
+                USER_RESULT_LIMIT,
+            )
+            .fetch_all(db_pool)
+            .await?
+            .into_iter()
+            .map(|row| ProfileResponse {
+                user: UserResponse { id: row.id, username: row.username },
+                display_name: row.display_name,
+                bio: row.bio,
+                avatar_url: row.avatar_url,
+                is_private: row.is_private,
+                follower_count: row.follower_count,
+                following_count: row.following_count,
+                event_count: row.event_count,
+            })
+            .collect()
+        } else {
+            vec![]
+        };
+
+        let mut rows = query_as!(ListedEvent,
+            r#"
+                select
+                    events.id as "id!"
+                    , events.created_at as "shown_at!"
+                    , null::uuid as "reposted_by_id?"
+                    , null::varchar as "reposted_by_username?"
+                from events
+                where events.search_vector @@ websearch_to_tsquery('english', $1)
+                    and visible_to($2, events.user_id)
+                order by
+                    ts_rank(events.search_vector, websearch_to_tsquery('english', $1)) desc
+                    , events.created_at desc
+                    , events.id desc
+                limit $3
+                offset $4
+            "#,
+            q,
+            viewer_id,
+            pagination.limit(),
+            pagination.offset(),
+        )
+        .fetch_all(db_pool)
+        .await?;
+
+        let has_more = rows.len() as i64 > pagination.page_size();
+        rows.truncate(pagination.page_size() as usize);
+        let events = listed_event_responses(rows, viewer_id, db_pool).await?;
+
+        Ok((SearchResponse {
+            users,
+            events,
+            next_page: if has_more { Some(pagination.page() + 1) } else { None },
+        }, StatusCode::Ok))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn like_prefix_escapes_wildcards() {
+        assert_eq!(like_prefix("Tim"), "tim%");
+        assert_eq!(like_prefix("100%_"), "100\\%\\_%");
+    }
+}
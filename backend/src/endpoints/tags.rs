@@ -0,0 +1,100 @@
+use crate::BackendApiEndpoint;
+use tide::Request;
+use tide::http::StatusCode;
+use shared::{ApiEndpoint, TagEvents, TrendingTags, NoPayload};
+use shared::responses::{PaginatedResponse, TrendingTagResponse};
+use crate::State;
+use crate::endpoints::authenticate_optional;
+use crate::endpoints::events::{ListedEvent, listed_event_responses};
+use crate::endpoints::pagination::{Cursor, Pagination};
+use chrono::{Duration, Utc};
+use sqlx::{query, query_as};
+use async_trait::async_trait;
+
+/// How far back `/tags/trending` looks when counting uses of each tag.
+const TRENDING_WINDOW_HOURS: i64 = 24;
+const TRENDING_LIMIT: i64 = 10;
+
+#[async_trait]
+impl BackendApiEndpoint for TagEvents {
+    async fn handler(req: Request<State>, _: NoPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+        let viewer_id = authenticate_optional(&req).await?.map(|user| user.id);
+        let tag = req.param("tag")?.trim_start_matches('#').to_lowercase();
+
+        let pagination = req.query::<Pagination>()?;
+        let before = pagination.before()?;
+        let after = pagination.after()?;
+
+        let rows = query_as!(ListedEvent,
+            r#"
+                select
+                    events.id as "id!"
+                    , events.created_at as "shown_at!"
+                    , null::uuid as "reposted_by_id?"
+                    , null::varchar as "reposted_by_username?"
+                from event_tags
+                inner join events on events.id = event_tags.event_id
+                where event_tags.tag = $1
+                    and visible_to($2, events.user_id)
+                    and not hidden_from($2, events.user_id)
+                    and ($5::timestamptz is null or (events.created_at, events.id) < ($5, $6))
+                    and ($7::timestamptz is null or (events.created_at, events.id) > ($7, $8))
+                order by
+                    case when $7::timestamptz is not null then events.created_at end asc
+                    , case when $7::timestamptz is not null then events.id end asc
+                    , events.created_at desc
+                    , events.id desc
+                limit $3
+                offset $4
+            "#,
+            tag,
+            viewer_id,
+            pagination.limit(),
+            pagination.offset(),
+            before.map(|cursor| cursor.at),
+            before.map(|cursor| cursor.id),
+            after.map(|cursor| cursor.at),
+            after.map(|cursor| cursor.id),
+        )
+        .fetch_all(db_pool)
+        .await?;
+
+        let (rows, next_cursor) = pagination.paginate(rows, |row| Cursor { at: row.shown_at, id: row.id });
+        let items = listed_event_responses(rows, viewer_id, db_pool).await?;
+
+        Ok((PaginatedResponse { items, next_cursor }, StatusCode::Ok))
+    }
+}
+
+#[async_trait]
+impl BackendApiEndpoint for TrendingTags {
+    async fn handler(req: Request<State>, _: NoPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+        let since = Utc::now() - Duration::hours(TRENDING_WINDOW_HOURS);
+
+        let rows = query!(
+            r#"
+                select event_tags.tag, count(*) as "event_count!"
+                from event_tags
+                inner join events on events.id = event_tags.event_id
+                where event_tags.created_at > $1
+                    and visible_to(null, events.user_id)
+                group by event_tags.tag
+                order by "event_count!" desc, event_tags.tag
+                limit $2
+            "#,
+            since,
+            TRENDING_LIMIT,
+        )
+        .fetch_all(db_pool)
+        .await?;
+
+        let trending = rows
+            .into_iter()
+            .map(|row| TrendingTagResponse { tag: row.tag, event_count: row.event_count })
+            .collect();
+
+        Ok((trending, StatusCode::Ok))
+    }
+}
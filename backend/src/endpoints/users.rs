@@ -1,23 +1,25 @@
-use shared::{GetUser, NoPayload, Login, ApiEndpoint, CreateUser};
+use shared::{GetUser, UserEvents, NoPayload, Login, ApiEndpoint, CreateUser};
 use crate::BackendApiEndpoint;
 use sqlx::PgPool;
 use sqlx::{query, query_as};
-use tide::{Error,Request};
+use tide::Request;
 use tide::http::StatusCode;
 use argonautica::{Hasher, Verifier};
-use rand::Rng;
-use rand::rngs::OsRng;
 use chrono::prelude::*;
 use uuid::Uuid;
-use rand::distributions::Alphanumeric;
 use futures::compat::Compat01As03;
 use failure::Fail;
 use crate::State;
 use crate::responses::BuildApiResponse;
-use shared::responses::{TokenResponse, UserResponse};
-use crate::env;
+use shared::responses::{NotificationKind, TokenResponse, UserResponse, ProfileResponse, RelationshipResponse, PaginatedResponse};
+use crate::endpoints::pagination::{Cursor, Pagination};
+use crate::endpoints::events::{ListedEvent, listed_event_responses};
+use crate::endpoints::notifications::notify;
+use crate::config::Config;
+use shared::errors::{ApiError, Resource};
 use shared::payloads::{CreateUserPayload, LoginPayload};
-use crate::endpoints::{authenticate, empty_response, get_auth_token, something_went_wrong};
+use crate::endpoints::{api_error, authenticate, authenticate_optional, create_auth_token, empty_response, get_auth_token, hash_token, validation_failed};
+use shared::validation::Validate;
 use async_trait::async_trait;
 
 
@@ -27,29 +29,21 @@ impl BackendApiEndpoint for CreateUser {
         let db_pool = &req.state().db_pool;
 
 
-        let result = query!("select 1 as one from users where username = $1", create_user.username)
-            .fetch_optional(db_pool)
-            .await?;
+        let mut errors = create_user.validate().err().unwrap_or_default();
 
-        if result.is_some() {
-            return Err(tide::Error::from_str(StatusCode::Conflict, "Submitted username already taken"));
-        }
-
-        let secret_key = std::env::var("SECRET_KEY")?;
-        let clear_text_password = create_user.password.clone();
+        if errors.for_field("username").is_none() {
+            let result = query!("select 1 as one from users where lower(username) = lower($1)", create_user.username)
+                .fetch_optional(db_pool)
+                .await?;
 
-        let mut hasher = Hasher::default();
-        if env::current().is_test() {
-            hasher.configure_iterations(10);
+            if result.is_some() {
+                errors.add("username", "is already taken");
+            }
         }
 
-        let hashed_password = Compat01As03::new(
-            hasher
-            .with_password(clear_text_password)
-            .with_secret_key(secret_key)
-            .hash_non_blocking()
-        ).await
-        .map_err(|err| err.compat())?;
+        errors.into_result().map_err(validation_failed)?;
+
+        let hashed_password = hash_password(create_user.password.clone(), &req.state().config).await?;
 
         let now = Utc::now();
         let row = query!(
@@ -62,24 +56,16 @@ impl BackendApiEndpoint for CreateUser {
             hashed_password,
             now,
             now,
-        ).fetch_one(db_pool).await?;
+        ).fetch_one(db_pool).await.map_err(|err| match &err {
+            // Someone else signed up with the name since it was checked above.
+            sqlx::Error::Database(db_err) if db_err.code().as_deref() == Some("23505") => api_error(ApiError::UsernameTaken),
+            _ => err.into(),
+        })?;
 
 
-        let raw_token: String = OsRng.sample_iter(&Alphanumeric).take(32).map(char::from).collect();
-        let token = query!(
-            r#"
-                insert into auth_tokens (id, user_id, token, created_at, updated_at)
-                values ($1, $2, $3, $4, $5) returning token
-            "#,
-            Uuid::new_v4(), 
-            row.id,
-            raw_token,
-            now,
-            now,
-        ).fetch_one(db_pool).await?;
+        let token = create_auth_token(row.id, None, req.state()).await?;
 
-
-        Ok((TokenResponse::new(&token.token),StatusCode::Created))
+        Ok((TokenResponse::new(&token),StatusCode::Created))
     }
 }
 
@@ -93,7 +79,7 @@ impl BackendApiEndpoint for Login {
             r#"
                 select id, hashed_password
                 from users
-                where username = $1
+                where lower(username) = lower($1)
             "#,
             username
         )
@@ -103,64 +89,101 @@ impl BackendApiEndpoint for Login {
         let password = payload.password;
         let user = match user {
             Some(user) => user,
-            None => return Err(Error::from_str(StatusCode::NotFound, "User not found")),
+            None => return Err(api_error(ApiError::NotFound { resource: Resource::User })),
         };
-        let user_password = user.hashed_password;
-        let secret_key = std::env::var("SECRET_KEY")?;
-
-        let mut verifier = Verifier::default();
-        let is_valid = Compat01As03::new(
-        verifier
-            .with_hash(user_password)
-            .with_password(password)
-            .with_secret_key(secret_key)
-            .verify_non_blocking()
-        ).await
-        .map_err(|err| err.compat())?;
+        let is_valid = verify_password(user.hashed_password, password, &req.state().config).await?;
 
         if is_valid {
-            let token_row = query!(
-                r#"
-                    select token
-                    from auth_tokens
-                    where user_id = $1
-                "#,
-                user.id
-            ).fetch_one(&db_pool).await?;
-
-            Ok((TokenResponse::new(&token_row.token),StatusCode::Created))
+            let token = create_auth_token(user.id, payload.device, req.state()).await?;
+
+            Ok((TokenResponse::new(&token),StatusCode::Created))
         } else {
-            Err(something_went_wrong(StatusCode::Forbidden))
+            Err(api_error(ApiError::IncorrectPassword))
         }
     }
 }
 
+pub(crate) async fn hash_password(password: String, config: &Config) -> tide::Result<String> {
+    let secret_key = config.secret_key.expose().to_string();
+
+    let mut hasher = Hasher::default();
+    hasher.configure_iterations(config.hash_iterations);
+
+    let hashed_password = Compat01As03::new(
+        hasher
+        .with_password(password)
+        .with_secret_key(secret_key)
+        .hash_non_blocking()
+    ).await
+    .map_err(|err| err.compat())?;
+
+    Ok(hashed_password)
+}
+
+pub(crate) async fn verify_password(hashed_password: String, password: String, config: &Config) -> tide::Result<bool> {
+    let secret_key = config.secret_key.expose().to_string();
+
+    let mut verifier = Verifier::default();
+    let is_valid = Compat01As03::new(
+    verifier
+        .with_hash(hashed_password)
+        .with_password(password)
+        .with_secret_key(secret_key)
+        .verify_non_blocking()
+    ).await
+    .map_err(|err| err.compat())?;
+
+    Ok(is_valid)
+}
+
 pub(crate) async fn follow(req: Request<State>) -> tide::Result {
     let db_pool = req.state().db_pool.clone();
     let current_user = authenticate(&req).await?;
     let username = req.param("username")?;
 
-    let row = query!("select id from users where username = $1", username).fetch_optional(&db_pool).await?;
-
-    let followed_id: Uuid = if let Some(row) = row {
-        row.id
-    } else {
-        todo!();
-    };
+    let followed_id = user_id_for_username(username, &db_pool).await?;
 
     if current_user.id == followed_id {
-        return Err(tide::Error::from_str(StatusCode::Conflict, "You cannot follow yourself"));
+        return Err(api_error(ApiError::CannotTargetSelf { action: "follow".to_string() }));
     }
 
-    if user_following(current_user.id, followed_id, &db_pool).await? {
-        return Err(tide::Error::from_str(StatusCode::Conflict, "You cannot follow the same user twice"));
+    if blocked_between(current_user.id, &[followed_id], &db_pool).await? {
+        return Err(api_error(ApiError::Blocked { action: "follow this user".to_string() }));
     }
 
     let now = Utc::now();
+
+    // Private accounts approve their followers, so ask rather than follow.
+    if is_private(followed_id, &db_pool).await? && !user_following(current_user.id, followed_id, &db_pool).await? {
+        let pg_res = query!(
+            r#"
+                insert into follow_requests (id, requester_id, requested_id, created_at, updated_at)
+                values ($1, $2, $3, $4, $5)
+                on conflict (requester_id, requested_id) do nothing
+            "#,
+            Uuid::new_v4(),
+            current_user.id,
+            followed_id,
+            now,
+            now,
+        ).execute(&db_pool).await?;
+
+        let status = if pg_res.rows_affected() == 1 {
+            notify(&db_pool, followed_id, current_user.id, NotificationKind::FollowRequest, None).await?;
+            StatusCode::Accepted
+        } else {
+            StatusCode::Ok
+        };
+
+        let relationship = relationship(current_user.id, followed_id, username, &db_pool).await?;
+        return Ok(relationship.to_response(status));
+    }
+
     let pg_res = query!(
         r#"
             insert into follows (id, follower_id, followed_id, created_at, updated_at)
             values ($1, $2, $3, $4, $5)
+            on conflict (follower_id, followed_id) do nothing
         "#,
         Uuid::new_v4(), 
         current_user.id,
@@ -169,11 +192,40 @@ pub(crate) async fn follow(req: Request<State>) -> tide::Result {
         now,
     ).execute(&db_pool).await?;
 
-    if pg_res.rows_affected() == 1 {
-        Ok(serde_json::Value::Null.to_response(StatusCode::Created))
+    // Following someone you already follow is a no-op rather than an error.
+    let status = if pg_res.rows_affected() == 1 {
+        notify(&db_pool, followed_id, current_user.id, NotificationKind::Follow, None).await?;
+        StatusCode::Created
     } else {
-        todo!()
-    }
+        StatusCode::Ok
+    };
+
+    let relationship = relationship(current_user.id, followed_id, username, &db_pool).await?;
+    Ok(relationship.to_response(status))
+}
+
+pub(crate) async fn unfollow(req: Request<State>) -> tide::Result {
+    let db_pool = req.state().db_pool.clone();
+    let current_user = authenticate(&req).await?;
+    let username = req.param("username")?;
+
+    let followed_id = user_id_for_username(username, &db_pool).await?;
+
+    query!(
+        "delete from follows where follower_id = $1 and followed_id = $2",
+        current_user.id,
+        followed_id,
+    ).execute(&db_pool).await?;
+
+    // Also withdraws a pending request to follow.
+    query!(
+        "delete from follow_requests where requester_id = $1 and requested_id = $2",
+        current_user.id,
+        followed_id,
+    ).execute(&db_pool).await?;
+
+    let relationship = relationship(current_user.id, followed_id, username, &db_pool).await?;
+    Ok(relationship.to_response(StatusCode::Ok))
 }
 
 pub(crate) async fn following(req: Request<State>) -> tide::Result {
@@ -181,46 +233,189 @@ pub(crate) async fn following(req: Request<State>) -> tide::Result {
     // let current_user = authenticate(&req).await?;
     let username = req.param("username")?;
 
-    let row = query!("select id from users where username = $1", username).fetch_optional(&db_pool).await?;
+    let user_id = user_id_for_username(username, &db_pool).await?;
+    let viewer_id = authenticate_optional(&req).await?.map(|user| user.id);
+    ensure_not_blocked(viewer_id, user_id, &db_pool).await?;
+    ensure_can_see_account(viewer_id, user_id, &db_pool).await?;
 
-    let user_id: Uuid = if let Some(row) = row {
-        row.id
-    } else {
-        todo!();
-    };
+    let pagination = req.query::<Pagination>()?;
+    let before = pagination.before()?;
+    let after = pagination.after()?;
 
-    let rows = query_as!(UserResponse,
+    let rows = query!(
         r#"
-            select users.id, users.username
+            select users.id, users.username, follows.id as follow_id, follows.created_at as followed_at
             from users
             inner join follows on follows.follower_id = $1
             and follows.followed_id = users.id
-        "#, user_id).fetch_all(&db_pool).await?;
-
-    Ok(rows.to_response(StatusCode::Ok))
+            where ($4::timestamptz is null or (follows.created_at, follows.id) < ($4, $5))
+                and ($6::timestamptz is null or (follows.created_at, follows.id) > ($6, $7))
+                and ($8::uuid is null or not exists (
+                    select 1 from blocks
+                    where (blocker_id = $8 and blocked_id = users.id)
+                        or (blocked_id = $8 and blocker_id = users.id)
+                ))
+            order by
+                case when $6::timestamptz is not null then follows.created_at end asc
+                , case when $6::timestamptz is not null then follows.id end asc
+                , follows.created_at desc
+                , follows.id desc
+            limit $2
+            offset $3
+        "#,
+        user_id,
+        pagination.limit(),
+        pagination.offset(),
+        before.map(|cursor| cursor.at),
+        before.map(|cursor| cursor.id),
+        after.map(|cursor| cursor.at),
+        after.map(|cursor| cursor.id),
+        viewer_id,
+    ).fetch_all(&db_pool).await?;
+
+    let (rows, next_cursor) = pagination.paginate(rows, |row| Cursor { at: row.followed_at, id: row.follow_id });
+    let items = rows
+        .into_iter()
+        .map(|row| UserResponse { id: row.id, username: row.username })
+        .collect();
+
+    Ok(PaginatedResponse { items, next_cursor }.to_response(StatusCode::Ok))
 }
 
 pub(crate) async fn followers(req: Request<State>) -> tide::Result {
     let db_pool = req.state().db_pool.clone();
     let username = req.param("username")?;
 
-    let row = query!("select id from users where username = $1", username).fetch_optional(&db_pool).await?;
+    let user_id = user_id_for_username(username, &db_pool).await?;
+    let viewer_id = authenticate_optional(&req).await?.map(|user| user.id);
+    ensure_not_blocked(viewer_id, user_id, &db_pool).await?;
+    ensure_can_see_account(viewer_id, user_id, &db_pool).await?;
 
-    let user_id: Uuid = if let Some(row) = row {
-        row.id
-    } else {
-        todo!();
-    };
+    let pagination = req.query::<Pagination>()?;
+    let before = pagination.before()?;
+    let after = pagination.after()?;
 
-    let rows = query_as!(UserResponse,
+    let rows = query!(
         r#"
-            select users.id, users.username
+            select users.id, users.username, follows.id as follow_id, follows.created_at as followed_at
             from users
             inner join follows on follows.followed_id = $1
             and follows.follower_id = users.id
-        "#, user_id).fetch_all(&db_pool).await?;
+            where ($4::timestamptz is null or (follows.created_at, follows.id) < ($4, $5))
+                and ($6::timestamptz is null or (follows.created_at, follows.id) > ($6, $7))
+                and ($8::uuid is null or not exists (
+                    select 1 from blocks
+                    where (blocker_id = $8 and blocked_id = users.id)
+                        or (blocked_id = $8 and blocker_id = users.id)
+                ))
+            order by
+                case when $6::timestamptz is not null then follows.created_at end asc
+                , case when $6::timestamptz is not null then follows.id end asc
+                , follows.created_at desc
+                , follows.id desc
+            limit $2
+            offset $3
+        "#,
+        user_id,
+        pagination.limit(),
+        pagination.offset(),
+        before.map(|cursor| cursor.at),
+        before.map(|cursor| cursor.id),
+        after.map(|cursor| cursor.at),
+        after.map(|cursor| cursor.id),
+        viewer_id,
+    ).fetch_all(&db_pool).await?;
+
+    let (rows, next_cursor) = pagination.paginate(rows, |row| Cursor { at: row.followed_at, id: row.follow_id });
+    let items = rows
+        .into_iter()
+        .map(|row| UserResponse { id: row.id, username: row.username })
+        .collect();
+
+    Ok(PaginatedResponse { items, next_cursor }.to_response(StatusCode::Ok))
+}
+
+pub(crate) async fn user_id_for_username(username: &str, db_pool: &PgPool) -> tide::Result<Uuid> {
+    let row = query!("select id from users where lower(username) = lower($1)", username)
+        .fetch_optional(db_pool)
+        .await?;
+
+    row.map(|row| row.id)
+        .ok_or_else(|| api_error(ApiError::NotFound { resource: Resource::User }))
+}
+
+/// Whether `user_id` has blocked, or been blocked by, any of `other_ids`.
+pub(crate) async fn blocked_between(user_id: Uuid, other_ids: &[Uuid], db_pool: &PgPool) -> tide::Result<bool> {
+    let row = query!(
+        r#"
+            select exists(
+                select 1 from blocks
+                where (blocker_id = $1 and blocked_id = any($2))
+                    or (blocked_id = $1 and blocker_id = any($2))
+            ) as "blocked!"
+        "#,
+        user_id,
+        other_ids,
+    )
+    .fetch_one(db_pool)
+    .await?;
+
+    Ok(row.blocked)
+}
+
+/// Hides users from anyone they have blocked or been blocked by, as if they didn't exist.
+async fn ensure_not_blocked(viewer_id: Option<Uuid>, user_id: Uuid, db_pool: &PgPool) -> tide::Result<()> {
+    match viewer_id {
+        Some(viewer_id) if blocked_between(viewer_id, &[user_id], db_pool).await? => {
+            Err(api_error(ApiError::NotFound { resource: Resource::User }))
+        }
+        _ => Ok(()),
+    }
+}
+
+async fn is_private(user_id: Uuid, db_pool: &PgPool) -> tide::Result<bool> {
+    let row = query!("select is_private from users where id = $1", user_id)
+        .fetch_one(db_pool)
+        .await?;
+
+    Ok(row.is_private)
+}
+
+/// Only a private account itself and its followers get to see who it follows and is followed by,
+/// or what it posts.
+pub(crate) async fn ensure_can_see_account(viewer_id: Option<Uuid>, user_id: Uuid, db_pool: &PgPool) -> tide::Result<()> {
+    if viewer_id == Some(user_id) || !is_private(user_id, db_pool).await? {
+        return Ok(());
+    }
+
+    match viewer_id {
+        Some(viewer_id) if user_following(viewer_id, user_id, db_pool).await? => Ok(()),
+        _ => Err(api_error(ApiError::PrivateAccount)),
+    }
+}
+
+pub(crate) async fn relationship(current_user_id: Uuid, other_user_id: Uuid, username: &str, db_pool: &PgPool) -> tide::Result<RelationshipResponse> {
+    let row = query!(
+        r#"
+            select
+                exists(select 1 from follow_requests where requester_id = $1 and requested_id = $2) as "requested!"
+                , exists(select 1 from blocks where blocker_id = $1 and blocked_id = $2) as "blocking!"
+                , exists(select 1 from mutes where muter_id = $1 and muted_id = $2) as "muting!"
+        "#,
+        current_user_id,
+        other_user_id,
+    )
+    .fetch_one(db_pool)
+    .await?;
 
-    Ok(rows.to_response(StatusCode::Ok))
+    Ok(RelationshipResponse {
+        username: username.to_string(),
+        following: user_following(current_user_id, other_user_id, db_pool).await?,
+        followed_by: user_following(other_user_id, current_user_id, db_pool).await?,
+        requested: row.requested,
+        blocking: row.blocking,
+        muting: row.muting,
+    })
 }
 
 async fn user_following(current_user_id: Uuid, followee_id: Uuid, db_pool: &PgPool,) -> tide::Result<bool> {
@@ -243,31 +438,108 @@ impl BackendApiEndpoint for GetUser {
     async fn handler(req: Request<State>, _: NoPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
         let db_pool = &req.state().db_pool;
         let username = req.param("username")?;
+        let viewer_id = authenticate_optional(&req).await?.map(|user| user.id);
+
+        let user_id = user_id_for_username(username, db_pool).await?;
+        ensure_not_blocked(viewer_id, user_id, db_pool).await?;
+
+        let resp = profile(user_id, db_pool).await?;
+        Ok((resp, StatusCode::Ok))
+    }
+}
+
+#[async_trait]
+impl BackendApiEndpoint for UserEvents {
+    async fn handler(req: Request<State>, _: NoPayload) -> tide::Result<(<Self as ApiEndpoint>::Response, StatusCode)> {
+        let db_pool = &req.state().db_pool;
+        let username = req.param("username")?;
+        let viewer_id = authenticate_optional(&req).await?.map(|user| user.id);
+
+        let user_id = user_id_for_username(username, db_pool).await?;
+        ensure_not_blocked(viewer_id, user_id, db_pool).await?;
+        ensure_can_see_account(viewer_id, user_id, db_pool).await?;
 
+        let pagination = req.query::<Pagination>()?;
+        let before = pagination.before()?;
+        let after = pagination.after()?;
 
-        let user = query_as!(UserResponse,
+        let rows = query_as!(ListedEvent,
             r#"
-                select id, username
-                from users
-                where username = $1
+                select
+                    events.id as "id!"
+                    , events.created_at as "shown_at!"
+                    , null::uuid as "reposted_by_id?"
+                    , null::varchar as "reposted_by_username?"
+                from events
+                where events.user_id = $1
+                    and ($4::timestamptz is null or (events.created_at, events.id) < ($4, $5))
+                    and ($6::timestamptz is null or (events.created_at, events.id) > ($6, $7))
+                order by
+                    case when $6::timestamptz is not null then events.created_at end asc
+                    , case when $6::timestamptz is not null then events.id end asc
+                    , events.created_at desc
+                    , events.id desc
+                limit $2
+                offset $3
             "#,
-            username
+            user_id,
+            pagination.limit(),
+            pagination.offset(),
+            before.map(|cursor| cursor.at),
+            before.map(|cursor| cursor.id),
+            after.map(|cursor| cursor.at),
+            after.map(|cursor| cursor.id),
         )
-        .fetch_optional(db_pool).await?;
+        .fetch_all(db_pool)
+        .await?;
 
+        let (rows, next_cursor) = pagination.paginate(rows, |row| Cursor { at: row.shown_at, id: row.id });
+        let items = listed_event_responses(rows, viewer_id, db_pool).await?;
 
-        let resp = user.ok_or_else(|| tide::Error::from_str(StatusCode::NotFound, "User does not exist"))?;
-        Ok((resp, StatusCode::Ok))
-
+        Ok((PaginatedResponse { items, next_cursor }, StatusCode::Ok))
     }
 }
 
+/// A user's profile fields along with how many followers, followees and events they have.
+pub(crate) async fn profile(user_id: Uuid, db_pool: &PgPool) -> tide::Result<ProfileResponse> {
+    let row = query!(
+        r#"
+            select
+                id
+                , username
+                , display_name
+                , bio
+                , avatar_url
+                , is_private
+                , (select count(*) from follows where follows.followed_id = users.id) as "follower_count!"
+                , (select count(*) from follows where follows.follower_id = users.id) as "following_count!"
+                , (select count(*) from events where events.user_id = users.id) as "event_count!"
+            from users
+            where id = $1
+        "#,
+        user_id,
+    )
+    .fetch_one(db_pool)
+    .await?;
+
+    Ok(ProfileResponse {
+        user: UserResponse { id: row.id, username: row.username },
+        display_name: row.display_name,
+        bio: row.bio,
+        avatar_url: row.avatar_url,
+        is_private: row.is_private,
+        follower_count: row.follower_count,
+        following_count: row.following_count,
+        event_count: row.event_count,
+    })
+}
+
 pub(crate) async fn logout(req: Request<State>) -> tide::Result {
     authenticate(&req).await?;
-    let auth_token = get_auth_token(&req)?;
+    let token_hash = hash_token(get_auth_token(&req)?, &req.state().config)?;
 
     let db_pool = &req.state().db_pool;
-    query!("delete from auth_tokens where token = $1", auth_token)
+    query!("delete from auth_tokens where token_hash = $1", token_hash)
         .execute(db_pool)
         .await?;
 
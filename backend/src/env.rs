@@ -1,4 +1,8 @@
-#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+use serde::Deserialize;
+use std::str::FromStr;
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Env {
     Production,
     Development,
@@ -11,12 +15,16 @@ impl Env {
     }
 }
 
-pub fn current() -> Env {
-    match std::env::var("APP_ENV").unwrap().as_str() {
-        "production" => Env::Production,
-        "development" => Env::Development,
-        "test" => Env::Test,
-        _ => panic!("Unknown Evironment"),
+impl FromStr for Env {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "production" => Ok(Env::Production),
+            "development" => Ok(Env::Development),
+            "test" => Ok(Env::Test),
+            _ => Err("expected production, development or test".to_string()),
+        }
     }
 }
 
@@ -26,10 +34,9 @@ mod test {
     use super::*;
 
     #[test]
-    fn in_test_env() {
-        std::env::set_var("APP_ENV", "test");
-        let env = current();
-
-        assert!(env.is_test());
+    fn parsing_envs() {
+        assert!("test".parse::<Env>().unwrap().is_test());
+        assert_eq!("production".parse::<Env>(), Ok(Env::Production));
+        assert!("staging".parse::<Env>().is_err());
     }
 }
\ No newline at end of file
@@ -0,0 +1,110 @@
+use async_trait::async_trait;
+use std::fmt::Debug;
+use std::io;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// Where mail goes when running tests; see `FileMailer::sent_to`.
+pub(crate) fn test_mail_dir() -> PathBuf {
+    std::env::temp_dir().join("backend-test-mail")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+impl Mail {
+    fn render(&self) -> String {
+        format!("To: {}\nSubject: {}\n\n{}", self.to, self.subject, self.body)
+    }
+}
+
+/// Delivers emails, such as password reset tokens.
+#[async_trait]
+pub(crate) trait Mailer: Debug + Send + Sync + 'static {
+    async fn send(&self, mail: Mail) -> io::Result<()>;
+}
+
+/// Writes each email to the log instead of sending it, for development.
+#[derive(Debug, Default)]
+pub(crate) struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, mail: Mail) -> io::Result<()> {
+        tide::log::info!("Sending mail\n{}", mail.render());
+        Ok(())
+    }
+}
+
+/// Writes each email to its own file under `root`, named after the recipient so tests can find
+/// what was sent to them.
+#[derive(Debug)]
+pub(crate) struct FileMailer {
+    root: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        FileMailer { root: root.into() }
+    }
+
+    /// Everything sent to `to`, in no particular order.
+    #[cfg(test)]
+    pub async fn sent_to(&self, to: &str) -> io::Result<Vec<String>> {
+        let mut mails = Vec::new();
+        let mut entries = match async_std::fs::read_dir(&self.root).await {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(mails),
+            Err(err) => return Err(err),
+        };
+
+        while let Some(entry) = futures::StreamExt::next(&mut entries).await {
+            let path = entry?.path();
+            let prefix = format!("{}-", to);
+            if path.file_name().and_then(|name| name.to_str()).map_or(false, |name| name.starts_with(&prefix)) {
+                mails.push(async_std::fs::read_to_string(path).await?);
+            }
+        }
+
+        Ok(mails)
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, mail: Mail) -> io::Result<()> {
+        async_std::fs::create_dir_all(&self.root).await?;
+        let path = self.root.join(format!("{}-{}.eml", mail.to, Uuid::new_v4()));
+        async_std::fs::write(path, mail.render()).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[async_std::test]
+    async fn file_mailer_keeps_mail_per_recipient() {
+        let root = std::env::temp_dir().join(format!("mailer-test-{}", Uuid::new_v4()));
+        let mailer = FileMailer::new(&root);
+
+        assert!(mailer.sent_to("tim@example.com").await.unwrap().is_empty());
+
+        let mail = Mail {
+            to: "tim@example.com".to_string(),
+            subject: "Hello".to_string(),
+            body: "Hi Tim".to_string(),
+        };
+        mailer.send(mail).await.unwrap();
+
+        let sent = mailer.sent_to("tim@example.com").await.unwrap();
+        assert_eq!(sent, vec!["To: tim@example.com\nSubject: Hello\n\nHi Tim".to_string()]);
+        assert!(mailer.sent_to("jim@example.com").await.unwrap().is_empty());
+
+        async_std::fs::remove_dir_all(&root).await.unwrap();
+    }
+}
@@ -1,21 +1,36 @@
-use shared::{Login, Me, Timeline};
+use shared::{Login, Me, UpdateMe, ChangePassword, RequestPasswordReset, ResetPassword, Timeline, Mentions, Notifications, MarkNotificationsRead, ListSessions, DeleteSession};
 use tide::Server;
-use tide::security::CorsMiddleware;
-use sqlx::{Pool, PgPool};
-use http_types::headers::HeaderValue;
-use tide::security::Origin;
+use sqlx::PgPool;
+use sqlx::postgres::PgPoolOptions;
 use tide::http::Method;
 use tide::{Request, Response, Body, StatusCode};
-use shared::{ApiEndpoint, GetUser, PostEvent, NoPayload, CreateUser};
-use shared::payloads::{CreateEventPayload, LoginPayload, CreateUserPayload};
+use shared::{ApiEndpoint, GetUser, UserEvents, FollowRequests, ApproveFollowRequest, RejectFollowRequest, BlockUser, UnblockUser, MuteUser, UnmuteUser, ListBlocks, ListMutes, PostEvent, EditEvent, DeleteEvent, GetEventRevisions, GetThread, LikeEvent, UnlikeEvent, Repost, Unrepost, ListConversations, CreateConversation, ListMessages, SendMessage, TagEvents, TrendingTags, UploadMedia, Search, NoPayload, CreateUser};
+use shared::payloads::{CreateEventPayload, EditEventPayload, LoginPayload, CreateUserPayload, MarkNotificationsReadPayload, CreateConversationPayload, SendMessagePayload, UpdateMePayload, ChangePasswordPayload, RequestPasswordResetPayload, ResetPasswordPayload};
 use async_trait::async_trait;
+use broadcast::{Broadcaster, NewEvent};
+use storage::{Storage, FileStorage, MemoryStorage};
+use mailer::{Mailer, FileMailer, LogMailer};
+use std::sync::Arc;
+use config::Config;
+use rate_limit::{MemoryRateLimitStore, RateLimit};
+use openapi::ApiDoc;
+use schemars::gen::SchemaGenerator;
+use shared::{QueryParam, PAGINATION_PARAMS};
+use shared::responses::{PaginatedResponse, RelationshipResponse, UserResponse};
 
 #[cfg(test)]
 mod tests;
 mod env;
+mod config;
 mod responses;
 mod endpoints;
 mod middlewares;
+mod broadcast;
+mod storage;
+mod mailer;
+mod migrations;
+mod rate_limit;
+mod openapi;
 
 #[async_std::main]
 async fn main() -> tide::Result<()>{
@@ -31,57 +46,182 @@ async fn main() -> tide::Result<()>{
     // println!("block_number: {:?}", block_number);
 
 
-    let db_pool = make_db_pool().await;
-    let server = server(db_pool).await;
+    let (config, args) = match Config::load(std::env::args().skip(1).collect()) {
+        Ok(loaded) => loaded,
+        Err(err) => {
+            eprintln!("Invalid configuration: {}", err);
+            std::process::exit(2);
+        }
+    };
 
-    server.listen("127.0.0.1:8080").await?;
+    let db_pool = make_db_pool(&config).await?;
+
+    match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        [] => {}
+        ["migrate"] | ["migrate", "up"] => {
+            for name in migrations::run(&db_pool, &config).await? {
+                println!("Applied {}", name);
+            }
+            return Ok(());
+        }
+        ["migrate", "down", steps @ ..] => {
+            let steps = match steps {
+                [] => 1,
+                [steps] => steps.parse()?,
+                _ => usage(),
+            };
+            for name in migrations::rollback(&db_pool, steps).await? {
+                println!("Rolled back {}", name);
+            }
+            return Ok(());
+        }
+        _ => usage(),
+    }
+
+    migrations::run(&db_pool, &config).await?;
+    let bind_address = config.bind_address;
+    let server = server(config, db_pool).await;
+
+    server.listen(bind_address.to_string()).await?;
 
     Ok(())
 }
 
-async fn server(db_pool: PgPool) -> Server<State> {
-    let mut server: Server<State> = Server::with_state(State{db_pool});
+fn usage() -> ! {
+    eprintln!("Usage: backend [--setting value]... [migrate [up | down [steps]]]");
+    std::process::exit(2);
+}
 
-    server.with(CorsMiddleware::new()
-        .allow_methods("GET, POST, PUT, PATCH, DELETE, OPTIONS".parse::<HeaderValue>().unwrap())
-        .allow_origin(Origin::Any)
-        .allow_credentials(true));
+async fn server(config: Config, db_pool: PgPool) -> Server<State> {
+    let storage: Arc<dyn Storage> = if config.app_env.is_test() {
+        Arc::new(MemoryStorage::default())
+    } else {
+        Arc::new(FileStorage::new(&config.media_root))
+    };
+    let mailer: Arc<dyn Mailer> = if config.app_env.is_test() {
+        Arc::new(FileMailer::new(mailer::test_mail_dir()))
+    } else {
+        match &config.mail_dir {
+            Some(dir) => Arc::new(FileMailer::new(dir)),
+            None => Arc::new(LogMailer),
+        }
+    };
+    let config = Arc::new(config);
+    let mut server: Server<State> = Server::with_state(State{db_pool, config: config.clone(), new_events: Broadcaster::new(), storage, mailer});
+
+    server.with(middlewares::Cors::new(&config.cors_origins));
+    // Outside of `ErrResponseToJson`, which replaces error responses along with their headers.
+    if config.rate_limiting {
+        server.with(RateLimit::new(
+            Arc::new(MemoryRateLimitStore::default()),
+            rate_limit::default_rules(),
+            &config.rate_limits,
+            config.trust_forwarded_for,
+        ));
+    }
     server.with(middlewares::ErrResponseToJson);
 
-    add_endpoint::<CreateUser>(&mut server);
+    let mut api = ApiDoc::new();
+
+    add_endpoint::<CreateUser>(&mut server, &mut api);
 
     // server.at("/me").get(endpoints::me::get);
-    add_endpoint::<Me>(&mut server);
+    add_endpoint::<Me>(&mut server, &mut api);
+    add_endpoint::<UpdateMe>(&mut server, &mut api);
+    add_endpoint::<ChangePassword>(&mut server, &mut api);
 
     // server.at("/me/timeline").get(endpoints::me::timeline);
-    add_endpoint::<Timeline>(&mut server);
-
-    add_endpoint::<Login>(&mut server);
+    add_endpoint::<Timeline>(&mut server, &mut api);
+    server.at("/me/timeline/stream").get(endpoints::me::timeline_stream);
+    api.raw_route(Method::Get, "/me/timeline/stream", &[QueryParam::optional("access_token")], "TimelineStream", "text/event-stream");
+    add_endpoint::<Mentions>(&mut server, &mut api);
+    add_endpoint::<Notifications>(&mut server, &mut api);
+    add_endpoint::<MarkNotificationsRead>(&mut server, &mut api);
+
+    add_endpoint::<ListSessions>(&mut server, &mut api);
+    add_endpoint::<DeleteSession>(&mut server, &mut api);
+
+    add_endpoint::<Login>(&mut server, &mut api);
+    add_endpoint::<RequestPasswordReset>(&mut server, &mut api);
+    add_endpoint::<ResetPassword>(&mut server, &mut api);
     server.at("/users/:username/session").delete(endpoints::users::logout);
+    api.route::<()>(Method::Delete, "/users/:username/session", &[], "Logout");
 
     server.at("/users/:username/follow").post(endpoints::users::follow);
+    server.at("/users/:username/follow").delete(endpoints::users::unfollow);
+    api.route::<RelationshipResponse>(Method::Post, "/users/:username/follow", &[], "Follow");
+    api.route::<RelationshipResponse>(Method::Delete, "/users/:username/follow", &[], "Unfollow");
 
     server.at("/users/:username/following").get(endpoints::users::following);
+    api.route::<PaginatedResponse<UserResponse>>(Method::Get, "/users/:username/following", PAGINATION_PARAMS, "Following");
 
     server.at("/users/:username/followers").get(endpoints::users::followers);
+    api.route::<PaginatedResponse<UserResponse>>(Method::Get, "/users/:username/followers", PAGINATION_PARAMS, "Followers");
 
     // server.at("/users/:username").get(endpoints::users::get);
-    add_endpoint::<GetUser>(&mut server);
+    add_endpoint::<GetUser>(&mut server, &mut api);
+    add_endpoint::<UserEvents>(&mut server, &mut api);
+
+    add_endpoint::<BlockUser>(&mut server, &mut api);
+    add_endpoint::<UnblockUser>(&mut server, &mut api);
+    add_endpoint::<MuteUser>(&mut server, &mut api);
+    add_endpoint::<UnmuteUser>(&mut server, &mut api);
+    add_endpoint::<ListBlocks>(&mut server, &mut api);
+    add_endpoint::<ListMutes>(&mut server, &mut api);
+
+    add_endpoint::<FollowRequests>(&mut server, &mut api);
+    add_endpoint::<ApproveFollowRequest>(&mut server, &mut api);
+    add_endpoint::<RejectFollowRequest>(&mut server, &mut api);
 
     // server.at("/events").post(endpoints::events::create);
-    add_endpoint::<PostEvent>(&mut server);
+    add_endpoint::<PostEvent>(&mut server, &mut api);
+    add_endpoint::<EditEvent>(&mut server, &mut api);
+    add_endpoint::<DeleteEvent>(&mut server, &mut api);
+    add_endpoint::<GetEventRevisions>(&mut server, &mut api);
+    add_endpoint::<GetThread>(&mut server, &mut api);
+    add_endpoint::<LikeEvent>(&mut server, &mut api);
+    add_endpoint::<UnlikeEvent>(&mut server, &mut api);
+    add_endpoint::<Repost>(&mut server, &mut api);
+    add_endpoint::<Unrepost>(&mut server, &mut api);
+
+    add_endpoint::<ListConversations>(&mut server, &mut api);
+    add_endpoint::<CreateConversation>(&mut server, &mut api);
+    add_endpoint::<ListMessages>(&mut server, &mut api);
+    add_endpoint::<SendMessage>(&mut server, &mut api);
+
+    add_endpoint::<TagEvents>(&mut server, &mut api);
+    add_endpoint::<TrendingTags>(&mut server, &mut api);
+
+    add_endpoint::<UploadMedia>(&mut server, &mut api);
+    server.at("/media/:file").get(endpoints::media::serve);
+    api.raw_route(Method::Get, "/media/:file", &[], "ServeMedia", "image/*");
+
+    add_endpoint::<Search>(&mut server, &mut api);
+
+    let spec = api.into_json();
+    server.at("/openapi.json").get(move |_| {
+        let spec = spec.clone();
+        async move { Body::from_json(&spec) }
+    });
 
     server
 }
 
-async fn make_db_pool() -> PgPool {
-    let db_url = std::env::var("DATABASE_URL").unwrap();
-    Pool::connect(&db_url).await.unwrap()
+async fn make_db_pool(config: &Config) -> tide::Result<PgPool> {
+    let db_pool = PgPoolOptions::new()
+        .max_connections(config.db_pool_size)
+        .connect(&config.database_url)
+        .await?;
+    Ok(db_pool)
 }
 
 #[derive(Debug, Clone)]
 struct State{
     db_pool: PgPool,
+    config: Arc<Config>,
+    new_events: Broadcaster<NewEvent>,
+    storage: Arc<dyn Storage>,
+    mailer: Arc<dyn Mailer>,
 }
 
 #[async_trait]
@@ -92,6 +232,9 @@ trait BackendApiEndpoint: ApiEndpoint {
 #[async_trait]
 trait GetRequestPayload: Sized {
     async fn get_payload(req: &mut Request<State>) -> tide::Result<Self>;
+
+    /// The OpenAPI `requestBody` for this payload, if it's sent with one.
+    fn request_body(schemas: &mut SchemaGenerator) -> Option<serde_json::Value>;
 }
 
 #[async_trait]
@@ -99,6 +242,10 @@ impl GetRequestPayload for NoPayload {
     async fn get_payload(_: &mut Request<State>) -> tide::Result<Self> {
         Ok(NoPayload)
     }
+
+    fn request_body(_: &mut SchemaGenerator) -> Option<serde_json::Value> {
+        None
+    }
 }
 
 macro_rules! impl_get_request_payload {
@@ -108,19 +255,33 @@ macro_rules! impl_get_request_payload {
             async fn get_payload(req: &mut Request<State>) -> tide::Result<Self> {
                 req.body_json().await
             }
+
+            fn request_body(schemas: &mut SchemaGenerator) -> Option<serde_json::Value> {
+                Some(openapi::json_body(schemas.subschema_for::<$name>()))
+            }
         }
     };
 }
 
 impl_get_request_payload!(CreateEventPayload);
+impl_get_request_payload!(EditEventPayload);
 impl_get_request_payload!(LoginPayload);
 impl_get_request_payload!(CreateUserPayload);
-
-fn add_endpoint<E>(server: &mut Server<State>)
+impl_get_request_payload!(MarkNotificationsReadPayload);
+impl_get_request_payload!(CreateConversationPayload);
+impl_get_request_payload!(SendMessagePayload);
+impl_get_request_payload!(UpdateMePayload);
+impl_get_request_payload!(ChangePasswordPayload);
+impl_get_request_payload!(RequestPasswordResetPayload);
+impl_get_request_payload!(ResetPasswordPayload);
+
+fn add_endpoint<E>(server: &mut Server<State>, api: &mut ApiDoc)
 where 
     E: 'static + BackendApiEndpoint,
     E::Payload: GetRequestPayload + Send,
 {
+    api.endpoint::<E>();
+
     let mut route = server.at(<E::Url as shared::Url>::URL_SPEC);
 
     let handler = |mut req: Request<State>| async {
@@ -138,7 +299,7 @@ where
         Method::Post => route.post(handler),
         Method::Head => route.head(handler),
         Method::Put => route.put(handler),
-        Method::Delete => route.options(handler),
+        Method::Delete => route.delete(handler),
         Method::Connect => route.connect(handler),
         Method::Options => route.options(handler),
         Method::Trace => route.trace(handler),
@@ -1,5 +1,8 @@
-use tide::{Request, Response, Next, Middleware};
+use tide::{Request, Response, Next, Middleware, StatusCode};
+use tide::http::Method;
+use tide::http::headers;
 use serde_json::json;
+use shared::errors::{ApiError, ErrorBody, ErrorResponse};
 
 
 
@@ -13,14 +16,16 @@ impl<State: Clone + Send + Sync + 'static> Middleware<State> for ErrResponseToJs
 
         if let Some(err) =  resp.error() {
             let status = err.status();
-            let body = json!({
-                "error": {
-                    "status_code" : status.to_string(),
-                    "message" : format!("{}", err)
+            let body = match err.downcast_ref::<ApiError>() {
+                Some(error) => ErrorBody::from(error.clone()),
+                None if status.is_server_error() => {
+                    tide::log::error!("Request failed: {:?}", err);
+                    error_body(status, ApiError::Internal, "Something went wrong")
                 }
-            });
+                None => error_body(status, ApiError::InvalidRequest, err.to_string()),
+            };
             let mut resp = Response::new(status);
-            resp.set_body(body);
+            resp.set_body(json!({ "error": body }));
 
             Ok(resp)
 
@@ -32,13 +37,8 @@ impl<State: Clone + Send + Sync + 'static> Middleware<State> for ErrResponseToJs
             } else {
                 let body = resp.take_body();
                 if body.is_empty().expect("no length on response body") {
-                    let new_body = json!({
-                        "error": {
-                            "status_code": status.to_string(),
-                            "message": "Something went wrong",
-                        }
-                    });
-                    resp.set_body(new_body);
+                    let error = if status.is_server_error() { ApiError::Internal } else { ApiError::InvalidRequest };
+                    resp.set_body(json!({ "error": error_body(status, error, "Something went wrong") }));
                 } else {
                     resp.set_body(body);
                 }
@@ -48,6 +48,103 @@ impl<State: Clone + Send + Sync + 'static> Middleware<State> for ErrResponseToJs
     }
 }
 
+/// For errors that don't come from an `ApiError` of their own, so keep their status.
+fn error_body(status: StatusCode, error: ApiError, message: impl Into<String>) -> ErrorBody {
+    ErrorBody {
+        status_code: status.to_string(),
+        error,
+        message: message.into(),
+    }
+}
+
+/// Lets browsers on the allowed origins call the API, with the `Authorization` header.
+///
+/// Origins that are listed by name are echoed back and may send credentials, since browsers
+/// refuse `*` on requests that carry them. Allowing `*` lets any other origin in too, but is
+/// answered with a literal `*` and no credentials, so that no site can act with a user's
+/// cookies. Requests from any other origin are turned away before they reach a handler.
+#[derive(Debug)]
+pub struct Cors {
+    origins: Vec<String>,
+}
+
+const CORS_ALLOW_METHODS: &str = "GET, POST, PUT, PATCH, DELETE";
+const CORS_ALLOW_HEADERS: &str = "Authorization, Content-Type";
+const CORS_MAX_AGE_SECONDS: &str = "86400";
+/// Response headers that pages may read, beyond the few that are always allowed.
+const CORS_EXPOSE_HEADERS: &str = "Retry-After, X-RateLimit-Limit, X-RateLimit-Remaining, X-RateLimit-Reset";
+
+impl Cors {
+    /// `origins` may include `*` to allow any origin.
+    pub fn new(origins: &[String]) -> Self {
+        Cors { origins: origins.to_vec() }
+    }
+
+    fn allows(&self, origin: &str) -> Option<AllowedOrigin> {
+        if self.origins.iter().any(|allowed| allowed == origin) {
+            Some(AllowedOrigin::Listed)
+        } else if self.origins.iter().any(|allowed| allowed == "*") {
+            Some(AllowedOrigin::Any)
+        } else {
+            None
+        }
+    }
+}
+
+enum AllowedOrigin {
+    Listed,
+    Any,
+}
+
+#[async_trait::async_trait]
+impl<State: Clone + Send + Sync + 'static> Middleware<State> for Cors {
+    async fn handle(&self, req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        let origin = match req.header(headers::ORIGIN) {
+            Some(origin) => origin.last().as_str().to_string(),
+            None => {
+                let mut resp = next.run(req).await;
+                resp.append_header(headers::VARY, "Origin");
+                return Ok(resp);
+            }
+        };
+
+        let allowed = match self.allows(&origin) {
+            Some(allowed) => allowed,
+            None => {
+                let error = ApiError::OriginNotAllowed;
+                let mut resp = Response::new(error.status());
+                resp.set_body(json!(ErrorResponse::from(error)));
+                resp.append_header(headers::VARY, "Origin");
+                return Ok(resp);
+            }
+        };
+
+        // Preflights are answered here for every route, so none of them need an OPTIONS handler.
+        let is_preflight = req.method() == Method::Options && req.header("Access-Control-Request-Method").is_some();
+        let mut resp = if is_preflight {
+            let mut resp = Response::new(StatusCode::NoContent);
+            resp.insert_header(headers::ACCESS_CONTROL_ALLOW_METHODS, CORS_ALLOW_METHODS);
+            resp.insert_header(headers::ACCESS_CONTROL_ALLOW_HEADERS, CORS_ALLOW_HEADERS);
+            resp.insert_header(headers::ACCESS_CONTROL_MAX_AGE, CORS_MAX_AGE_SECONDS);
+            resp
+        } else {
+            let mut resp = next.run(req).await;
+            resp.insert_header(headers::ACCESS_CONTROL_EXPOSE_HEADERS, CORS_EXPOSE_HEADERS);
+            resp
+        };
+
+        match allowed {
+            AllowedOrigin::Listed => {
+                resp.insert_header(headers::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+                resp.insert_header(headers::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true");
+            }
+            AllowedOrigin::Any => resp.insert_header(headers::ACCESS_CONTROL_ALLOW_ORIGIN, "*"),
+        }
+        resp.append_header(headers::VARY, "Origin");
+        Ok(resp)
+    }
+}
+
 // use crate::State;
 // use tide::{Request, Response, Next, Middleware};
 // use serde_json::json;
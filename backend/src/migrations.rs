@@ -0,0 +1,225 @@
+//! The database schema, as an ordered list of migrations embedded in the binary. They're
+//! applied on startup, or with `backend migrate`, and recorded in `schema_migrations` along
+//! with a checksum so that editing one after it has been applied is caught rather than
+//! silently ignored. To change the schema, add a new pair of files to `migrations/` and list
+//! them in `MIGRATIONS`.
+
+use crate::config::Config;
+use sha2::{Digest, Sha256};
+use sqlx::{Executor, PgPool, Postgres, Row, Transaction};
+
+pub(crate) struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    up: &'static str,
+    down: &'static str,
+}
+
+impl Migration {
+    fn checksum(&self) -> String {
+        hex::encode(Sha256::digest(self.up.as_bytes()))
+    }
+}
+
+macro_rules! migration {
+    ($version:expr, $name:literal) => {
+        Migration {
+            version: $version,
+            name: $name,
+            up: include_str!(concat!("../migrations/", $name, ".up.sql")),
+            down: include_str!(concat!("../migrations/", $name, ".down.sql")),
+        }
+    };
+}
+
+pub(crate) const MIGRATIONS: &[Migration] = &[
+    migration!(1, "0001_initial"),
+    migration!(2, "0002_auth_token_sessions"),
+    migration!(3, "0003_hash_auth_tokens"),
+    migration!(4, "0004_replies"),
+    migration!(5, "0005_likes"),
+    migration!(6, "0006_reposts_and_quotes"),
+    migration!(7, "0007_event_revisions"),
+    migration!(8, "0008_unique_follows"),
+    migration!(9, "0009_event_tags"),
+    migration!(10, "0010_event_mentions"),
+    migration!(11, "0011_notifications"),
+    migration!(12, "0012_conversations"),
+    migration!(13, "0013_blocks_and_mutes"),
+    migration!(14, "0014_private_accounts"),
+    migration!(15, "0015_profiles"),
+    migration!(16, "0016_media"),
+    migration!(17, "0017_search"),
+    migration!(18, "0018_password_resets"),
+    migration!(19, "0019_case_insensitive_usernames"),
+    migration!(20, "0020_visible_to"),
+    migration!(21, "0021_hide_blocked_users"),
+    migration!(22, "0022_on_timeline"),
+];
+
+/// Held while migrating, so that several servers starting at once don't race each other.
+const LOCK_KEY: i64 = 0x6d69_6772_6174_6500;
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum MigrationError {
+    #[error("migration {version} ({name}) has been changed since it was applied")]
+    ChecksumMismatch { version: i64, name: &'static str },
+    #[error("the database has migration {0} applied, which this build doesn't know about")]
+    Unknown(i64),
+    #[error("the database already has tables, but not the schema {0} creates, so it has to be brought up to date by hand")]
+    UnrecognisedSchema(&'static str),
+    #[error(transparent)]
+    Database(#[from] sqlx::Error),
+}
+
+async fn ensure_tracking_table(db_pool: &PgPool) -> Result<(), MigrationError> {
+    db_pool.execute(
+        r#"
+            create table if not exists schema_migrations (
+                version bigint primary key,
+                name varchar not null,
+                checksum varchar not null,
+                applied_at timestamp with time zone not null default now()
+            )
+        "#,
+    ).await?;
+
+    Ok(())
+}
+
+async fn applied(db_pool: &PgPool) -> Result<Vec<(i64, String)>, MigrationError> {
+    let rows = sqlx::query("select version, checksum from schema_migrations order by version")
+        .fetch_all(db_pool)
+        .await?;
+
+    Ok(rows.iter().map(|row| (row.get("version"), row.get("checksum"))).collect())
+}
+
+async fn record<'c>(executor: impl Executor<'c, Database = sqlx::Postgres>, migration: &Migration) -> Result<(), MigrationError> {
+    sqlx::query("insert into schema_migrations (version, name, checksum) values ($1, $2, $3)")
+        .bind(migration.version)
+        .bind(migration.name)
+        .bind(migration.checksum())
+        .execute(executor)
+        .await?;
+
+    Ok(())
+}
+
+/// Makes the parts of the configuration that backfills depend on readable from the migration
+/// as `current_setting('app.<name>')`, for the rest of the transaction.
+async fn configure(tx: &mut Transaction<'_, Postgres>, config: &Config) -> Result<(), MigrationError> {
+    sqlx::query("select set_config('app.auth_token_ttl_days', $1, true), set_config('app.secret_key', $2, true)")
+        .bind(config.auth_token_ttl_days.to_string())
+        .bind(config.secret_key.expose())
+        .execute(&mut *tx)
+        .await?;
+
+    Ok(())
+}
+
+/// Databases created by hand from the original `setup.sql` already have the initial schema, so
+/// it's recorded as applied instead of being run again, and the rest are applied on top. Ones
+/// made from a later `setup.sql` match none of the migrations, so they're refused rather than
+/// guessed at.
+async fn adopt_existing_schema(db_pool: &PgPool) -> Result<(), MigrationError> {
+    let row = sqlx::query(
+        r#"
+            select
+                to_regclass('users') is not null as has_users,
+                exists(select 1 from information_schema.columns where table_name = 'auth_tokens' and column_name = 'token') as has_plaintext_tokens,
+                exists(select 1 from information_schema.columns where table_name = 'auth_tokens' and column_name = 'device') as has_sessions
+        "#,
+    )
+        .fetch_one(db_pool)
+        .await?;
+
+    if !row.get::<bool, _>("has_users") {
+        return Ok(());
+    }
+    if !row.get::<bool, _>("has_plaintext_tokens") || row.get::<bool, _>("has_sessions") {
+        return Err(MigrationError::UnrecognisedSchema(MIGRATIONS[0].name));
+    }
+
+    tide::log::info!("Recording the existing schema as migration {}", MIGRATIONS[0].name);
+    record(db_pool, &MIGRATIONS[0]).await?;
+
+    Ok(())
+}
+
+/// Runs `migrate` while holding an advisory lock on a connection of its own.
+async fn with_lock<T>(db_pool: &PgPool, migrate: impl std::future::Future<Output = Result<T, MigrationError>>) -> Result<T, MigrationError> {
+    let mut lock = db_pool.acquire().await?;
+    sqlx::query("select pg_advisory_lock($1)").bind(LOCK_KEY).execute(&mut lock).await?;
+    let result = migrate.await;
+    sqlx::query("select pg_advisory_unlock($1)").bind(LOCK_KEY).execute(&mut lock).await?;
+    result
+}
+
+/// Applies every migration the database doesn't have yet, each in its own transaction, and
+/// returns the names of the ones it ran.
+pub(crate) async fn run(db_pool: &PgPool, config: &Config) -> Result<Vec<&'static str>, MigrationError> {
+    with_lock(db_pool, async {
+        ensure_tracking_table(db_pool).await?;
+
+        let mut applied = applied(db_pool).await?;
+        if applied.is_empty() {
+            adopt_existing_schema(db_pool).await?;
+            applied = self::applied(db_pool).await?;
+        }
+
+        for (version, checksum) in &applied {
+            let migration = find(*version)?;
+            if migration.checksum() != *checksum {
+                return Err(MigrationError::ChecksumMismatch { version: migration.version, name: migration.name });
+            }
+        }
+
+        let mut ran = Vec::new();
+        for migration in MIGRATIONS.iter().filter(|migration| !applied.iter().any(|(version, _)| *version == migration.version)) {
+            tide::log::info!("Applying migration {}", migration.name);
+
+            let mut tx = db_pool.begin().await?;
+            configure(&mut tx, config).await?;
+            (&mut tx).execute(migration.up).await?;
+            record(&mut tx, migration).await?;
+            tx.commit().await?;
+
+            ran.push(migration.name);
+        }
+
+        Ok(ran)
+    }).await
+}
+
+/// Undoes the last `steps` applied migrations, newest first, and returns their names.
+pub(crate) async fn rollback(db_pool: &PgPool, steps: usize) -> Result<Vec<&'static str>, MigrationError> {
+    with_lock(db_pool, async {
+        ensure_tracking_table(db_pool).await?;
+
+        let mut rolled_back = Vec::new();
+        for (version, _) in applied(db_pool).await?.into_iter().rev().take(steps) {
+            let migration = find(version)?;
+            tide::log::info!("Rolling back migration {}", migration.name);
+
+            let mut tx = db_pool.begin().await?;
+            (&mut tx).execute(migration.down).await?;
+            sqlx::query("delete from schema_migrations where version = $1")
+                .bind(migration.version)
+                .execute(&mut tx)
+                .await?;
+            tx.commit().await?;
+
+            rolled_back.push(migration.name);
+        }
+
+        Ok(rolled_back)
+    }).await
+}
+
+fn find(version: i64) -> Result<&'static Migration, MigrationError> {
+    MIGRATIONS
+        .iter()
+        .find(|migration| migration.version == version)
+        .ok_or(MigrationError::Unknown(version))
+}
@@ -0,0 +1,163 @@
+//! An OpenAPI 3 description of the API, served at `/openapi.json`. It's filled in as routes are
+//! registered, from the same `ApiEndpoint` declarations the handlers use, so it can't drift from
+//! what the server actually answers. Schemas come from the `JsonSchema` derives on the shared
+//! payload and response types, with their doc comments as descriptions.
+
+use crate::GetRequestPayload;
+use schemars::JsonSchema;
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use schemars::schema::{InstanceType, Schema, SchemaObject};
+use schemars::visit::{visit_schema_object, Visitor};
+use serde_json::{json, Map, Value};
+use shared::errors::ErrorResponse;
+use shared::responses::ApiResponse;
+use shared::{ApiEndpoint, QueryParam, Url};
+use std::collections::BTreeMap;
+use tide::http::Method;
+
+pub(crate) struct ApiDoc {
+    schemas: SchemaGenerator,
+    paths: BTreeMap<String, Map<String, Value>>,
+}
+
+impl ApiDoc {
+    pub fn new() -> Self {
+        ApiDoc {
+            schemas: SchemaSettings::openapi3().with_visitor(NullAsNullable).into_generator(),
+            paths: BTreeMap::new(),
+        }
+    }
+
+    /// Documents an endpoint registered with `add_endpoint`.
+    pub fn endpoint<E>(&mut self)
+    where
+        E: ApiEndpoint,
+        E::Payload: GetRequestPayload,
+    {
+        let request_body = <E::Payload as GetRequestPayload>::request_body(&mut self.schemas);
+        let content = json_content(self.schemas.subschema_for::<ApiResponse<E::Response>>());
+        let name = std::any::type_name::<E>().rsplit("::").next().unwrap_or_default();
+
+        self.add(E::METHOD, <E::Url as Url>::URL_SPEC, <E::Url as Url>::QUERY_PARAMS, name, request_body, content);
+    }
+
+    /// Documents a route with a handler of its own that answers with `{"data": R}` like the
+    /// endpoints do.
+    pub fn route<R: JsonSchema>(&mut self, method: Method, url_spec: &str, query_params: &[QueryParam], name: &str) {
+        let content = json_content(self.schemas.subschema_for::<ApiResponse<R>>());
+        self.add(method, url_spec, query_params, name, None, content);
+    }
+
+    /// Documents a route that answers with something other than JSON, such as a file.
+    pub fn raw_route(&mut self, method: Method, url_spec: &str, query_params: &[QueryParam], name: &str, content_type: &str) {
+        let content = json!({ content_type: { "schema": { "type": "string", "format": "binary" } } });
+        self.add(method, url_spec, query_params, name, None, content);
+    }
+
+    fn add(&mut self, method: Method, url_spec: &str, query_params: &[QueryParam], name: &str, request_body: Option<Value>, content: Value) {
+        let (path, mut parameters) = path_and_parameters(url_spec);
+        parameters.extend(query_params.iter().map(|param| {
+            json!({ "name": param.name, "in": "query", "required": param.required, "schema": { "type": "string" } })
+        }));
+
+        let mut operation = json!({
+            "operationId": name,
+            "parameters": parameters,
+            "responses": {
+                // Handlers pick the exact status, e.g. 201 when something was created.
+                "2XX": { "description": "Success", "content": content },
+                "default": { "description": "Failure", "content": json_content(self.schemas.subschema_for::<ErrorResponse>()) },
+            },
+        });
+        if let Some(request_body) = request_body {
+            operation["requestBody"] = request_body;
+        }
+
+        self.paths
+            .entry(path)
+            .or_default()
+            .insert(method.to_string().to_lowercase(), operation);
+    }
+
+    pub fn into_json(mut self) -> Value {
+        // The generator only applies its visitors to root schemas, so shared ones are done here.
+        let mut schemas = self.schemas.take_definitions();
+        for schema in schemas.values_mut() {
+            for visitor in self.schemas.visitors_mut() {
+                visitor.visit_schema(schema);
+            }
+        }
+
+        json!({
+            "openapi": "3.0.3",
+            "info": {
+                "title": "API",
+                "version": env!("CARGO_PKG_VERSION"),
+            },
+            "paths": self.paths,
+            "components": {
+                "schemas": schemas,
+                "securitySchemes": {
+                    "bearerAuth": { "type": "http", "scheme": "bearer" },
+                },
+            },
+            // Most endpoints need a token. Public ones personalise their answer when given one.
+            "security": [{ "bearerAuth": [] }, {}],
+        })
+    }
+}
+
+/// OpenAPI 3.0 has no `null` type, only `nullable`, which `()` responses need.
+#[derive(Debug, Clone)]
+struct NullAsNullable;
+
+impl Visitor for NullAsNullable {
+    fn visit_schema_object(&mut self, schema: &mut SchemaObject) {
+        if schema.instance_type == Some(InstanceType::Null.into()) {
+            schema.instance_type = None;
+            schema.enum_values = Some(vec![Value::Null]);
+            schema.extensions.insert("nullable".to_string(), Value::Bool(true));
+        }
+        visit_schema_object(self, schema);
+    }
+}
+
+/// A required JSON request body.
+pub(crate) fn json_body(schema: Schema) -> Value {
+    json!({ "required": true, "content": json_content(schema) })
+}
+
+fn json_content(schema: Schema) -> Value {
+    json!({ "application/json": { "schema": schema } })
+}
+
+/// Turns tide's `/users/:username` into OpenAPI's `/users/{username}`, with a parameter for
+/// each segment that is filled in.
+fn path_and_parameters(url_spec: &str) -> (String, Vec<Value>) {
+    let mut parameters = Vec::new();
+    let segments: Vec<String> = url_spec
+        .split('/')
+        .map(|segment| match segment.strip_prefix(':') {
+            Some(name) => {
+                parameters.push(json!({ "name": name, "in": "path", "required": true, "schema": { "type": "string" } }));
+                format!("{{{}}}", name)
+            }
+            None => segment.to_string(),
+        })
+        .collect();
+
+    (segments.join("/"), parameters)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn path_segments_become_parameters() {
+        let (path, parameters) = path_and_parameters("/conversations/:id/messages");
+
+        assert_eq!(path, "/conversations/{id}/messages");
+        assert_eq!(parameters, vec![json!({ "name": "id", "in": "path", "required": true, "schema": { "type": "string" } })]);
+    }
+}
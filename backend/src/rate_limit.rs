@@ -0,0 +1,423 @@
+//! Token bucket rate limits on the routes worth abusing, such as logging in and posting.
+//!
+//! Each rule names a route and how often it may be called per client IP and per signed in
+//! user. A bucket holds up to `capacity` tokens and refills evenly over `period`; a request
+//! takes one token, and is turned away with a 429 when there are none left. The buckets live
+//! in a `RateLimitStore`, in memory by default, so that several servers could share one later.
+
+use crate::State;
+use crate::endpoints::{authenticate_session, AuthenticatedSession};
+use async_trait::async_trait;
+use serde_json::json;
+use shared::errors::{ApiError, ErrorResponse};
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tide::http::Method;
+use tide::{Middleware, Next, Request, Response};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Limit {
+    pub capacity: u32,
+    pub period: Duration,
+}
+
+impl Limit {
+    pub const fn new(capacity: u32, period: Duration) -> Self {
+        Limit { capacity, period }
+    }
+
+    const fn per_minute(capacity: u32) -> Self {
+        Limit::new(capacity, Duration::from_secs(60))
+    }
+
+    const fn per_hour(capacity: u32) -> Self {
+        Limit::new(capacity, Duration::from_secs(60 * 60))
+    }
+}
+
+/// Parses limits written like `5/1m`: five requests every minute. Periods are in seconds,
+/// minutes or hours, and the count before the unit can be left out.
+impl FromStr for Limit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("{:?} should look like 5/1m, a number of requests per period", s);
+
+        let (capacity, period) = s.split_once('/').ok_or_else(invalid)?;
+        let capacity: u32 = capacity.trim().parse().map_err(|_| invalid())?;
+
+        let period = period.trim();
+        let (count, unit) = period.split_at(period.len().saturating_sub(1));
+        let count: u64 = if count.is_empty() { 1 } else { count.parse().map_err(|_| invalid())? };
+        let unit_seconds = match unit {
+            "s" => 1,
+            "m" => 60,
+            "h" => 60 * 60,
+            _ => return Err(invalid()),
+        };
+
+        if capacity == 0 || count == 0 {
+            return Err(format!("{:?} must allow at least one request per period", s));
+        }
+
+        Ok(Limit::new(capacity, Duration::from_secs(count * unit_seconds)))
+    }
+}
+
+/// The outcome of taking a token from a bucket.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Decision {
+    /// Whether the bucket had a token. It's only taken if every bucket asked for at once had one.
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// How long until a token is available again, when `allowed` is false.
+    pub retry_after: Duration,
+    /// How long until the bucket is full again.
+    pub reset_after: Duration,
+}
+
+/// Keeps the buckets, keyed by rule and client.
+#[async_trait]
+pub(crate) trait RateLimitStore: Debug + Send + Sync + 'static {
+    /// Takes a token from each of the buckets, or from none of them if any is empty, so that a
+    /// request turned away by one limit doesn't use up the others. Returns a decision per bucket,
+    /// in the same order.
+    async fn take(&self, buckets: &[(String, Limit)], now: Instant) -> Vec<Decision>;
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, limit: Limit, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        let rate = limit.capacity as f64 / limit.period.as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(limit.capacity as f64);
+        self.updated_at = now;
+    }
+
+    fn time_until(&self, tokens: f64, limit: Limit) -> Duration {
+        let missing = (tokens - self.tokens).max(0.0);
+        Duration::from_secs_f64(missing * limit.period.as_secs_f64() / limit.capacity as f64)
+    }
+}
+
+/// Forgets buckets once this many are kept, as long as they've filled up again.
+const MAX_IDLE_BUCKETS: usize = 10_000;
+
+/// Keeps buckets in this process, so each server counts on its own.
+#[derive(Debug, Default)]
+pub(crate) struct MemoryRateLimitStore {
+    buckets: Mutex<HashMap<String, (Bucket, Limit)>>,
+}
+
+#[async_trait]
+impl RateLimitStore for MemoryRateLimitStore {
+    async fn take(&self, keys: &[(String, Limit)], now: Instant) -> Vec<Decision> {
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.len() >= MAX_IDLE_BUCKETS {
+            buckets.retain(|_, (bucket, limit)| {
+                let mut bucket = *bucket;
+                bucket.refill(*limit, now);
+                bucket.tokens < limit.capacity as f64
+            });
+        }
+
+        for (key, limit) in keys {
+            let (bucket, _) = buckets
+                .entry(key.clone())
+                .or_insert((Bucket { tokens: limit.capacity as f64, updated_at: now }, *limit));
+            bucket.refill(*limit, now);
+        }
+
+        let all_allowed = keys.iter().all(|(key, _)| buckets[key].0.tokens >= 1.0);
+
+        keys.iter()
+            .map(|(key, limit)| {
+                let (bucket, _) = buckets.get_mut(key).unwrap();
+                let allowed = bucket.tokens >= 1.0;
+                if all_allowed {
+                    bucket.tokens -= 1.0;
+                }
+
+                Decision {
+                    allowed,
+                    limit: limit.capacity,
+                    remaining: bucket.tokens.floor() as u32,
+                    retry_after: if allowed { Duration::from_secs(0) } else { bucket.time_until(1.0, *limit) },
+                    reset_after: bucket.time_until(limit.capacity as f64, *limit),
+                }
+            })
+            .collect()
+    }
+}
+
+/// A rate limited route, named so its limits can be changed with the `rate_limits` setting,
+/// e.g. `login.ip=20/1m`.
+#[derive(Debug, Clone)]
+pub(crate) struct Rule {
+    pub name: &'static str,
+    pub method: Method,
+    /// A tide route such as `/users/:username/session`.
+    pub path: &'static str,
+    pub per_ip: Option<Limit>,
+    pub per_user: Option<Limit>,
+}
+
+impl Rule {
+    fn matches(&self, method: Method, path: &str) -> bool {
+        let mut spec = self.path.trim_matches('/').split('/');
+        let mut path = path.trim_matches('/').split('/');
+
+        method == self.method && loop {
+            match (spec.next(), path.next()) {
+                (None, None) => break true,
+                (Some(spec), Some(segment)) if spec.starts_with(':') && !segment.is_empty() => continue,
+                (Some(spec), Some(segment)) if spec == segment => continue,
+                _ => break false,
+            }
+        }
+    }
+}
+
+/// The rules the server starts with.
+pub(crate) fn default_rules() -> Vec<Rule> {
+    vec![
+        Rule {
+            name: "login",
+            method: Method::Post,
+            path: "/users/:username/session",
+            per_ip: Some(Limit::per_minute(10)),
+            per_user: None,
+        },
+        Rule {
+            name: "signup",
+            method: Method::Post,
+            path: "/users",
+            per_ip: Some(Limit::per_hour(5)),
+            per_user: None,
+        },
+        Rule {
+            name: "password_reset",
+            method: Method::Post,
+            path: "/password_resets",
+            per_ip: Some(Limit::per_hour(5)),
+            per_user: None,
+        },
+        Rule {
+            name: "post_event",
+            method: Method::Post,
+            path: "/events",
+            per_ip: Some(Limit::new(60, Duration::from_secs(10 * 60))),
+            per_user: Some(Limit::new(30, Duration::from_secs(10 * 60))),
+        },
+    ]
+}
+
+/// The names accepted by `rate_limits` overrides, such as `login.ip`.
+pub(crate) fn override_names(rules: &[Rule]) -> Vec<String> {
+    rules
+        .iter()
+        .flat_map(|rule| vec![format!("{}.ip", rule.name), format!("{}.user", rule.name)])
+        .collect()
+}
+
+pub(crate) struct RateLimit {
+    store: Arc<dyn RateLimitStore>,
+    rules: Vec<Rule>,
+    trust_forwarded_for: bool,
+}
+
+impl Debug for RateLimit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RateLimit").field("rules", &self.rules).finish()
+    }
+}
+
+impl RateLimit {
+    /// Applies `overrides`, keyed like `login.ip`, on top of `rules`.
+    pub fn new(store: Arc<dyn RateLimitStore>, mut rules: Vec<Rule>, overrides: &HashMap<String, Limit>, trust_forwarded_for: bool) -> Self {
+        for rule in &mut rules {
+            if let Some(limit) = overrides.get(&format!("{}.ip", rule.name)) {
+                rule.per_ip = Some(*limit);
+            }
+            if let Some(limit) = overrides.get(&format!("{}.user", rule.name)) {
+                rule.per_user = Some(*limit);
+            }
+        }
+
+        RateLimit { store, rules, trust_forwarded_for }
+    }
+
+    /// The client's IP, without a port. Forwarding headers are only believed behind a proxy
+    /// that sets them, since otherwise any client could pick a fresh IP for each request.
+    fn client_ip(&self, req: &Request<State>) -> String {
+        let forwarded = if self.trust_forwarded_for { forwarded_for(req) } else { None };
+        let addr = forwarded.or_else(|| req.peer_addr()).unwrap_or("unknown");
+
+        addr.parse::<std::net::SocketAddr>()
+            .map(|addr| addr.ip().to_string())
+            .unwrap_or_else(|_| addr.to_string())
+    }
+}
+
+/// The address our proxy saw the request come from, from `Forwarded` or else `X-Forwarded-For`.
+/// That's the last hop listed: proxies append to these headers, and the client can put anything
+/// it likes in front.
+fn forwarded_for(req: &Request<State>) -> Option<&str> {
+    let last_hop = |name: &str| {
+        req.header(name)?
+            .iter()
+            .flat_map(|value| value.as_str().split(','))
+            .map(str::trim)
+            .filter(|hop| !hop.is_empty())
+            .last()
+    };
+
+    match last_hop("Forwarded") {
+        Some(hop) => hop.split(';').find_map(|pair| {
+            let (key, value) = pair.split_once('=')?;
+            if key.trim().eq_ignore_ascii_case("for") {
+                Some(value.trim().trim_matches('"'))
+            } else {
+                None
+            }
+        }),
+        None => last_hop("X-Forwarded-For"),
+    }
+}
+
+fn insert_rate_limit_headers(resp: &mut Response, decision: &Decision) {
+    resp.insert_header("X-RateLimit-Limit", decision.limit.to_string());
+    resp.insert_header("X-RateLimit-Remaining", decision.remaining.to_string());
+    resp.insert_header("X-RateLimit-Reset", seconds(decision.reset_after).to_string());
+}
+
+/// Whole seconds, rounded up so that clients never retry too early.
+fn seconds(duration: Duration) -> u64 {
+    duration.as_secs_f64().ceil() as u64
+}
+
+#[async_trait]
+impl Middleware<State> for RateLimit {
+    async fn handle(&self, mut req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        let rule = match self.rules.iter().find(|rule| rule.matches(req.method(), req.url().path())) {
+            Some(rule) => rule,
+            None => return Ok(next.run(req).await),
+        };
+
+        let mut keys = Vec::new();
+        if let Some(limit) = rule.per_ip {
+            keys.push((format!("{}:ip:{}", rule.name, self.client_ip(&req)), limit));
+        }
+        if let Some(limit) = rule.per_user {
+            // Requests that aren't signed in are left for the handler to turn away. Those that are
+            // keep their session, so the handler doesn't look it up a second time.
+            if req.header("Authorization").is_some() {
+                if let Ok((user, token_id)) = authenticate_session(&req).await {
+                    keys.push((format!("{}:user:{}", rule.name, user.id), limit));
+                    req.set_ext(AuthenticatedSession(user, token_id));
+                }
+            }
+        }
+
+        let decisions = self.store.take(&keys, Instant::now()).await;
+
+        if let Some(decision) = decisions.iter().find(|decision| !decision.allowed) {
+            let error = ApiError::RateLimited { retry_after: seconds(decision.retry_after) };
+            let mut resp = Response::new(error.status());
+            resp.insert_header("Retry-After", seconds(decision.retry_after).to_string());
+            insert_rate_limit_headers(&mut resp, decision);
+            resp.set_body(json!(ErrorResponse::from(error)));
+            return Ok(resp);
+        }
+
+        // The headers describe whichever limit is closest to running out.
+        let mut resp = next.run(req).await;
+        if let Some(decision) = decisions.iter().min_by_key(|decision| decision.remaining) {
+            insert_rate_limit_headers(&mut resp, decision);
+        }
+        Ok(resp)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parsing_limits() {
+        assert_eq!("5/1m".parse(), Ok(Limit::new(5, Duration::from_secs(60))));
+        assert_eq!("30/10m".parse(), Ok(Limit::new(30, Duration::from_secs(600))));
+        assert_eq!("2/h".parse(), Ok(Limit::new(2, Duration::from_secs(3600))));
+        for limit in &["", "5", "5/", "5/1d", "x/1m", "0/1m", "5/0s"] {
+            assert!(limit.parse::<Limit>().is_err(), "{:?} was accepted", limit);
+        }
+    }
+
+    #[test]
+    fn matching_routes() {
+        let rule = &default_rules()[0];
+        assert!(rule.matches(Method::Post, "/users/tim/session"));
+        assert!(rule.matches(Method::Post, "/users/tim/session/"));
+        assert!(!rule.matches(Method::Delete, "/users/tim/session"));
+        assert!(!rule.matches(Method::Post, "/users//session"));
+        assert!(!rule.matches(Method::Post, "/users/tim"));
+        assert!(!rule.matches(Method::Post, "/users/tim/session/extra"));
+    }
+
+    async fn take(store: &MemoryRateLimitStore, key: &str, limit: Limit, now: Instant) -> Decision {
+        store.take(&[(key.to_string(), limit)], now).await[0]
+    }
+
+    #[async_std::test]
+    async fn buckets_empty_and_refill() {
+        let store = MemoryRateLimitStore::default();
+        let limit = Limit::new(2, Duration::from_secs(60));
+        let start = Instant::now();
+
+        let first = take(&store, "a", limit, start).await;
+        assert!(first.allowed);
+        assert_eq!(first.remaining, 1);
+        assert!(take(&store, "a", limit, start).await.allowed);
+
+        let denied = take(&store, "a", limit, start).await;
+        assert!(!denied.allowed);
+        assert_eq!(denied.remaining, 0);
+        assert_eq!(seconds(denied.retry_after), 30);
+        assert_eq!(seconds(denied.reset_after), 60);
+
+        // Other keys have buckets of their own.
+        assert!(take(&store, "b", limit, start).await.allowed);
+
+        // One token comes back every 30 seconds.
+        assert!(!take(&store, "a", limit, start + Duration::from_secs(29)).await.allowed);
+        assert!(take(&store, "a", limit, start + Duration::from_secs(31)).await.allowed);
+        assert_eq!(take(&store, "a", limit, start + Duration::from_secs(600)).await.remaining, 1);
+    }
+
+    #[async_std::test]
+    async fn an_empty_bucket_leaves_the_others_alone() {
+        let store = MemoryRateLimitStore::default();
+        let limit = Limit::new(1, Duration::from_secs(60));
+        let now = Instant::now();
+
+        assert!(take(&store, "ip", limit, now).await.allowed);
+
+        let buckets = [("user".to_string(), limit), ("ip".to_string(), limit)];
+        let decisions = store.take(&buckets, now).await;
+        assert!(decisions[0].allowed);
+        assert!(!decisions[1].allowed);
+
+        // The request was turned away, so its user keeps the token.
+        assert!(take(&store, "user", limit, now).await.allowed);
+    }
+}
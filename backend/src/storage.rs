@@ -0,0 +1,106 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Where uploaded files are kept, addressed by flat keys such as `"<id>.png"`.
+///
+/// Keys are chosen by the server, never taken from a request as is.
+#[async_trait]
+pub(crate) trait Storage: Debug + Send + Sync + 'static {
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> io::Result<()>;
+
+    /// `None` if nothing is stored under `key`.
+    async fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>>;
+
+    /// Deleting a key that isn't there is not an error.
+    async fn delete(&self, key: &str) -> io::Result<()>;
+}
+
+/// Keeps each file under `root`, which is created on first write.
+#[derive(Debug)]
+pub(crate) struct FileStorage {
+    root: PathBuf,
+}
+
+impl FileStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        FileStorage { root: root.into() }
+    }
+}
+
+#[async_trait]
+impl Storage for FileStorage {
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> io::Result<()> {
+        async_std::fs::create_dir_all(&self.root).await?;
+        async_std::fs::write(self.root.join(key), bytes).await
+    }
+
+    async fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        match async_std::fs::read(self.root.join(key)).await {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> io::Result<()> {
+        match async_std::fs::remove_file(self.root.join(key)).await {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Keeps files in memory, for tests.
+#[derive(Debug, Default)]
+pub(crate) struct MemoryStorage {
+    blobs: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+#[async_trait]
+impl Storage for MemoryStorage {
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> io::Result<()> {
+        self.blobs.lock().unwrap().insert(key.to_string(), bytes);
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.blobs.lock().unwrap().get(key).cloned())
+    }
+
+    async fn delete(&self, key: &str) -> io::Result<()> {
+        self.blobs.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    async fn round_trip(storage: &dyn Storage) {
+        assert_eq!(storage.get("a.png").await.unwrap(), None);
+
+        storage.put("a.png", vec![1, 2, 3]).await.unwrap();
+        assert_eq!(storage.get("a.png").await.unwrap(), Some(vec![1, 2, 3]));
+
+        storage.delete("a.png").await.unwrap();
+        assert_eq!(storage.get("a.png").await.unwrap(), None);
+        storage.delete("a.png").await.unwrap();
+    }
+
+    #[async_std::test]
+    async fn memory_storage_round_trip() {
+        round_trip(&MemoryStorage::default()).await;
+    }
+
+    #[async_std::test]
+    async fn file_storage_round_trip() {
+        let root = std::env::temp_dir().join(format!("storage-test-{}", uuid::Uuid::new_v4()));
+        round_trip(&FileStorage::new(&root)).await;
+        async_std::fs::remove_dir_all(&root).await.unwrap();
+    }
+}
@@ -0,0 +1,313 @@
+use crate::tests::test_utils::*;
+use serde_json::json;
+use assert_json_diff::{assert_json_include, assert_json_eq};
+use crate::server;
+use shared::payloads::{CreateConversationPayload, CreateEventPayload, SendMessagePayload};
+
+#[async_std::test]
+async fn blocking_removes_follows_both_ways() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+
+    let (_, status, _) = post("/users/jim/follow", None::<()>)
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 201);
+    let (_, status, _) = post("/users/tim/follow", None::<()>)
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 201);
+
+    let (json, status, _) = post("/users/jim/block", None::<()>)
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 201);
+    assert_json_include!(actual: json, expected: json!({"data": {
+        "username": "jim",
+        "following": false,
+        "followed_by": false,
+        "blocking": true,
+        "muting": false,
+    }}));
+
+    // Blocking again is a no-op.
+    let (_, status, _) = post("/users/jim/block", None::<()>)
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 200);
+
+    let (json, status, _) = post("/users/tim/follow", None::<()>)
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 403);
+    assert_json_include!(actual: json, expected: json!({
+        "error": {"message": "You cannot follow this user"}
+    }));
+
+    let (json, status, _) = get("/users/tim/followers").send(&mut server).await;
+    assert_eq!(status, 200);
+    assert_json_eq!(json, json!({"data": {"items": [], "next_cursor": null}}));
+
+    let (json, status, _) = get("/me/blocks")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: json, expected: json!({"data": [{"username": "jim"}]}));
+
+    let (json, status, _) = delete("/users/jim/block")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: json, expected: json!({"data": {"blocking": false}}));
+
+    let (_, status, _) = post("/users/tim/follow", None::<()>)
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 201);
+}
+
+#[async_std::test]
+async fn blocked_users_cannot_see_or_reply() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+
+    let event_id = post_event("hello", &tim_token, &server).await;
+
+    let (_, status, _) = post("/users/jim/block", None::<()>)
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 201);
+
+    // To each other, a blocked pair's events don't exist.
+    let (_, status, _) = post("/events",
+        Some(CreateEventPayload { in_reply_to: Some(event_id.parse().unwrap()), ..new_event("hi") }))
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 404);
+
+    let (_, status, _) = get("/users/tim")
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 404);
+
+    let (_, status, _) = get("/users/tim/followers")
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 404);
+
+    // Everyone else can still see the profile.
+    let (_, status, _) = get("/users/tim").send(&mut server).await;
+    assert_eq!(status, 200);
+}
+
+#[async_std::test]
+async fn blocking_yourself() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+
+    let (json, status, _) = post("/users/tim/block", None::<()>)
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server).await;
+    assert_eq!(status, 409);
+    assert_json_include!(actual: json, expected: json!({
+        "error": {"message": "You cannot block yourself"}
+    }));
+
+    let (json, status, _) = post("/users/tim/mute", None::<()>)
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server).await;
+    assert_eq!(status, 409);
+    assert_json_include!(actual: json, expected: json!({
+        "error": {"message": "You cannot mute yourself"}
+    }));
+}
+
+#[async_std::test]
+async fn muted_users_are_hidden_from_the_timeline() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+
+    let (_, status, _) = post("/users/jim/follow", None::<()>)
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 201);
+
+    post_event("from jim", &jim_token, &server).await;
+    post_event("from tim", &tim_token, &server).await;
+
+    let (json, status, _) = post("/users/jim/mute", None::<()>)
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 201);
+    assert_json_include!(actual: json, expected: json!({"data": {
+        "following": true,
+        "muting": true,
+    }}));
+
+    let (json, status, _) = get("/me/timeline")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 200);
+    let items = json["data"]["items"].as_array().unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0]["content"], "from tim");
+
+    let (json, status, _) = get("/me/mutes")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: json, expected: json!({"data": [{"username": "jim"}]}));
+
+    let (_, status, _) = delete("/users/jim/mute")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 200);
+
+    let (json, _, _) = get("/me/timeline")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(json["data"]["items"].as_array().unwrap().len(), 2);
+}
+
+#[async_std::test]
+async fn blocked_users_events_are_hidden_everywhere() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+
+    let event_id = post_event("hello #rust", &tim_token, &server).await;
+    post_reply("hi @tim", &event_id, &jim_token, &server).await;
+
+    let (_, status, _) = post("/users/jim/block", None::<()>)
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 201);
+
+    for path in &["like", "repost"] {
+        let (_, status, _) = post(&format!("/events/{}/{}", event_id, path), None::<()>)
+            .header("Authorization", format!("Bearer {}", jim_token))
+            .send(&mut server).await;
+        assert_eq!(status, 404);
+    }
+
+    let (_, status, _) = post("/events",
+        Some(CreateEventPayload { quote_of: Some(event_id.parse().unwrap()), ..new_event("look") }))
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 404);
+
+    let (_, status, _) = get(&format!("/events/{}/thread", event_id))
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 404);
+
+    // The reply made before the block drops out of the thread, the tag and the notifications.
+    let (json, status, _) = get(&format!("/events/{}/thread", event_id))
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 200);
+    assert_eq!(json["data"]["replies"], json!([]));
+
+    let (json, status, _) = get("/tags/rust/events")
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 200);
+    assert_json_eq!(json, json!({"data": {"items": [], "next_cursor": null}}));
+
+    let (json, status, _) = get("/me/notifications")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 200);
+    assert_eq!(json["data"]["items"], json!([]));
+}
+
+#[async_std::test]
+async fn muted_users_are_hidden_from_tags_and_notifications() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+
+    post_event("hey @tim #rust", &jim_token, &server).await;
+
+    let (_, status, _) = post("/users/jim/mute", None::<()>)
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 201);
+
+    let (json, status, _) = get("/tags/rust/events")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 200);
+    assert_eq!(json["data"]["items"], json!([]));
+
+    let (json, status, _) = get("/me/notifications")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 200);
+    assert_eq!(json["data"]["items"], json!([]));
+
+    // Muting only changes what tim is shown.
+    let (json, _, _) = get("/tags/rust/events").send(&mut server).await;
+    assert_eq!(json["data"]["items"].as_array().unwrap().len(), 1);
+}
+
+#[async_std::test]
+async fn blocking_stops_conversations() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+
+    let (json, status, _) = post("/conversations", Some(CreateConversationPayload { usernames: vec!["jim".to_string()] }))
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 201);
+    let messages_url = format!("/conversations/{}/messages", json["data"]["id"].as_str().unwrap());
+
+    for (token, content) in &[(&tim_token, "from tim"), (&jim_token, "from jim")] {
+        let (_, status, _) = post(&messages_url, Some(SendMessagePayload { content: content.to_string() }))
+            .header("Authorization", format!("Bearer {}", token))
+            .send(&mut server).await;
+        assert_eq!(status, 201);
+    }
+
+    let (_, status, _) = post("/users/jim/block", None::<()>)
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 201);
+
+    let (json, status, _) = post(&messages_url, Some(SendMessagePayload { content: "still there?".to_string() }))
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 403);
+    assert_json_include!(actual: json, expected: json!({
+        "error": {"message": "You cannot send messages to this conversation"}
+    }));
+
+    let (json, status, _) = get(&messages_url)
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: json, expected: json!({"data": {"items": [{"content": "from tim"}], "next_cursor": null}}));
+
+    let (json, _, _) = get("/conversations")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_json_include!(actual: json, expected: json!({"data": {"items": [{"last_message": {"content": "from tim"}}]}}));
+}
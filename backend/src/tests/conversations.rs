@@ -0,0 +1,177 @@
+use crate::tests::test_utils::*;
+use crate::{State, Server};
+use serde_json::json;
+use assert_json_diff::assert_json_include;
+use crate::server;
+use shared::payloads::{CreateConversationPayload, SendMessagePayload};
+
+async fn start_conversation(usernames: &[&str], token: &str, server: &Server<State>) -> String {
+    let (json, status, _) = post("/conversations",
+        Some(CreateConversationPayload {
+            usernames: usernames.iter().map(|username| username.to_string()).collect(),
+        }))
+    .header("Authorization", format!("Bearer {}", token))
+    .send(server).await;
+    assert_eq!(status, 201);
+
+    json["data"]["id"].as_str().unwrap().to_string()
+}
+
+async fn send_message(conversation_id: &str, text: &str, token: &str, server: &Server<State>) {
+    let (_, status, _) = post(&format!("/conversations/{}/messages", conversation_id),
+        Some(SendMessagePayload { content: text.to_string() }))
+    .header("Authorization", format!("Bearer {}", token))
+    .send(server).await;
+    assert_eq!(status, 201);
+}
+
+#[async_std::test]
+async fn messaging() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+
+    let conversation_id = start_conversation(&["jim"], &tim_token, &server).await;
+    send_message(&conversation_id, "one", &tim_token, &server).await;
+    send_message(&conversation_id, "two", &jim_token, &server).await;
+    send_message(&conversation_id, "three", &tim_token, &server).await;
+
+    let (json, status, _) = get(&format!("/conversations/{}/messages?page_size=2", conversation_id))
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: &json, expected: json!({
+        "data": {"items": [
+            {"content": "three", "user": {"username": "tim"}},
+            {"content": "two", "user": {"username": "jim"}},
+        ]}
+    }));
+    let cursor = json["data"]["next_cursor"].as_str().unwrap().to_string();
+
+    let (json, status, _) = get(&format!("/conversations/{}/messages?page_size=2&before={}", conversation_id, cursor))
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: &json, expected: json!({
+        "data": {"items": [{"content": "one"}], "next_cursor": null}
+    }));
+    assert_eq!(json["data"]["items"].as_array().unwrap().len(), 1);
+
+    let (json, status, _) = get("/conversations")
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: json, expected: json!({
+        "data": {"items": [{
+            "id": conversation_id,
+            "members": [{"username": "jim"}, {"username": "tim"}],
+            "last_message": {"content": "three"},
+        }]}
+    }));
+}
+
+#[async_std::test]
+async fn group_conversations_are_reused() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+    create_user_and_authenticate(&mut server, Some("bob".to_string())).await;
+
+    let group_id = start_conversation(&["jim", "bob"], &tim_token, &server).await;
+    let pair_id = start_conversation(&["tim"], &jim_token, &server).await;
+    assert_ne!(group_id, pair_id);
+
+    let (json, status, _) = post("/conversations", Some(CreateConversationPayload {
+            usernames: vec!["tim".to_string(), "bob".to_string()],
+        }))
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 200);
+    assert_eq!(json["data"]["id"], group_id);
+}
+
+#[async_std::test]
+async fn only_members_can_read_or_send() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    create_user_and_authenticate(&mut server, Some("jim".to_string())).await;
+    let bob_token = create_user_and_authenticate(&mut server, Some("bob".to_string())).await.token;
+
+    let conversation_id = start_conversation(&["jim"], &tim_token, &server).await;
+
+    let (json, status, _) = get(&format!("/conversations/{}/messages", conversation_id))
+        .header("Authorization", format!("Bearer {}", bob_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 403);
+    assert_json_include!(actual: json, expected: json!({
+        "error": {"message": "You are not a member of this conversation"}
+    }));
+
+    let (_, status, _) = post(&format!("/conversations/{}/messages", conversation_id),
+        Some(SendMessagePayload { content: "hi".to_string() }))
+        .header("Authorization", format!("Bearer {}", bob_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 403);
+
+    let (json, _, _) = get("/conversations")
+        .header("Authorization", format!("Bearer {}", bob_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(json["data"]["items"].as_array().unwrap().len(), 0);
+}
+
+#[async_std::test]
+async fn blocked_users_cannot_start_conversations() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+
+    let (_, status, _) = post("/users/tim/block", None::<()>)
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 201);
+
+    let (json, status, _) = post("/conversations", Some(CreateConversationPayload {
+            usernames: vec!["jim".to_string()],
+        }))
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 403);
+    assert_json_include!(actual: json, expected: json!({
+        "error": {"message": "You cannot start a conversation with this user"}
+    }));
+}
+
+#[async_std::test]
+async fn conversation_with_nobody_else() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+
+    let (json, status, _) = post("/conversations", Some(CreateConversationPayload {
+            usernames: vec!["tim".to_string()],
+        }))
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 422);
+    assert_json_include!(actual: json, expected: json!({
+        "error": {"message": "A conversation needs at least one other member"}
+    }));
+}
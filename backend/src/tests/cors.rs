@@ -0,0 +1,115 @@
+use crate::tests::test_utils::*;
+use crate::{State, Server};
+use crate::server;
+use serde_json::json;
+use assert_json_diff::assert_json_include;
+
+const APP_ORIGIN: &str = "https://app.example.com";
+
+async fn strict_server(test_db: &TestDb) -> Server<State> {
+    let mut config = test_db.config();
+    config.cors_origins = vec![APP_ORIGIN.to_string()];
+    server(config, test_db.db()).await
+}
+
+#[async_std::test]
+async fn preflights_from_allowed_origins() {
+    let test_db = TestDb::new().await;
+    let server = strict_server(&test_db).await;
+
+    // Routes registered with `add_endpoint` need no OPTIONS handler of their own.
+    for (url, method) in &[("/me/password", "PUT"), ("/me", "PATCH"), ("/events", "POST"), ("/users/tim/follow", "DELETE")] {
+        let res = options(url)
+            .header("Origin", APP_ORIGIN)
+            .header("Access-Control-Request-Method", method)
+            .header("Access-Control-Request-Headers", "authorization, content-type")
+            .send_raw(&server).await;
+
+        assert_eq!(res.status(), 204, "preflight for {} {}", method, url);
+        assert_eq!(res["Access-Control-Allow-Origin"], APP_ORIGIN);
+        assert_eq!(res["Access-Control-Allow-Credentials"], "true");
+        assert!(res["Access-Control-Allow-Methods"].as_str().contains(method));
+        assert!(res["Access-Control-Allow-Headers"].as_str().contains("Authorization"));
+    }
+}
+
+#[async_std::test]
+async fn requests_from_allowed_origins() {
+    let test_db = TestDb::new().await;
+    let mut server = strict_server(&test_db).await;
+
+    create_user_and_authenticate(&mut server, Some("tim".to_string())).await;
+
+    let (_, status, headers) = get("/users/tim").header("Origin", APP_ORIGIN).send(&server).await;
+    assert_eq!(status, 200);
+    assert_eq!(headers["access-control-allow-origin"], APP_ORIGIN);
+    assert_eq!(headers["vary"], "Origin");
+
+    // Errors can be read by the page too.
+    let (_, status, headers) = get("/users/nobody").header("Origin", APP_ORIGIN).send(&server).await;
+    assert_eq!(status, 404);
+    assert_eq!(headers["access-control-allow-origin"], APP_ORIGIN);
+
+    // Requests that aren't cross-origin are untouched.
+    let (_, status, headers) = get("/users/tim").send(&server).await;
+    assert_eq!(status, 200);
+    assert!(headers.get("access-control-allow-origin").is_none());
+}
+
+#[async_std::test]
+async fn disallowed_origins_are_turned_away() {
+    let test_db = TestDb::new().await;
+    let mut server = strict_server(&test_db).await;
+
+    create_user_and_authenticate(&mut server, Some("tim".to_string())).await;
+
+    let res = options("/users/tim")
+        .header("Origin", "https://evil.example.com")
+        .header("Access-Control-Request-Method", "GET")
+        .send_raw(&server).await;
+    assert_eq!(res.status(), 403);
+    assert!(res.header("Access-Control-Allow-Origin").is_none());
+
+    let (json, status, headers) = get("/users/tim").header("Origin", "https://evil.example.com").send(&server).await;
+    assert_eq!(status, 403);
+    assert!(headers.get("access-control-allow-origin").is_none());
+    assert_eq!(headers["vary"], "Origin");
+    assert_json_include!(actual: json, expected: json!({"error": {"message": "Origin not allowed"}}));
+}
+
+#[async_std::test]
+async fn any_origin_outside_production() {
+    let test_db = TestDb::new().await;
+    let server = server(test_db.config(), test_db.db()).await;
+
+    // Any origin may call the API, but not with credentials.
+    let res = options("/events")
+        .header("Origin", "http://localhost:8000")
+        .header("Access-Control-Request-Method", "POST")
+        .send_raw(&server).await;
+    assert_eq!(res.status(), 204);
+    assert_eq!(res["Access-Control-Allow-Origin"], "*");
+    assert!(res.header("Access-Control-Allow-Credentials").is_none());
+}
+
+#[async_std::test]
+async fn listed_origins_keep_credentials_alongside_any_origin() {
+    let test_db = TestDb::new().await;
+    let mut config = test_db.config();
+    config.cors_origins = vec!["*".to_string(), APP_ORIGIN.to_string()];
+    let server = server(config, test_db.db()).await;
+
+    let res = options("/events")
+        .header("Origin", APP_ORIGIN)
+        .header("Access-Control-Request-Method", "POST")
+        .send_raw(&server).await;
+    assert_eq!(res["Access-Control-Allow-Origin"], APP_ORIGIN);
+    assert_eq!(res["Access-Control-Allow-Credentials"], "true");
+
+    let res = options("/events")
+        .header("Origin", "https://evil.example.com")
+        .header("Access-Control-Request-Method", "POST")
+        .send_raw(&server).await;
+    assert_eq!(res["Access-Control-Allow-Origin"], "*");
+    assert!(res.header("Access-Control-Allow-Credentials").is_none());
+}
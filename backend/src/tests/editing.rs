@@ -0,0 +1,126 @@
+use crate::tests::test_utils::*;
+use serde_json::json;
+use assert_json_diff::assert_json_include;
+use crate::server;
+use shared::payloads::EditEventPayload;
+use uuid::Uuid;
+
+#[async_std::test]
+async fn editing_own_event() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let token = create_user_and_authenticate(&mut server, None).await.token;
+    let event_id = post_event("first", &token, &server).await;
+
+    for content in &["second", "third"] {
+        let (json, status, _) = patch(&format!("/events/{}", event_id),
+            Some(EditEventPayload {
+                content: content.to_string(),
+            }))
+            .header("Authorization", format!("Bearer {}", token))
+            .send(&mut server)
+            .await;
+        assert_eq!(status, 200);
+        assert_json_include!(actual: &json, expected: json!({
+            "data": {
+                "id": event_id,
+                "content": content,
+            }
+        }));
+        assert!(!json["data"]["edited_at"].is_null());
+    }
+
+    let (json, status, _) = get(&format!("/events/{}/revisions", event_id)).send(&mut server).await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: &json, expected: json!({
+        "data": [
+            {"content": "second"},
+            {"content": "first"},
+        ]
+    }));
+    assert_eq!(json["data"].as_array().unwrap().len(), 2);
+
+    let (json, status, _) = get("/me/timeline")
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: json, expected: json!({
+        "data": {"items": [
+            {"content": "third"},
+        ]}
+    }));
+}
+
+#[async_std::test]
+async fn cannot_edit_or_delete_others_events() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+    let event_id = post_event("hello", &jim_token, &server).await;
+
+    let (json, status, _) = patch(&format!("/events/{}", event_id),
+        Some(EditEventPayload {
+            content: "hijacked".to_string(),
+        }))
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 403);
+    assert_json_include!(actual: json, expected: json!({
+        "error": {
+            "message": "You can only change your own events",
+        }
+    }));
+
+    let (_, status, _) = delete(&format!("/events/{}", event_id))
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 403);
+}
+
+#[async_std::test]
+async fn deleting_own_event() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let token = create_user_and_authenticate(&mut server, None).await.token;
+    let event_id = post_event("hello", &token, &server).await;
+
+    post(&format!("/events/{}/like", event_id), None::<()>)
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server)
+        .await;
+
+    let (_, status, _) = delete(&format!("/events/{}", event_id))
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 200);
+
+    let (json, status, _) = get("/me/timeline")
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 200);
+    assert_eq!(json["data"]["items"].as_array().unwrap().len(), 0);
+
+    let (_, status, _) = delete(&format!("/events/{}", event_id))
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 404);
+}
+
+#[async_std::test]
+async fn revisions_of_unknown_event() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let (_, status, _) = get(&format!("/events/{}/revisions", Uuid::new_v4())).send(&mut server).await;
+    assert_eq!(status, 404);
+}
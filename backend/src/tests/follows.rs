@@ -7,7 +7,7 @@ use crate::server;
 #[async_std::test]
 async fn following_another_user() {
     let test_db = TestDb::new().await;
-    let mut server = server(test_db.db()).await;
+    let mut server = server(test_db.config(), test_db.db()).await;
 
     let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
     create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
@@ -15,11 +15,11 @@ async fn following_another_user() {
     for username in &["tim", "jim"] {
         let (json, status, _) = get(&format!("/users/{}/followers", username)).send(&mut server).await;
         assert_eq!(status, 200);
-        assert_json_eq!(json, json!({"data" : []}));
+        assert_json_eq!(json, json!({"data" : {"items": [], "next_cursor": null}}));
 
         let (json, status, _) = get(&format!("/users/{}/followers", username)).send(&mut server).await;
         assert_eq!(status, 200);
-        assert_json_eq!(json, json!({"data" : []}));
+        assert_json_eq!(json, json!({"data" : {"items": [], "next_cursor": null}}));
     }
 
     let (json, status, _) = post("/users/jim/follow", 
@@ -28,41 +28,45 @@ async fn following_another_user() {
         .send(&mut server).await;
     assert_eq!(status, 201);
 
-    assert_json_include!(actual: json, expected: json!({"data" : null}));
+    assert_json_include!(actual: json, expected: json!({"data" : {
+        "username": "jim",
+        "following": true,
+        "followed_by": false,
+    }}));
 
     let (json, status, _) = get("/users/tim/following").send(&mut server).await;
     assert_eq!(status, 200);
-    assert_json_include!(actual: json, expected: json!({"data" : [
+    assert_json_include!(actual: json, expected: json!({"data" : {"items": [
             {
                 "username" : "jim"
             }
-        ]
+        ]}
     }));
 
     let (json, status, _) = get("/users/jim/following").send(&mut server).await;
     assert_eq!(status, 200);
-    assert_json_include!(actual: json, expected: json!({"data" : []}));
+    assert_json_include!(actual: json, expected: json!({"data" : {"items": []}}));
 
     let (json, status, _) = get("/users/tim/followers").send(&mut server).await;
     assert_eq!(status, 200);
-    assert_json_include!(actual: json, expected: json!({"data" : []}));
+    assert_json_include!(actual: json, expected: json!({"data" : {"items": []}}));
 
     let (json, status, _) = get("/users/jim/followers").send(&mut server).await;
     assert_eq!(status, 200);
-    assert_json_include!(actual: json, expected: json!({"data" : [
+    assert_json_include!(actual: json, expected: json!({"data" : {"items": [
             {
                 "username" : "tim"
             }
-        ]
+        ]}
     }));
 }
 
 
 
 #[async_std::test]
-async fn follow_same_user_twice() {
+async fn follow_same_user_twice_is_idempotent() {
     let test_db = TestDb::new().await;
-    let mut server = server(test_db.db()).await;
+    let mut server = server(test_db.config(), test_db.db()).await;
 
     let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string()))
         .await
@@ -79,21 +83,25 @@ async fn follow_same_user_twice() {
         .header("Authorization", format!("Bearer {}", tim_token))
         .send(&mut server)
         .await;
-    assert_eq!(status, 409);
+    assert_eq!(status, 200);
     assert_json_include!(
         actual: json,
         expected: json!({
-            "error": {
-                "message": "You cannot follow the same user twice",
+            "data": {
+                "following": true,
             }
         })
     );
+
+    let (json, status, _) = get("/users/jim/followers").send(&mut server).await;
+    assert_eq!(status, 200);
+    assert_eq!(json["data"]["items"].as_array().unwrap().len(), 1);
 }
 
 #[async_std::test]
 async fn cannot_follow_self() {
     let test_db = TestDb::new().await;
-    let mut server = server(test_db.db()).await;
+    let mut server = server(test_db.config(), test_db.db()).await;
 
     let bobs_token = create_user_and_authenticate(&mut server, Some("bob".to_string()))
         .await
@@ -115,3 +123,121 @@ async fn cannot_follow_self() {
 }
 
 
+
+
+#[async_std::test]
+async fn unfollowing_a_user() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string()))
+        .await
+        .token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string()))
+        .await
+        .token;
+
+    for (token, username) in &[(&tim_token, "jim"), (&jim_token, "tim")] {
+        let (_, status, _) = post(&format!("/users/{}/follow", username), None::<()>)
+            .header("Authorization", format!("Bearer {}", token))
+            .send(&mut server)
+            .await;
+        assert_eq!(status, 201);
+    }
+
+    let (json, status, _) = delete("/users/jim/follow")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 200);
+    assert_json_include!(
+        actual: json,
+        expected: json!({
+            "data": {
+                "username": "jim",
+                "following": false,
+                "followed_by": true,
+            }
+        })
+    );
+
+    let (json, status, _) = get("/users/jim/followers").send(&mut server).await;
+    assert_eq!(status, 200);
+    assert_json_eq!(json, json!({"data" : {"items": [], "next_cursor": null}}));
+
+    let (_, status, _) = delete("/users/jim/follow")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 200);
+}
+
+#[async_std::test]
+async fn unknown_users_are_not_found() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let token = create_user_and_authenticate(&mut server, Some("tim".to_string()))
+        .await
+        .token;
+
+    let (json, status, _) = post("/users/nobody/follow", None::<()>)
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 404);
+    assert_json_include!(
+        actual: json,
+        expected: json!({
+            "error": {
+                "message": "User does not exist",
+            }
+        })
+    );
+
+    let (_, status, _) = delete("/users/nobody/follow")
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 404);
+
+    for path in &["following", "followers"] {
+        let (_, status, _) = get(&format!("/users/nobody/{}", path)).send(&mut server).await;
+        assert_eq!(status, 404);
+    }
+}
+
+#[async_std::test]
+async fn paginating_followers() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    create_user_and_authenticate(&mut server, Some("tim".to_string())).await;
+
+    for username in &["amy", "bob", "cat"] {
+        let token = create_user_and_authenticate(&mut server, Some(username.to_string()))
+            .await
+            .token;
+        let (_, status, _) = post("/users/tim/follow", None::<()>)
+            .header("Authorization", format!("Bearer {}", token))
+            .send(&mut server)
+            .await;
+        assert_eq!(status, 201);
+    }
+
+    let (json, status, _) = get("/users/tim/followers?page_size=2").send(&mut server).await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: &json, expected: json!({"data" : {"items": [
+        {"username": "cat"},
+        {"username": "bob"},
+    ]}}));
+    let cursor = json["data"]["next_cursor"].as_str().unwrap().to_string();
+
+    let (json, status, _) = get(&format!("/users/tim/followers?page_size=2&before={}", cursor)).send(&mut server).await;
+    assert_eq!(status, 200);
+    assert_json_eq!(json["data"]["items"].as_array().unwrap().len(), 1);
+    assert_json_include!(actual: &json, expected: json!({"data" : {
+        "items": [{"username": "amy"}],
+        "next_cursor": null,
+    }}));
+}
\ No newline at end of file
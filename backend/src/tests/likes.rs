@@ -0,0 +1,134 @@
+use crate::tests::test_utils::*;
+use serde_json::json;
+use assert_json_diff::assert_json_include;
+use crate::server;
+use uuid::Uuid;
+
+#[async_std::test]
+async fn liking_an_event() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+
+    let event_id = post_event("hello", &jim_token, &server).await;
+
+    let (json, status, _) = post(&format!("/events/{}/like", event_id), None::<()>)
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 201);
+    assert_json_include!(actual: json, expected: json!({
+        "data": {
+            "event_id": event_id,
+            "like_count": 1,
+            "liked_by_me": true,
+        }
+    }));
+
+    let (_, status, _) = post("/users/jim/follow", None::<()>)
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 201);
+
+    let (json, status, _) = get("/me/timeline")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: json, expected: json!({
+        "data": {"items": [
+            {"content": "hello", "like_count": 1, "liked_by_me": true},
+        ]}
+    }));
+
+    let (json, status, _) = get("/me/timeline")
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: json, expected: json!({
+        "data": {"items": [
+            {"content": "hello", "like_count": 1, "liked_by_me": false},
+        ]}
+    }));
+}
+
+#[async_std::test]
+async fn like_same_event_twice() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let token = create_user_and_authenticate(&mut server, None).await.token;
+    let event_id = post_event("hello", &token, &server).await;
+
+    let (_, status, _) = post(&format!("/events/{}/like", event_id), None::<()>)
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 201);
+
+    let (json, status, _) = post(&format!("/events/{}/like", event_id), None::<()>)
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 409);
+    assert_json_include!(actual: json, expected: json!({
+        "error": {
+            "message": "You cannot like the same event twice",
+        }
+    }));
+}
+
+#[async_std::test]
+async fn unliking_an_event() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let token = create_user_and_authenticate(&mut server, None).await.token;
+    let event_id = post_event("hello", &token, &server).await;
+
+    let (json, status, _) = delete(&format!("/events/{}/like", event_id))
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 404);
+    assert_json_include!(actual: json, expected: json!({
+        "error": {
+            "message": "You have not liked this event",
+        }
+    }));
+
+    post(&format!("/events/{}/like", event_id), None::<()>)
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server)
+        .await;
+
+    let (json, status, _) = delete(&format!("/events/{}/like", event_id))
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: json, expected: json!({
+        "data": {
+            "like_count": 0,
+            "liked_by_me": false,
+        }
+    }));
+}
+
+#[async_std::test]
+async fn liking_unknown_event() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let token = create_user_and_authenticate(&mut server, None).await.token;
+
+    let (_, status, _) = post(&format!("/events/{}/like", Uuid::new_v4()), None::<()>)
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 404);
+}
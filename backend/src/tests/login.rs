@@ -4,12 +4,13 @@ use assert_json_diff::assert_json_include;
 use shared::payloads::LoginPayload;
 
 use crate::server;
+use crate::endpoints::hash_token;
 
 
 #[async_std::test]
 async fn authenticating_without_auth_header() {
     let test_db = TestDb::new().await;
-    let mut server = server(test_db.db()).await;
+    let mut server = server(test_db.config(), test_db.db()).await;
 
     create_user_and_authenticate(&mut server, None).await;
 
@@ -27,7 +28,7 @@ async fn authenticating_without_auth_header() {
 #[async_std::test]
 async fn authenticating_with_invalid_auth_header() {
     let test_db = TestDb::new().await;
-    let mut server = server(test_db.db()).await;
+    let mut server = server(test_db.config(), test_db.db()).await;
 
     let token = create_user_and_authenticate(&mut server, None).await.token;
 
@@ -40,16 +41,16 @@ async fn authenticating_with_invalid_auth_header() {
 #[async_std::test]
 async fn login_unknown_user() {
     let test_db = TestDb::new().await;
-    let mut server = server(test_db.db()).await;
+    let mut server = server(test_db.config(), test_db.db()).await;
 
-    let (_, status, _) = post("/users/Geoff/session", Some(LoginPayload { password : "123456".to_string()})).send(&mut server).await;
+    let (_, status, _) = post("/users/Geoff/session", Some(LoginPayload { password : "correct-horse-7".to_string(), device: None })).send(&mut server).await;
     assert_eq!(status, 404);
 }
 
 #[async_std::test]
 async fn login_invalid_password() {
     let test_db = TestDb::new().await;
-    let mut server = server(test_db.db()).await;
+    let mut server = server(test_db.config(), test_db.db()).await;
 
     let username = "Geoff";
     create_user_and_authenticate(&mut server, Some(username.to_string())).await;
@@ -57,17 +58,52 @@ async fn login_invalid_password() {
     let (json, status, _) = post(
         &format!("/users/{}/session", username),
         Some(LoginPayload {
-            password: "654321".to_string(),
+            password: "battery-staple-9".to_string(),
+            device: None,
         }),
     ).send(&mut server).await;
-    assert_eq!(status, 403);
+    assert_eq!(status, 401);
     assert_json_include!(
         actual: json,
         expected: json!({
             "error": {
-                "status_code": "403",
-                "message": "Something went wrong",
+                "status_code": "401",
+                "code": "incorrect_password",
+                "message": "Incorrect password",
             }
         }),
     );
-}
\ No newline at end of file
+}
+
+#[async_std::test]
+async fn login_ignores_username_case() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    create_user_and_authenticate(&mut server, Some("tim".to_string())).await;
+
+    let (_, status, _) = post("/users/Tim/session", Some(LoginPayload { password: "correct-horse-7".to_string(), device: None }))
+        .send(&mut server).await;
+    assert_eq!(status, 201);
+}
+
+#[async_std::test]
+async fn auth_tokens_are_stored_hashed() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let token = create_user_and_authenticate(&mut server, None).await.token;
+
+    let (stored,): (String,) = sqlx::query_as("select token_hash from auth_tokens")
+        .fetch_one(&test_db.db())
+        .await
+        .unwrap();
+    assert_ne!(stored, token);
+    assert_eq!(stored, hash_token(&token, &test_db.config()).unwrap());
+
+    let (_, status, _) = get("/me")
+        .header("Authorization", format!("Bearer {}", stored))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 401);
+}
@@ -1,10 +1,12 @@
 use crate::tests::test_utils::*;
 use crate::server;
+use crate::endpoints::hash_token;
+use shared::payloads::LoginPayload;
 
 #[async_std::test]
 async fn logging_out() {
     let test_db = TestDb::new().await;
-    let mut server = server(test_db.db()).await;
+    let mut server = server(test_db.config(), test_db.db()).await;
 
     let token = create_user_and_authenticate(&mut server, Some("tim".to_string()))
         .await
@@ -22,9 +24,50 @@ async fn logging_out() {
         .await;
     assert_eq!(status, 200);
 
+    let (remaining,): (i64,) = sqlx::query_as("select count(*) from auth_tokens where token_hash = $1")
+        .bind(hash_token(&token, &test_db.config()).unwrap())
+        .fetch_one(&test_db.db())
+        .await
+        .unwrap();
+    assert_eq!(remaining, 0);
+
     let (_, status, _) = get("/me")
         .header("Authorization", format!("Bearer {}", token))
         .send(&mut server)
         .await;
     assert_eq!(status, 401);
+}
+
+#[async_std::test]
+async fn logging_out_keeps_other_sessions() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let first_token = create_user_and_authenticate(&mut server, Some("tim".to_string()))
+        .await
+        .token;
+
+    let (json, status, _) = post("/users/tim/session", Some(LoginPayload { password: "correct-horse-7".to_string(), device: None }))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 201);
+    let second_token = json["data"]["token"].as_str().unwrap().to_string();
+
+    let (_, status, _) = delete("/users/tim/session")
+        .header("Authorization", format!("Bearer {}", first_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 200);
+
+    let (_, status, _) = get("/me")
+        .header("Authorization", format!("Bearer {}", first_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 401);
+
+    let (_, status, _) = get("/me")
+        .header("Authorization", format!("Bearer {}", second_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 200);
 }
\ No newline at end of file
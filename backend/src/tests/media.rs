@@ -0,0 +1,254 @@
+use crate::tests::test_utils::*;
+use crate::{State, Server};
+use serde_json::json;
+use assert_json_diff::assert_json_include;
+use crate::server;
+use shared::payloads::{CreateEventPayload, UpdateMePayload};
+use image::{DynamicImage, GenericImageView, ImageOutputFormat, RgbImage};
+
+const BOUNDARY: &str = "media-test-boundary";
+
+fn png(width: u32, height: u32) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    DynamicImage::ImageRgb8(RgbImage::new(width, height))
+        .write_to(&mut bytes, ImageOutputFormat::Png)
+        .unwrap();
+    bytes
+}
+
+fn multipart(content_type: &str, file: &[u8]) -> Vec<u8> {
+    let mut body = format!(
+        "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"upload\"\r\nContent-Type: {}\r\n\r\n",
+        BOUNDARY,
+        content_type,
+    ).into_bytes();
+    body.extend_from_slice(file);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", BOUNDARY).as_bytes());
+    body
+}
+
+async fn upload(content_type: &str, file: &[u8], token: &str, server: &Server<State>) -> (serde_json::Value, tide::StatusCode) {
+    let (json, status, _) = post_bytes("/media", &format!("multipart/form-data; boundary={}", BOUNDARY), multipart(content_type, file))
+        .header("Authorization", format!("Bearer {}", token))
+        .send(server).await;
+    (json, status)
+}
+
+async fn upload_png(token: &str, server: &Server<State>) -> String {
+    let (json, status) = upload("image/png", &png(4, 4), token, server).await;
+    assert_eq!(status, 201);
+
+    json["data"]["id"].as_str().unwrap().to_string()
+}
+
+async fn post_event_with_media(media_ids: Vec<String>, token: &str, server: &Server<State>) -> (serde_json::Value, tide::StatusCode) {
+    let (json, status, _) = post("/events",
+        Some(CreateEventPayload {
+            media_ids: media_ids.iter().map(|id| id.parse().unwrap()).collect(),
+            ..new_event("look")
+        }))
+        .header("Authorization", format!("Bearer {}", token))
+        .send(server).await;
+    (json, status)
+}
+
+#[async_std::test]
+async fn uploading_and_serving_an_image() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+
+    let file = png(640, 480);
+    let (json, status) = upload("image/png", &file, &token, &server).await;
+    assert_eq!(status, 201);
+    assert_json_include!(actual: &json, expected: json!({"data": {
+        "content_type": "image/png",
+        "width": 640,
+        "height": 480,
+    }}));
+
+    let mut res = get(json["data"]["url"].as_str().unwrap())
+        .header("Authorization", format!("Bearer {}", token))
+        .send_raw(&server).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.content_type().unwrap().essence(), "image/png");
+    assert_eq!(res["Cache-Control"], "private, no-store");
+    assert_eq!(res.body_bytes().await.unwrap(), file);
+
+    let mut res = get(json["data"]["thumbnail_url"].as_str().unwrap())
+        .header("Authorization", format!("Bearer {}", token))
+        .send_raw(&server).await;
+    assert_eq!(res.status(), 200);
+    let thumbnail = image::load_from_memory(&res.body_bytes().await.unwrap()).unwrap();
+    assert_eq!(thumbnail.dimensions(), (320, 240));
+
+    let (_, status, _) = get(&format!("/media/{}.gif", json["data"]["id"].as_str().unwrap())).send(&server).await;
+    assert_eq!(status, 404);
+    let (_, status, _) = get("/media/..%2FCargo.toml").send(&server).await;
+    assert_eq!(status, 404);
+}
+
+#[async_std::test]
+async fn rejecting_unsupported_uploads() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+
+    let (json, status) = upload("text/plain", b"hello", &token, &server).await;
+    assert_eq!(status, 415);
+    assert_json_include!(actual: json, expected: json!({
+        "error": {"message": "Only PNG, JPEG and GIF images are supported"}
+    }));
+
+    let (json, status) = upload("image/png", b"not a png", &token, &server).await;
+    assert_eq!(status, 415);
+    assert_json_include!(actual: json, expected: json!({
+        "error": {"message": "File is not a valid image"}
+    }));
+
+    // The sign-in is checked before the body is even looked at.
+    let (json, status, _) = post_bytes("/media", "text/plain", b"not multipart".to_vec())
+        .send(&server).await;
+    assert_eq!(status, 400);
+    assert_json_include!(actual: json, expected: json!({
+        "error": {"message": "Missing value for 'Authorization' header"}
+    }));
+}
+
+#[async_std::test]
+async fn rejecting_images_too_big_to_decode() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+
+    // A GIF of a few dozen bytes whose header claims it's 65535 pixels square.
+    let mut file = Vec::new();
+    DynamicImage::ImageRgb8(RgbImage::new(1, 1))
+        .write_to(&mut file, ImageOutputFormat::Gif)
+        .unwrap();
+    file[6..10].copy_from_slice(&[0xff; 4]);
+
+    let (json, status) = upload("image/gif", &file, &token, &server).await;
+    assert_eq!(status, 413);
+    assert_json_include!(actual: json, expected: json!({
+        "error": {"message": "Images can be at most 8192 pixels wide and tall"}
+    }));
+}
+
+#[async_std::test]
+async fn attaching_media_to_an_event() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+
+    let first = upload_png(&tim_token, &server).await;
+    let second = upload_png(&tim_token, &server).await;
+
+    let (json, status) = post_event_with_media(vec![second.clone(), first.clone()], &tim_token, &server).await;
+    assert_eq!(status, 201);
+    let event_id = json["data"]["id"].as_str().unwrap().to_string();
+
+    let (json, status, _) = get(&format!("/events/{}/thread", event_id)).send(&server).await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: json, expected: json!({"data": {"event": {"media": [
+        {"id": second, "url": format!("/media/{}.png", second)},
+        {"id": first},
+    ]}}}));
+
+    // Media can only be attached once, and only by whoever uploaded it.
+    let (_, status) = post_event_with_media(vec![first.clone()], &tim_token, &server).await;
+    assert_eq!(status, 404);
+
+    let jims = upload_png(&jim_token, &server).await;
+    let (json, status) = post_event_with_media(vec![jims], &tim_token, &server).await;
+    assert_eq!(status, 404);
+    assert_json_include!(actual: json, expected: json!({
+        "error": {"message": "Media does not exist"}
+    }));
+
+    let mut ids = Vec::new();
+    for _ in 0..5 {
+        ids.push(upload_png(&tim_token, &server).await);
+    }
+    let (json, status) = post_event_with_media(ids, &tim_token, &server).await;
+    assert_eq!(status, 422);
+    assert_json_include!(actual: json, expected: json!({
+        "error": {"message": "An event can have at most 4 media attachments"}
+    }));
+
+    // Deleting the event removes its files.
+    let (_, status, _) = delete(&format!("/events/{}", event_id))
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&server).await;
+    assert_eq!(status, 200);
+
+    let (_, status, _) = get(&format!("/media/{}.png", first)).send(&server).await;
+    assert_eq!(status, 404);
+}
+
+#[async_std::test]
+async fn media_of_private_events_is_only_served_to_those_who_can_see_them() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+
+    let (_, status, _) = patch("/me", Some(UpdateMePayload { is_private: Some(true), ..Default::default() }))
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&server).await;
+    assert_eq!(status, 200);
+
+    let media_id = upload_png(&jim_token, &server).await;
+    let (_, status) = post_event_with_media(vec![media_id.clone()], &jim_token, &server).await;
+    assert_eq!(status, 201);
+
+    for url in &[format!("/media/{}.png", media_id), format!("/media/{}.thumb.png", media_id)] {
+        let (_, status, _) = get(url).send(&server).await;
+        assert_eq!(status, 404);
+
+        let (_, status, _) = get(url)
+            .header("Authorization", format!("Bearer {}", tim_token))
+            .send(&server).await;
+        assert_eq!(status, 404);
+
+        let res = get(url)
+            .header("Authorization", format!("Bearer {}", jim_token))
+            .send_raw(&server).await;
+        assert_eq!(res.status(), 200);
+        assert_eq!(res["Cache-Control"], "private, max-age=31536000, immutable");
+    }
+}
+
+#[async_std::test]
+async fn unattached_media_is_only_served_to_its_uploader() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+
+    let media_id = upload_png(&jim_token, &server).await;
+
+    for url in &[format!("/media/{}.png", media_id), format!("/media/{}.thumb.png", media_id)] {
+        let (_, status, _) = get(url).send(&server).await;
+        assert_eq!(status, 404);
+
+        let (_, status, _) = get(url)
+            .header("Authorization", format!("Bearer {}", tim_token))
+            .send(&server).await;
+        assert_eq!(status, 404);
+    }
+
+    let (_, status) = post_event_with_media(vec![media_id.clone()], &jim_token, &server).await;
+    assert_eq!(status, 201);
+
+    let res = get(&format!("/media/{}.png", media_id)).send_raw(&server).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res["Cache-Control"], "public, max-age=31536000, immutable");
+}
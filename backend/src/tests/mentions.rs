@@ -0,0 +1,105 @@
+use crate::tests::test_utils::*;
+use serde_json::json;
+use assert_json_diff::assert_json_include;
+use crate::server;
+use shared::payloads::EditEventPayload;
+
+#[async_std::test]
+async fn mentions_on_events() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    create_user_and_authenticate(&mut server, Some("jim".to_string())).await;
+    post_event("hey @jim and @nobody, says @tim", &tim_token, &server).await;
+
+    let (json, status, _) = get("/me/timeline")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server)
+        .await;
+
+    assert_eq!(status, 200);
+    let mentions = json["data"]["items"][0]["mentions"].as_array().unwrap();
+    assert_eq!(mentions.len(), 1);
+    assert_eq!(mentions[0]["username"], "jim");
+    assert!(mentions[0]["id"].is_string());
+}
+
+#[async_std::test]
+async fn listing_mentions() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+
+    post_event("first @jim", &tim_token, &server).await;
+    post_event("not a mention", &tim_token, &server).await;
+    post_event("second @jim", &tim_token, &server).await;
+    post_event("@tim only", &jim_token, &server).await;
+
+    let (json, status, _) = get("/me/mentions")
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server)
+        .await;
+
+    assert_eq!(status, 200);
+    assert_json_include!(actual: &json, expected: json!({
+        "data": {
+            "items": [
+                {"content": "second @jim", "user": {"username": "tim"}},
+                {"content": "first @jim", "user": {"username": "tim"}},
+            ],
+            "next_cursor": null,
+        }
+    }));
+    assert_eq!(json["data"]["items"].as_array().unwrap().len(), 2);
+}
+
+#[async_std::test]
+async fn editing_updates_mentions() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+    create_user_and_authenticate(&mut server, Some("bob".to_string())).await;
+
+    let event_id = post_event("hi @jim", &tim_token, &server).await;
+
+    let (json, status, _) = patch(&format!("/events/{}", event_id), Some(EditEventPayload { content: "hi @bob".to_string() }))
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: &json, expected: json!({
+        "data": {"mentions": [{"username": "bob"}]}
+    }));
+
+    let (json, _, _) = get("/me/mentions")
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(json["data"]["items"], json!([]));
+}
+
+#[async_std::test]
+async fn mentions_ignore_case() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("Jim".to_string())).await.token;
+
+    post_event("hey @JIM", &tim_token, &server).await;
+
+    let (json, status, _) = get("/me/mentions")
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server)
+        .await;
+
+    assert_eq!(status, 200);
+    assert_json_include!(actual: &json, expected: json!({
+        "data": {"items": [{"content": "hey @JIM", "mentions": [{"username": "Jim"}]}]}
+    }));
+}
@@ -0,0 +1,98 @@
+use crate::tests::test_utils::*;
+use crate::endpoints::hash_token;
+use crate::migrations::{self, MigrationError, MIGRATIONS};
+use chrono::{DateTime, Duration, Utc};
+use uuid::Uuid;
+
+#[async_std::test]
+async fn migrations_are_tracked() {
+    let test_db = TestDb::new().await;
+    let db_pool = test_db.db();
+
+    let versions: Vec<i64> = sqlx::query_scalar("select version from schema_migrations order by version")
+        .fetch_all(&db_pool)
+        .await
+        .unwrap();
+    assert_eq!(versions, MIGRATIONS.iter().map(|migration| migration.version).collect::<Vec<_>>());
+
+    // Running them again is a no-op.
+    assert!(migrations::run(&db_pool, &test_db.config()).await.unwrap().is_empty());
+
+    sqlx::query("update schema_migrations set checksum = 'edited' where version = 1")
+        .execute(&db_pool)
+        .await
+        .unwrap();
+    match migrations::run(&db_pool, &test_db.config()).await {
+        Err(MigrationError::ChecksumMismatch { version: 1, .. }) => {}
+        other => panic!("expected a checksum mismatch, got {:?}", other),
+    }
+}
+
+#[async_std::test]
+async fn migrations_roll_back_and_reapply() {
+    let test_db = TestDb::new().await;
+    let db_pool = test_db.db();
+
+    let rolled_back = migrations::rollback(&db_pool, MIGRATIONS.len()).await.unwrap();
+    assert_eq!(rolled_back, MIGRATIONS.iter().rev().map(|migration| migration.name).collect::<Vec<_>>());
+
+    let has_users: Option<String> = sqlx::query_scalar("select to_regclass('users')::text")
+        .fetch_one(&db_pool)
+        .await
+        .unwrap();
+    assert_eq!(has_users, None);
+
+    let applied = migrations::run(&db_pool, &test_db.config()).await.unwrap();
+    assert_eq!(applied, MIGRATIONS.iter().map(|migration| migration.name).collect::<Vec<_>>());
+}
+
+#[async_std::test]
+async fn only_the_initial_schema_is_adopted() {
+    let test_db = TestDb::new().await;
+    let db_pool = test_db.db();
+
+    // A database set up by hand with the original schema is recorded as having it, then brought
+    // up to date.
+    migrations::rollback(&db_pool, MIGRATIONS.len() - 1).await.unwrap();
+    sqlx::query("delete from schema_migrations").execute(&db_pool).await.unwrap();
+
+    let applied = migrations::run(&db_pool, &test_db.config()).await.unwrap();
+    assert_eq!(applied, MIGRATIONS[1..].iter().map(|migration| migration.name).collect::<Vec<_>>());
+
+    // One with any later schema can't be matched to a migration.
+    sqlx::query("delete from schema_migrations").execute(&db_pool).await.unwrap();
+    match migrations::run(&db_pool, &test_db.config()).await {
+        Err(MigrationError::UnrecognisedSchema(_)) => {}
+        other => panic!("expected the schema to be refused, got {:?}", other),
+    }
+}
+
+#[async_std::test]
+async fn tokens_from_before_the_migrations_keep_working() {
+    let test_db = TestDb::new().await;
+    let db_pool = test_db.db();
+    migrations::rollback(&db_pool, MIGRATIONS.len() - 1).await.unwrap();
+
+    let user_id = Uuid::new_v4();
+    sqlx::query("insert into users (id, username, hashed_password, created_at, updated_at) values ($1, 'bob', 'x', now(), now())")
+        .bind(user_id)
+        .execute(&db_pool)
+        .await
+        .unwrap();
+    sqlx::query("insert into auth_tokens (id, user_id, token, created_at, updated_at) values ($1, $2, 'abc', now(), now())")
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .execute(&db_pool)
+        .await
+        .unwrap();
+
+    migrations::run(&db_pool, &test_db.config()).await.unwrap();
+
+    let (token_hash, device, expires_at): (String, Option<String>, DateTime<Utc>) = sqlx::query_as("select token_hash, device, expires_at from auth_tokens")
+        .fetch_one(&db_pool)
+        .await
+        .unwrap();
+    assert_eq!(token_hash, hash_token("abc", &test_db.config()).unwrap());
+    assert_eq!(device, None);
+    assert!(expires_at > Utc::now() + Duration::days(test_db.config().auth_token_ttl_days - 1));
+}
@@ -5,4 +5,23 @@ mod posting;
 mod follows;
 mod timeline;
 mod users;
-mod logout;
\ No newline at end of file
+mod logout;
+mod sessions;
+mod threads;
+mod likes;
+mod reposts;
+mod editing;
+mod timeline_stream;
+mod tags;
+mod mentions;
+mod notifications;
+mod conversations;
+mod blocks;
+mod private_accounts;
+mod media;
+mod search;
+mod passwords;
+mod migrations;
+mod cors;
+mod rate_limits;
+mod openapi;
\ No newline at end of file
@@ -0,0 +1,183 @@
+use crate::tests::test_utils::*;
+use crate::{State, Server};
+use serde_json::json;
+use assert_json_diff::{assert_json_eq, assert_json_include};
+use crate::server;
+use shared::payloads::MarkNotificationsReadPayload;
+use uuid::Uuid;
+
+async fn unread_count(token: &str, server: &Server<State>) -> i64 {
+    let (json, status, _) = get("/me")
+        .header("Authorization", format!("Bearer {}", token))
+        .send(server)
+        .await;
+    assert_eq!(status, 200);
+
+    json["data"]["unread_count"].as_i64().unwrap()
+}
+
+#[async_std::test]
+async fn notified_of_follows_likes_replies_and_mentions() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+
+    let event_id = post_event("hello", &tim_token, &server).await;
+
+    let (_, status, _) = post("/users/tim/follow", None::<()>)
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 201);
+
+    let (_, status, _) = post(&format!("/events/{}/like", event_id), None::<()>)
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 201);
+
+    post_reply("hi back", &event_id, &jim_token, &server).await;
+    post_event("cc @tim", &jim_token, &server).await;
+
+    // Acting on your own things doesn't notify you.
+    post(&format!("/events/{}/like", event_id), None::<()>)
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server)
+        .await;
+    post_reply("reply to myself", &event_id, &tim_token, &server).await;
+
+    let (json, status, _) = get("/me/notifications")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server)
+        .await;
+
+    assert_eq!(status, 200);
+    assert_json_include!(actual: &json, expected: json!({
+        "data": {
+            "items": [
+                {"kind": "mention", "actor": {"username": "jim"}, "event": {"content": "cc @tim"}, "read": false},
+                {"kind": "reply", "actor": {"username": "jim"}, "event": {"content": "hi back"}, "read": false},
+                {"kind": "like", "actor": {"username": "jim"}, "event": {"content": "hello"}, "read": false},
+                {"kind": "follow", "actor": {"username": "jim"}, "event": null, "read": false},
+            ],
+            "next_cursor": null,
+        }
+    }));
+    assert_eq!(json["data"]["items"].as_array().unwrap().len(), 4);
+    assert_eq!(unread_count(&tim_token, &server).await, 4);
+    assert_eq!(unread_count(&jim_token, &server).await, 0);
+}
+
+#[async_std::test]
+async fn marking_notifications_read() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+
+    post_event("@tim one", &jim_token, &server).await;
+    post_event("@tim two", &jim_token, &server).await;
+    post_event("@tim three", &jim_token, &server).await;
+
+    let (json, _, _) = get("/me/notifications")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server)
+        .await;
+    let second_newest = json["data"]["items"][1]["id"].as_str().unwrap().parse::<Uuid>().unwrap();
+
+    let (json, status, _) = post("/me/notifications/read", Some(MarkNotificationsReadPayload { up_to: second_newest }))
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server)
+        .await;
+
+    assert_eq!(status, 200);
+    assert_json_eq!(json, json!({"data": {"unread_count": 1}}));
+    assert_eq!(unread_count(&tim_token, &server).await, 1);
+
+    let (json, _, _) = get("/me/notifications")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server)
+        .await;
+    assert_json_include!(actual: json, expected: json!({
+        "data": {"items": [
+            {"event": {"content": "@tim three"}, "read": false},
+            {"event": {"content": "@tim two"}, "read": true},
+            {"event": {"content": "@tim one"}, "read": true},
+        ]}
+    }));
+}
+
+#[async_std::test]
+async fn marking_someone_elses_notifications_read() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+
+    post_event("@tim hi", &jim_token, &server).await;
+
+    let (json, _, _) = get("/me/notifications")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server)
+        .await;
+    let notification_id = json["data"]["items"][0]["id"].as_str().unwrap().parse::<Uuid>().unwrap();
+
+    let (json, status, _) = post("/me/notifications/read", Some(MarkNotificationsReadPayload { up_to: notification_id }))
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server)
+        .await;
+
+    assert_eq!(status, 404);
+    assert_json_include!(actual: json, expected: json!({
+        "error": {
+            "message": "Notification does not exist"
+        }
+    }));
+    assert_eq!(unread_count(&tim_token, &server).await, 1);
+}
+
+#[async_std::test]
+async fn muted_users_do_not_count_as_unread() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+    let bob_token = create_user_and_authenticate(&mut server, Some("bob".to_string())).await.token;
+
+    post_event("@tim from jim", &jim_token, &server).await;
+    post_event("@tim from bob", &bob_token, &server).await;
+    assert_eq!(unread_count(&tim_token, &server).await, 2);
+
+    let (_, status, _) = post("/users/jim/mute", None::<()>)
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 201);
+    assert_eq!(unread_count(&tim_token, &server).await, 1);
+
+    let (json, _, _) = get("/me/notifications")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server)
+        .await;
+    let newest = json["data"]["items"][0]["id"].as_str().unwrap().parse::<Uuid>().unwrap();
+
+    let (json, status, _) = post("/me/notifications/read", Some(MarkNotificationsReadPayload { up_to: newest }))
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 200);
+    assert_json_eq!(json, json!({"data": {"unread_count": 0}}));
+
+    // What was hidden while muted wasn't read, so it comes back unread.
+    let (_, status, _) = delete("/users/jim/mute")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 200);
+    assert_eq!(unread_count(&tim_token, &server).await, 1);
+}
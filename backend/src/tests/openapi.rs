@@ -0,0 +1,41 @@
+use crate::tests::test_utils::*;
+use crate::server;
+
+#[async_std::test]
+async fn spec_covers_every_route() {
+    let test_db = TestDb::new().await;
+    let server = server(test_db.config(), test_db.db()).await;
+
+    let (json, status, _) = get("/openapi.json").send(&server).await;
+    assert_eq!(status, 200);
+    assert_eq!(json["openapi"], "3.0.3");
+
+    // Routes with handlers of their own are listed alongside the `ApiEndpoint` ones.
+    let paths = &json["paths"];
+    for (path, method) in &[
+        ("/users", "post"),
+        ("/users/{username}/session", "post"),
+        ("/users/{username}/session", "delete"),
+        ("/users/{username}/follow", "post"),
+        ("/users/{username}/follow", "delete"),
+        ("/users/{username}/followers", "get"),
+        ("/users/{username}/following", "get"),
+        ("/me/timeline/stream", "get"),
+        ("/media", "post"),
+        ("/media/{file}", "get"),
+    ] {
+        assert!(paths[path][method].is_object(), "{} {} is missing", method, path);
+    }
+
+    let post_event = &paths["/events"]["post"];
+    assert_eq!(post_event["requestBody"]["content"]["application/json"]["schema"]["$ref"], "#/components/schemas/CreateEventPayload");
+    assert_eq!(post_event["responses"]["default"]["content"]["application/json"]["schema"]["$ref"], "#/components/schemas/ErrorResponse");
+
+    let followers = &paths["/users/{username}/followers"]["get"];
+    let parameters: Vec<_> = followers["parameters"].as_array().unwrap().iter().map(|param| param["name"].as_str().unwrap()).collect();
+    assert_eq!(parameters, vec!["username", "before", "after", "page", "page_size"]);
+
+    let event = &json["components"]["schemas"]["EventResponse"];
+    assert_eq!(event["properties"]["id"]["format"], "uuid");
+    assert_eq!(event["properties"]["tags"]["description"], "Hashtags in `content`, lowercased and without the leading `#`.");
+}
@@ -0,0 +1,213 @@
+use crate::tests::test_utils::*;
+use serde_json::json;
+use assert_json_diff::assert_json_include;
+use shared::payloads::{ChangePasswordPayload, LoginPayload, RequestPasswordResetPayload, ResetPasswordPayload, UpdateMePayload};
+use crate::{State, Server};
+use crate::server;
+use tide::StatusCode;
+use uuid::Uuid;
+
+async fn login(username: &str, password: &str, server: &Server<State>) -> StatusCode {
+    let (_, status, _) = post(
+        &format!("/users/{}/session", username),
+        Some(LoginPayload {
+            password: password.to_string(),
+            device: None,
+        }),
+    ).send(server).await;
+    status
+}
+
+async fn set_email(email: &str, token: &str, server: &Server<State>) {
+    let (json, status, _) = patch("/me", Some(UpdateMePayload { email: Some(email.to_string()), ..Default::default() }))
+        .header("Authorization", format!("Bearer {}", token))
+        .send(server).await;
+    assert_eq!(status, 200);
+    assert_eq!(json["data"]["email"], email);
+}
+
+async fn request_reset(username: &str, server: &Server<State>) {
+    let (_, status, _) = post("/password_resets", Some(RequestPasswordResetPayload { username: username.to_string() }))
+        .send(server).await;
+    assert_eq!(status, 202);
+}
+
+/// The reset token from the most recent mail, which has it on a line of its own.
+async fn reset_token_sent_to(email: &str) -> String {
+    let mails = mail_sent_to(email).await;
+    assert_eq!(mails.len(), 1);
+
+    mails[0]
+        .lines()
+        .find(|line| line.len() == 32 && line.chars().all(|c| c.is_ascii_alphanumeric()))
+        .unwrap()
+        .to_string()
+}
+
+fn unique_email() -> String {
+    format!("{}@example.com", Uuid::new_v4())
+}
+
+#[async_std::test]
+async fn changing_password_signs_out_other_sessions() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let other_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+    let (json, _, _) = post("/users/tim/session", Some(LoginPayload { password: "correct-horse-7".to_string(), device: None }))
+        .send(&server).await;
+    let old_session = json["data"]["token"].as_str().unwrap().to_string();
+
+    let (json, status, _) = put("/me/password", Some(ChangePasswordPayload {
+            current_password: "wrong".to_string(),
+            new_password: "battery-staple-9".to_string(),
+        }))
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&server).await;
+    assert_eq!(status, 401);
+    assert_json_include!(actual: json, expected: json!({
+        "error": {"code": "incorrect_password", "message": "Incorrect password"}
+    }));
+
+    let (json, status, _) = put("/me/password", Some(ChangePasswordPayload {
+            current_password: "correct-horse-7".to_string(),
+            new_password: "short".to_string(),
+        }))
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&server).await;
+    assert_eq!(status, 422);
+    assert_json_include!(actual: json, expected: json!({
+        "error": {"fields": [{"field": "new_password", "message": "must be at least 8 characters"}]}
+    }));
+
+    let (_, status, _) = put("/me/password", Some(ChangePasswordPayload {
+            current_password: "correct-horse-7".to_string(),
+            new_password: "battery-staple-9".to_string(),
+        }))
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&server).await;
+    assert_eq!(status, 200);
+
+    assert_eq!(login("tim", "correct-horse-7", &server).await, 401);
+    assert_eq!(login("tim", "battery-staple-9", &server).await, 201);
+
+    let (_, status, _) = get("/me").header("Authorization", format!("Bearer {}", token)).send(&server).await;
+    assert_eq!(status, 200);
+    let (_, status, _) = get("/me").header("Authorization", format!("Bearer {}", old_session)).send(&server).await;
+    assert_eq!(status, 401);
+
+    // Other accounts are left alone.
+    let (_, status, _) = get("/me").header("Authorization", format!("Bearer {}", other_token)).send(&server).await;
+    assert_eq!(status, 200);
+}
+
+#[async_std::test]
+async fn resetting_a_forgotten_password() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let email = unique_email();
+    set_email(&email, &token, &server).await;
+
+    // The username is matched regardless of case, as when logging in.
+    request_reset("Tim", &server).await;
+    let reset_token = reset_token_sent_to(&email).await;
+
+    let (_, status, _) = post("/password_resets/reset", Some(ResetPasswordPayload {
+            token: reset_token.clone(),
+            new_password: "battery-staple-9".to_string(),
+        }))
+        .send(&server).await;
+    assert_eq!(status, 200);
+
+    assert_eq!(login("tim", "battery-staple-9", &server).await, 201);
+    let (_, status, _) = get("/me").header("Authorization", format!("Bearer {}", token)).send(&server).await;
+    assert_eq!(status, 401);
+
+    // Tokens only work once.
+    let (json, status, _) = post("/password_resets/reset", Some(ResetPasswordPayload {
+            token: reset_token,
+            new_password: "hijacked-pass-1".to_string(),
+        }))
+        .send(&server).await;
+    assert_eq!(status, 404);
+    assert_json_include!(actual: json, expected: json!({
+        "error": {"message": "Password reset token is invalid or expired"}
+    }));
+    assert_eq!(login("tim", "hijacked-pass-1", &server).await, 401);
+}
+
+#[async_std::test]
+async fn reset_requests_dont_reveal_accounts() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    create_user_and_authenticate(&mut server, Some("tim".to_string())).await;
+
+    // Neither an unknown user nor one without an email gets anything different back.
+    request_reset("nobody", &server).await;
+    request_reset("tim", &server).await;
+
+    let (_, status, _) = post("/password_resets/reset", Some(ResetPasswordPayload {
+            token: "made-up".to_string(),
+            new_password: "battery-staple-9".to_string(),
+        }))
+        .send(&server).await;
+    assert_eq!(status, 404);
+}
+
+#[async_std::test]
+async fn expired_reset_tokens_are_rejected() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let email = unique_email();
+    set_email(&email, &token, &server).await;
+
+    request_reset("tim", &server).await;
+    let reset_token = reset_token_sent_to(&email).await;
+
+    sqlx::query("update password_resets set expires_at = now() - interval '1 minute'")
+        .execute(&test_db.db())
+        .await
+        .unwrap();
+
+    let (_, status, _) = post("/password_resets/reset", Some(ResetPasswordPayload {
+            token: reset_token,
+            new_password: "battery-staple-9".to_string(),
+        }))
+        .send(&server).await;
+    assert_eq!(status, 404);
+    assert_eq!(login("tim", "correct-horse-7", &server).await, 201);
+}
+
+#[async_std::test]
+async fn invalid_emails() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+
+    let (json, status, _) = patch("/me", Some(UpdateMePayload { email: Some("not an email".to_string()), ..Default::default() }))
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&server).await;
+    assert_eq!(status, 422);
+    assert_json_include!(actual: json, expected: json!({"error": {"code": "invalid_email", "message": "email is invalid"}}));
+
+    let email = unique_email();
+    set_email(&email, &tim_token, &server).await;
+
+    let (json, status, _) = patch("/me", Some(UpdateMePayload { email: Some(email.to_uppercase()), ..Default::default() }))
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&server).await;
+    assert_eq!(status, 409);
+    assert_json_include!(actual: json, expected: json!({"error": {"code": "email_taken", "message": "Email is already taken"}}));
+
+    // The email is private to the account.
+    let (json, _, _) = get("/users/tim").send(&server).await;
+    assert!(json["data"].get("email").is_none());
+}
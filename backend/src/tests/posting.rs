@@ -2,18 +2,21 @@ use crate::tests::test_utils::*;
 use serde_json::json;
 use assert_json_diff::assert_json_include;
 use crate::server;
-use shared::payloads::CreateEventPayload;
+use shared::payloads::{CreateEventPayload, EditEventPayload};
 
 #[async_std::test]
 async fn make_valid_post() {
     let test_db = TestDb::new().await;
-    let mut server = server(test_db.db()).await;
+    let mut server = server(test_db.config(), test_db.db()).await;
 
     let token = create_user_and_authenticate(&mut server, None).await.token;
 
     let (json, status, _) = post("/events",
         Some(CreateEventPayload {
             content: "Hello".to_string(),
+            in_reply_to: None,
+            quote_of: None,
+            media_ids: vec![],
         })).header("Authorization", format!("Bearer {}", token))
         .send(&mut server).await;
 
@@ -28,7 +31,7 @@ async fn make_valid_post() {
 #[async_std::test]
 async fn make_invalid_post() {
     let test_db = TestDb::new().await;
-    let mut server = server(test_db.db()).await;
+    let mut server = server(test_db.config(), test_db.db()).await;
 
     let token = create_user_and_authenticate(&mut server, None).await.token;
 
@@ -36,12 +39,18 @@ async fn make_invalid_post() {
     let (json, status, _) = post("/events", 
         Some(CreateEventPayload {
             content: text,
+            in_reply_to: None,
+            quote_of: None,
+            media_ids: vec![],
         })).header("Authorization", format!("Bearer {}", token))
         .send(&mut server).await;
 
-    assert_eq!(status, 409);
+    assert_eq!(status, 422);
     assert_json_include!(actual: json, expected: json!({
         "error" : {
+            "code" : "content_too_long",
+            "field" : "content",
+            "max_length" : 200,
             "message" : "content too long"
         }
     }));
@@ -50,7 +59,7 @@ async fn make_invalid_post() {
 #[async_std::test]
 async fn invalid_data_gets_mapped_to_a_422() {
     let test_db = TestDb::new().await;
-    let mut server = server(test_db.db()).await;
+    let mut server = server(test_db.config(), test_db.db()).await;
 
     let token = create_user_and_authenticate(&mut server, None).await.token;
 
@@ -69,4 +78,25 @@ async fn invalid_data_gets_mapped_to_a_422() {
             }
         })
     );
-}
\ No newline at end of file
+}
+#[async_std::test]
+async fn length_is_counted_in_characters() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let token = create_user_and_authenticate(&mut server, None).await.token;
+
+    // 200 characters, but twice as many bytes.
+    let text = std::iter::repeat('é').take(200).collect::<String>();
+    let event_id = post_event(&text, &token, &server).await;
+
+    let (_, status, _) = patch(&format!("/events/{}", event_id), Some(EditEventPayload { content: format!("{}!", &text[2..]) }))
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server).await;
+    assert_eq!(status, 200);
+
+    let (_, status, _) = patch(&format!("/events/{}", event_id), Some(EditEventPayload { content: format!("{}!", text) }))
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server).await;
+    assert_eq!(status, 422);
+}
@@ -0,0 +1,244 @@
+use crate::tests::test_utils::*;
+use crate::{State, Server};
+use serde_json::json;
+use assert_json_diff::{assert_json_include, assert_json_eq};
+use crate::server;
+use shared::payloads::{CreateEventPayload, UpdateMePayload};
+
+async fn set_private(is_private: bool, token: &str, server: &Server<State>) {
+    let (json, status, _) = patch("/me", Some(UpdateMePayload { is_private: Some(is_private), ..Default::default() }))
+        .header("Authorization", format!("Bearer {}", token))
+        .send(server).await;
+    assert_eq!(status, 200);
+    assert_eq!(json["data"]["is_private"], is_private);
+}
+
+#[async_std::test]
+async fn following_a_private_account_needs_approval() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+
+    set_private(true, &jim_token, &server).await;
+
+    let (json, status, _) = post("/users/jim/follow", None::<()>)
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 202);
+    assert_json_include!(actual: json, expected: json!({"data": {
+        "username": "jim",
+        "following": false,
+        "requested": true,
+    }}));
+
+    // Asking again doesn't create another request.
+    let (_, status, _) = post("/users/jim/follow", None::<()>)
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 200);
+
+    let (json, status, _) = get("/me/follow_requests")
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: &json, expected: json!({"data": {"items": [
+        {"user": {"username": "tim"}},
+    ]}}));
+    let request_id = json["data"]["items"][0]["id"].as_str().unwrap().to_string();
+
+    let (json, _, _) = get("/me/notifications")
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server).await;
+    assert_json_include!(actual: json, expected: json!({"data": {"items": [
+        {"kind": "follow_request", "actor": {"username": "tim"}},
+    ]}}));
+
+    // Only the account asked can answer.
+    let (_, status, _) = post(&format!("/me/follow_requests/{}/approve", request_id), None::<()>)
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 404);
+
+    let (json, status, _) = post(&format!("/me/follow_requests/{}/approve", request_id), None::<()>)
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: json, expected: json!({"data": {
+        "username": "tim",
+        "followed_by": true,
+    }}));
+
+    let (json, _, _) = get("/me/follow_requests")
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server).await;
+    assert_json_eq!(json, json!({"data": {"items": [], "next_cursor": null}}));
+
+    let (json, status, _) = get("/users/jim/followers")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: json, expected: json!({"data": {"items": [{"username": "tim"}]}}));
+}
+
+#[async_std::test]
+async fn rejecting_a_follow_request() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+
+    set_private(true, &jim_token, &server).await;
+
+    let (_, status, _) = post("/users/jim/follow", None::<()>)
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 202);
+
+    let (json, _, _) = get("/me/follow_requests")
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server).await;
+    let request_id = json["data"]["items"][0]["id"].as_str().unwrap().to_string();
+
+    let (json, status, _) = post(&format!("/me/follow_requests/{}/reject", request_id), None::<()>)
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: json, expected: json!({"data": {
+        "username": "tim",
+        "followed_by": false,
+    }}));
+
+    let (_, status, _) = post(&format!("/me/follow_requests/{}/reject", request_id), None::<()>)
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 404);
+
+    let (json, status, _) = get("/users/jim/followers")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 403);
+    assert_json_include!(actual: json, expected: json!({
+        "error": {"message": "This account is private"}
+    }));
+}
+
+#[async_std::test]
+async fn private_events_are_hidden_from_non_followers() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+
+    set_private(true, &jim_token, &server).await;
+    let event_id = post_event("secret #hidden", &jim_token, &server).await;
+
+    let (_, status, _) = get(&format!("/events/{}/thread", event_id)).send(&mut server).await;
+    assert_eq!(status, 404);
+
+    let (json, status, _) = get("/tags/hidden/events").send(&mut server).await;
+    assert_eq!(status, 200);
+    assert_json_eq!(json, json!({"data": {"items": [], "next_cursor": null}}));
+
+    let (_, status, _) = post(&format!("/events/{}/like", event_id), None::<()>)
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 404);
+
+    let (_, status, _) = post("/events",
+        Some(CreateEventPayload { in_reply_to: Some(event_id.parse().unwrap()), ..new_event("reply") }))
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 404);
+
+    // The owner still sees their own events.
+    let (_, status, _) = get(&format!("/events/{}/thread", event_id))
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 200);
+
+    // Going public lets pending requests through and shows the events to everyone.
+    let (_, status, _) = post("/users/jim/follow", None::<()>)
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 202);
+
+    set_private(false, &jim_token, &server).await;
+
+    let (json, _, _) = get("/me/timeline")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_json_include!(actual: json, expected: json!({"data": {"items": [{"content": "secret #hidden"}]}}));
+
+    let (_, status, _) = get(&format!("/events/{}/thread", event_id)).send(&mut server).await;
+    assert_eq!(status, 200);
+}
+
+#[async_std::test]
+async fn followers_cannot_repost_private_events() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+
+    let (_, status, _) = post("/users/jim/follow", None::<()>)
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 201);
+
+    // Existing followers stay when an account goes private.
+    set_private(true, &jim_token, &server).await;
+    let event_id = post_event("for followers", &jim_token, &server).await;
+
+    let (json, _, _) = get("/me/timeline")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_json_include!(actual: json, expected: json!({"data": {"items": [{"content": "for followers"}]}}));
+
+    let (json, status, _) = post(&format!("/events/{}/repost", event_id), None::<()>)
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 403);
+    assert_json_include!(actual: json, expected: json!({
+        "error": {"message": "You cannot repost events from a private account"}
+    }));
+}
+
+#[async_std::test]
+async fn unfollowed_private_events_cannot_be_unliked() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+
+    let (_, status, _) = post("/users/jim/follow", None::<()>)
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 201);
+
+    set_private(true, &jim_token, &server).await;
+    let event_id = post_event("for followers", &jim_token, &server).await;
+
+    let (_, status, _) = post(&format!("/events/{}/like", event_id), None::<()>)
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 201);
+
+    let (_, status, _) = delete("/users/jim/follow")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 200);
+
+    // Telling "not liked" apart from "not found" would give away that the event exists.
+    for path in &["like", "repost"] {
+        let (_, status, _) = delete(&format!("/events/{}/{}", event_id, path))
+            .header("Authorization", format!("Bearer {}", tim_token))
+            .send(&mut server).await;
+        assert_eq!(status, 404);
+    }
+}
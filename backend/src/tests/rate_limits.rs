@@ -0,0 +1,111 @@
+use crate::tests::test_utils::*;
+use crate::{State, Server};
+use crate::server;
+use crate::rate_limit::Limit;
+use serde_json::json;
+use assert_json_diff::assert_json_include;
+use shared::payloads::LoginPayload;
+use std::time::Duration;
+
+async fn limited_server(test_db: &TestDb, limits: &[(&str, Limit)]) -> Server<State> {
+    let mut config = test_db.config();
+    config.rate_limiting = true;
+    config.trust_forwarded_for = true;
+    for (name, limit) in limits {
+        config.rate_limits.insert(name.to_string(), *limit);
+    }
+    server(config, test_db.db()).await
+}
+
+fn login_from(ip: &str) -> TestRequest {
+    post("/users/tim/session", Some(LoginPayload { password: "wrong-guess-1".to_string(), device: None }))
+        .header("X-Forwarded-For", ip)
+}
+
+fn post_event(token: &str) -> TestRequest {
+    post("/events", Some(new_event("Hello")))
+        .header("Authorization", format!("Bearer {}", token))
+}
+
+#[async_std::test]
+async fn logins_are_limited_per_ip() {
+    let test_db = TestDb::new().await;
+    let mut server = limited_server(&test_db, &[("login.ip", Limit::new(2, Duration::from_secs(60)))]).await;
+
+    create_user_and_authenticate(&mut server, Some("tim".to_string())).await;
+
+    let (_, status, headers) = login_from("10.0.0.1").send(&server).await;
+    assert_eq!(status, 401);
+    assert_eq!(headers["x-ratelimit-limit"], "2");
+    assert_eq!(headers["x-ratelimit-remaining"], "1");
+
+    let (_, status, _) = login_from("10.0.0.1").send(&server).await;
+    assert_eq!(status, 401);
+
+    let (json, status, headers) = login_from("10.0.0.1").send(&server).await;
+    assert_eq!(status, 429);
+    assert_eq!(headers["retry-after"], "30");
+    assert_eq!(headers["x-ratelimit-remaining"], "0");
+    assert_json_include!(actual: json, expected: json!({
+        "error": {"status_code": "429", "code": "rate_limited", "retry_after": 30, "message": "Too many requests, try again in 30 seconds"}
+    }));
+
+    // Other clients, and other routes, are unaffected.
+    let (_, status, _) = login_from("10.0.0.2").send(&server).await;
+    assert_eq!(status, 401);
+    let (_, status, headers) = get("/users/tim").header("X-Forwarded-For", "10.0.0.1").send(&server).await;
+    assert_eq!(status, 200);
+    assert!(headers.get("x-ratelimit-limit").is_none());
+}
+
+#[async_std::test]
+async fn posting_is_limited_per_user() {
+    let test_db = TestDb::new().await;
+    let mut server = limited_server(&test_db, &[("post_event.user", Limit::new(1, Duration::from_secs(60)))]).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+
+    let (_, status, _) = post_event(&tim_token).send(&server).await;
+    assert_eq!(status, 201);
+    let (_, status, headers) = post_event(&tim_token).send(&server).await;
+    assert_eq!(status, 429);
+    assert_eq!(headers["retry-after"], "60");
+
+    // Both posts came from the same IP, but the limit is on the user.
+    let (_, status, _) = post_event(&jim_token).send(&server).await;
+    assert_eq!(status, 201);
+}
+
+#[async_std::test]
+async fn forwarded_ips_are_ignored_unless_trusted() {
+    let test_db = TestDb::new().await;
+    let mut config = test_db.config();
+    config.rate_limiting = true;
+    config.rate_limits.insert("login.ip".to_string(), Limit::new(1, Duration::from_secs(60)));
+    let mut server = server(config, test_db.db()).await;
+
+    create_user_and_authenticate(&mut server, Some("tim".to_string())).await;
+
+    let (_, status, _) = login_from("10.0.0.1").send(&server).await;
+    assert_eq!(status, 401);
+    let (_, status, _) = login_from("10.0.0.2").send(&server).await;
+    assert_eq!(status, 429);
+}
+
+#[async_std::test]
+async fn only_the_ip_our_proxy_saw_counts() {
+    let test_db = TestDb::new().await;
+    let mut server = limited_server(&test_db, &[("login.ip", Limit::new(1, Duration::from_secs(60)))]).await;
+
+    create_user_and_authenticate(&mut server, Some("tim".to_string())).await;
+
+    // Clients can put whatever they like in front of the address the proxy appends.
+    let (_, status, _) = login_from("1.1.1.1, 10.0.0.1").send(&server).await;
+    assert_eq!(status, 401);
+    let (_, status, _) = login_from("2.2.2.2, 10.0.0.1").send(&server).await;
+    assert_eq!(status, 429);
+
+    let (_, status, _) = login_from("2.2.2.2, 10.0.0.2").send(&server).await;
+    assert_eq!(status, 401);
+}
@@ -0,0 +1,196 @@
+use crate::tests::test_utils::*;
+use crate::{State, Server};
+use serde_json::json;
+use assert_json_diff::assert_json_include;
+use crate::server;
+use shared::payloads::CreateEventPayload;
+use uuid::Uuid;
+
+async fn follow(username: &str, token: &str, server: &Server<State>) {
+    let (_, status, _) = post(&format!("/users/{}/follow", username), None::<()>)
+        .header("Authorization", format!("Bearer {}", token))
+        .send(server)
+        .await;
+    assert_eq!(status, 201);
+}
+
+async fn repost(event_id: &str, token: &str, server: &Server<State>) {
+    let (_, status, _) = post(&format!("/events/{}/repost", event_id), None::<()>)
+        .header("Authorization", format!("Bearer {}", token))
+        .send(server)
+        .await;
+    assert_eq!(status, 201);
+}
+
+#[async_std::test]
+async fn sees_reposts_from_following() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+    let kim_token = create_user_and_authenticate(&mut server, Some("kim".to_string())).await.token;
+
+    let event_id = post_event("hello", &kim_token, &server).await;
+    follow("jim", &tim_token, &server).await;
+    repost(&event_id, &jim_token, &server).await;
+
+    let (json, status, _) = get("/me/timeline")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: json, expected: json!({
+        "data": {"items": [
+            {
+                "content": "hello",
+                "user": {"username": "kim"},
+                "reposted_by": {"username": "jim"},
+                "repost_count": 1,
+                "reposted_by_me": false,
+            },
+        ]}
+    }));
+}
+
+#[async_std::test]
+async fn duplicate_appearances_are_collapsed() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+    let kim_token = create_user_and_authenticate(&mut server, Some("kim".to_string())).await.token;
+
+    let event_id = post_event("hello", &kim_token, &server).await;
+    post_event("later", &kim_token, &server).await;
+    follow("jim", &tim_token, &server).await;
+    follow("kim", &tim_token, &server).await;
+    repost(&event_id, &jim_token, &server).await;
+    repost(&event_id, &tim_token, &server).await;
+
+    let (json, status, _) = get("/me/timeline")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 200);
+    assert_eq!(json["data"]["items"].as_array().unwrap().len(), 2);
+    assert_json_include!(actual: json, expected: json!({
+        "data": {"items": [
+            {
+                "content": "hello",
+                "reposted_by": {"username": "tim"},
+                "repost_count": 2,
+                "reposted_by_me": true,
+            },
+            {
+                "content": "later",
+                "reposted_by": null,
+            },
+        ]}
+    }));
+}
+
+#[async_std::test]
+async fn repost_same_event_twice() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let token = create_user_and_authenticate(&mut server, None).await.token;
+    let event_id = post_event("hello", &token, &server).await;
+    repost(&event_id, &token, &server).await;
+
+    let (json, status, _) = post(&format!("/events/{}/repost", event_id), None::<()>)
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 409);
+    assert_json_include!(actual: json, expected: json!({
+        "error": {
+            "message": "You cannot repost the same event twice",
+        }
+    }));
+}
+
+#[async_std::test]
+async fn undoing_a_repost() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+
+    let event_id = post_event("hello", &jim_token, &server).await;
+    repost(&event_id, &tim_token, &server).await;
+
+    let (json, status, _) = delete(&format!("/events/{}/repost", event_id))
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: json, expected: json!({
+        "data": {
+            "repost_count": 0,
+            "reposted_by_me": false,
+        }
+    }));
+
+    let (json, status, _) = get("/me/timeline")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 200);
+    assert_eq!(json["data"]["items"].as_array().unwrap().len(), 0);
+}
+
+#[async_std::test]
+async fn quoting_an_event() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+
+    let event_id = post_event("original", &jim_token, &server).await;
+    post_quote("my take", &event_id, &tim_token, &server).await;
+
+    let (json, status, _) = get("/me/timeline")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: json, expected: json!({
+        "data": {"items": [
+            {
+                "content": "my take",
+                "user": {"username": "tim"},
+                "quoted_event": {
+                    "id": event_id,
+                    "content": "original",
+                    "user": {"username": "jim"},
+                },
+            },
+        ]}
+    }));
+}
+
+#[async_std::test]
+async fn quoting_unknown_event() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let token = create_user_and_authenticate(&mut server, None).await.token;
+
+    let (json, status, _) = post("/events",
+        Some(CreateEventPayload { quote_of: Some(Uuid::new_v4()), ..new_event("my take") }))
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server).await;
+    assert_eq!(status, 404);
+    assert_json_include!(actual: json, expected: json!({
+        "error": {
+            "code": "not_found",
+            "resource": "event",
+            "message": "Event does not exist",
+        }
+    }));
+}
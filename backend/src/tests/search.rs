@@ -0,0 +1,129 @@
+use crate::tests::test_utils::*;
+use serde_json::json;
+use assert_json_diff::assert_json_include;
+use crate::server;
+use shared::payloads::UpdateMePayload;
+
+fn contents(json: &serde_json::Value) -> Vec<String> {
+    json["data"]["events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|event| event["content"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[async_std::test]
+async fn searching_events_and_users() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let timothy_token = create_user_and_authenticate(&mut server, Some("timothy".to_string())).await.token;
+    create_user_and_authenticate(&mut server, Some("jim".to_string())).await;
+
+    let (_, status, _) = patch("/me", Some(UpdateMePayload { display_name: Some("Jimbo".to_string()), ..Default::default() }))
+        .header("Authorization", format!("Bearer {}", timothy_token))
+        .send(&mut server).await;
+    assert_eq!(status, 200);
+
+    post_event("Cooking pasta tonight", &tim_token, &server).await;
+    post_event("pasta pasta pasta, all the pasta", &timothy_token, &server).await;
+    post_event("Nothing to see here", &tim_token, &server).await;
+
+    let (json, status, _) = get("/search?q=Pasta").send(&mut server).await;
+    assert_eq!(status, 200);
+    assert_eq!(contents(&json), vec!["pasta pasta pasta, all the pasta", "Cooking pasta tonight"]);
+    assert_eq!(json["data"]["users"], json!([]));
+    assert_eq!(json["data"]["next_page"], json!(null));
+
+    // Stemming matches other forms of the word.
+    let (json, _, _) = get("/search?q=cooked").send(&mut server).await;
+    assert_eq!(contents(&json), vec!["Cooking pasta tonight"]);
+
+    // Usernames match by prefix, an exact match first; display names match too.
+    let (json, _, _) = get("/search?q=tim").send(&mut server).await;
+    assert_json_include!(actual: &json, expected: json!({"data": {"users": [
+        {"username": "tim"},
+        {"username": "timothy", "display_name": "Jimbo"},
+    ]}}));
+    let (json, _, _) = get("/search?q=JIM").send(&mut server).await;
+    assert_json_include!(actual: &json, expected: json!({"data": {"users": [
+        {"username": "jim"},
+        {"username": "timothy"},
+    ]}}));
+
+    let (json, _, _) = get("/search?q=%25").send(&mut server).await;
+    assert_eq!(json["data"]["users"], json!([]));
+
+    let (json, status, _) = get("/search?q=%20").send(&mut server).await;
+    assert_eq!(status, 400);
+    assert_json_include!(actual: json, expected: json!({
+        "error": {"message": "Search query cannot be empty"}
+    }));
+}
+
+#[async_std::test]
+async fn search_is_paged() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+
+    for n in 0..3 {
+        post_event(&format!("soup number {}", n), &token, &server).await;
+    }
+
+    let (json, status, _) = get("/search?q=soup&page_size=2").send(&mut server).await;
+    assert_eq!(status, 200);
+    assert_eq!(contents(&json).len(), 2);
+    assert_eq!(json["data"]["next_page"], json!(2));
+
+    let (json, _, _) = get("/search?q=soup&page_size=2&page=2").send(&mut server).await;
+    assert_eq!(contents(&json), vec!["soup number 0"]);
+    assert_eq!(json["data"]["next_page"], json!(null));
+}
+
+#[async_std::test]
+async fn search_respects_blocks_and_private_accounts() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+    let kim_token = create_user_and_authenticate(&mut server, Some("kim".to_string())).await.token;
+
+    post_event("secret garden", &jim_token, &server).await;
+    post_event("public garden", &kim_token, &server).await;
+
+    let (_, status, _) = patch("/me", Some(UpdateMePayload { is_private: Some(true), ..Default::default() }))
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 200);
+
+    let (_, status, _) = post("/users/kim/block", None::<()>)
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 201);
+
+    let (json, _, _) = get("/search?q=garden")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(json["data"]["events"], json!([]));
+
+    let (json, _, _) = get("/search?q=kim")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(json["data"]["users"], json!([]));
+
+    // The private account is still listed, and its owner finds their own events.
+    let (json, _, _) = get("/search?q=jim")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_json_include!(actual: json, expected: json!({"data": {"users": [{"username": "jim", "is_private": true}]}}));
+
+    let (json, _, _) = get("/search?q=garden")
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server).await;
+    assert_eq!(contents(&json), vec!["public garden", "secret garden"]);
+}
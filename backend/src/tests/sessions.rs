@@ -0,0 +1,145 @@
+use crate::tests::test_utils::*;
+use serde_json::json;
+use assert_json_diff::assert_json_include;
+use shared::payloads::LoginPayload;
+use crate::{State, Server};
+use crate::server;
+
+async fn login(username: &str, device: Option<&str>, server: &Server<State>) -> String {
+    let (json, status, _) = post(
+        &format!("/users/{}/session", username),
+        Some(LoginPayload {
+            password: "correct-horse-7".to_string(),
+            device: device.map(|device| device.to_string()),
+        }),
+    ).send(server).await;
+    assert_eq!(status, 201);
+
+    json["data"]["token"].as_str().unwrap().to_string()
+}
+
+#[async_std::test]
+async fn listing_sessions() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    create_user_and_authenticate(&mut server, Some("tim".to_string())).await;
+    let token = login("tim", Some("laptop"), &server).await;
+
+    let (json, status, _) = get("/me/sessions")
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 200);
+    assert_eq!(json["data"].as_array().unwrap().len(), 2);
+    assert_json_include!(
+        actual: json,
+        expected: json!({
+            "data": [
+                {
+                    "device": "laptop",
+                    "current": true,
+                },
+                {
+                    "device": null,
+                    "current": false,
+                },
+            ]
+        })
+    );
+}
+
+#[async_std::test]
+async fn revoking_a_session() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let phone_token = create_user_and_authenticate(&mut server, Some("tim".to_string()))
+        .await
+        .token;
+    let laptop_token = login("tim", Some("laptop"), &server).await;
+
+    let (json, _, _) = get("/me/sessions")
+        .header("Authorization", format!("Bearer {}", laptop_token))
+        .send(&mut server)
+        .await;
+    let phone_session_id = json["data"][1]["id"].as_str().unwrap().to_string();
+
+    let (_, status, _) = delete(&format!("/me/sessions/{}", phone_session_id))
+        .header("Authorization", format!("Bearer {}", laptop_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 200);
+
+    let (_, status, _) = get("/me")
+        .header("Authorization", format!("Bearer {}", phone_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 401);
+
+    let (_, status, _) = get("/me")
+        .header("Authorization", format!("Bearer {}", laptop_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 200);
+}
+
+#[async_std::test]
+async fn cannot_revoke_another_users_session() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string()))
+        .await
+        .token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string()))
+        .await
+        .token;
+
+    let (json, _, _) = get("/me/sessions")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server)
+        .await;
+    let tim_session_id = json["data"][0]["id"].as_str().unwrap().to_string();
+
+    let (json, status, _) = delete(&format!("/me/sessions/{}", tim_session_id))
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 404);
+    assert_json_include!(
+        actual: json,
+        expected: json!({
+            "error": {
+                "message": "Session does not exist",
+            }
+        })
+    );
+}
+
+#[async_std::test]
+async fn expired_token_is_rejected() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let token = create_user_and_authenticate(&mut server, None).await.token;
+
+    sqlx::query("update auth_tokens set expires_at = now() - interval '1 day'")
+        .execute(&test_db.db())
+        .await
+        .unwrap();
+
+    let (json, status, _) = get("/me")
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 401);
+    assert_json_include!(
+        actual: json,
+        expected: json!({
+            "error": {
+                "message": "Auth token expired",
+            }
+        })
+    );
+}
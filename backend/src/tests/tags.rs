@@ -0,0 +1,161 @@
+use crate::tests::test_utils::*;
+use serde_json::json;
+use assert_json_diff::{assert_json_eq, assert_json_include};
+use crate::server;
+use shared::payloads::{EditEventPayload, UpdateMePayload};
+
+#[async_std::test]
+async fn tags_on_events() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let token = create_user_and_authenticate(&mut server, None).await.token;
+    post_event("Learning #Tide with #rust, #RUST and issue#12", &token, &server).await;
+
+    let (json, status, _) = get("/me/timeline")
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server)
+        .await;
+
+    assert_eq!(status, 200);
+    assert_eq!(json["data"]["items"][0]["tags"], json!(["rust", "tide"]));
+}
+
+#[async_std::test]
+async fn events_for_tag() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let token = create_user_and_authenticate(&mut server, None).await.token;
+    post_event("oldest #rust", &token, &server).await;
+    post_event("not tagged", &token, &server).await;
+    post_event("middle #Rust", &token, &server).await;
+    post_event("other #go", &token, &server).await;
+    post_event("newest #rust", &token, &server).await;
+
+    let (json, status, _) = get("/tags/RUST/events?page_size=2").send(&mut server).await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: &json, expected: json!({
+        "data": {"items": [
+            {"content": "newest #rust", "tags": ["rust"]},
+            {"content": "middle #Rust", "tags": ["rust"]},
+        ]}
+    }));
+    assert_eq!(json["data"]["items"].as_array().unwrap().len(), 2);
+    let cursor = json["data"]["next_cursor"].as_str().unwrap().to_string();
+
+    let (json, status, _) = get(&format!("/tags/rust/events?page_size=2&before={}", cursor)).send(&mut server).await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: &json, expected: json!({
+        "data": {
+            "items": [{"content": "oldest #rust"}],
+            "next_cursor": null,
+        }
+    }));
+    assert_eq!(json["data"]["items"].as_array().unwrap().len(), 1);
+}
+
+#[async_std::test]
+async fn editing_updates_tags() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let token = create_user_and_authenticate(&mut server, None).await.token;
+    let event_id = post_event("about #rust", &token, &server).await;
+
+    let (json, status, _) = patch(&format!("/events/{}", event_id), Some(EditEventPayload { content: "about #go".to_string() }))
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 200);
+    assert_eq!(json["data"]["tags"], json!(["go"]));
+
+    let (json, _, _) = get("/tags/rust/events").send(&mut server).await;
+    assert_eq!(json["data"]["items"], json!([]));
+
+    let (json, _, _) = get("/tags/go/events").send(&mut server).await;
+    assert_json_include!(actual: json, expected: json!({
+        "data": {"items": [{"content": "about #go"}]}
+    }));
+}
+
+#[async_std::test]
+async fn trending_tags() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let token = create_user_and_authenticate(&mut server, None).await.token;
+    post_event("#rust #go", &token, &server).await;
+    post_event("#rust", &token, &server).await;
+    post_event("#old #old #old", &token, &server).await;
+    post_event("#old", &token, &server).await;
+
+    sqlx::query("update event_tags set created_at = now() - interval '2 days' where tag = 'old'")
+        .execute(&test_db.db())
+        .await
+        .unwrap();
+
+    let (json, status, _) = get("/tags/trending").send(&mut server).await;
+
+    assert_eq!(status, 200);
+    assert_json_eq!(json, json!({
+        "data": [
+            {"tag": "rust", "event_count": 2},
+            {"tag": "go", "event_count": 1},
+        ]
+    }));
+}
+
+#[async_std::test]
+async fn private_accounts_do_not_trend() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+
+    let (_, status, _) = patch("/me", Some(UpdateMePayload { is_private: Some(true), ..Default::default() }))
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&server).await;
+    assert_eq!(status, 200);
+
+    post_event("#rust", &tim_token, &server).await;
+    post_event("#secret #rust", &jim_token, &server).await;
+
+    let (json, status, _) = get("/tags/trending").send(&mut server).await;
+
+    assert_eq!(status, 200);
+    assert_json_eq!(json, json!({
+        "data": [
+            {"tag": "rust", "event_count": 1},
+        ]
+    }));
+}
+
+#[async_std::test]
+async fn editing_does_not_make_old_tags_trend() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let token = create_user_and_authenticate(&mut server, None).await.token;
+    let event_id = post_event("about #rust", &token, &server).await;
+
+    sqlx::query("update events set created_at = now() - interval '2 days'")
+        .execute(&test_db.db())
+        .await
+        .unwrap();
+    sqlx::query("update event_tags set created_at = now() - interval '2 days'")
+        .execute(&test_db.db())
+        .await
+        .unwrap();
+
+    let (_, status, _) = patch(&format!("/events/{}", event_id), Some(EditEventPayload { content: "still about #rust and #go".to_string() }))
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 200);
+
+    let (json, status, _) = get("/tags/trending").send(&mut server).await;
+    assert_eq!(status, 200);
+    assert_json_eq!(json, json!({"data": []}));
+}
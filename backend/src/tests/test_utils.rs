@@ -3,11 +3,12 @@ use tide::http::{Request, Method, Url, Response};
 use tide::StatusCode;
 use crate::State;
 use crate::Server;
+use crate::config::Config;
 use sqlx::{PgConnection,Connection,Postgres,PgPool};
 use serde::Serialize;
 use serde_json::Value;
 use shared::responses::{ApiResponse, TokenResponse};
-use shared::payloads::CreateUserPayload;
+use shared::payloads::{CreateEventPayload, CreateUserPayload};
 
 pub use shared::payloads;
 pub use shared::responses;
@@ -32,6 +33,47 @@ pub fn post<T: Serialize>(url: &str, body: Option<T>) -> TestRequest {
      }
 }
 
+/// A POST with a body that isn't JSON, such as a multipart upload.
+pub fn post_bytes(url: &str, content_type: &str, body: Vec<u8>) -> TestRequest {
+    TestRequest {
+        url: url.to_string(),
+        headers: HashMap::new(),
+        kind: TestRequestKind::PostBytes(content_type.to_string(), body),
+    }
+}
+
+pub fn patch<T: Serialize>(url: &str, body: Option<T>) -> TestRequest {
+    let body = body.map(|body| {
+      serde_json::to_value(body).unwrap()  
+    });
+    TestRequest {
+        url: url.to_string(),
+        headers: HashMap::new(),
+        kind: TestRequestKind::Patch(body),
+     }
+}
+
+pub fn put<T: Serialize>(url: &str, body: Option<T>) -> TestRequest {
+    let body = body.map(|body| {
+      serde_json::to_value(body).unwrap()  
+    });
+    TestRequest {
+        url: url.to_string(),
+        headers: HashMap::new(),
+        kind: TestRequestKind::Put(body),
+     }
+}
+
+/// An OPTIONS request, such as a CORS preflight; its response has no body, so send it with
+/// `send_raw`.
+pub fn options(url: &str) -> TestRequest {
+    TestRequest {
+        url: url.to_string(),
+        headers: HashMap::new(),
+        kind: TestRequestKind::Options,
+    }
+}
+
 pub fn delete(url: &str) -> TestRequest {
     TestRequest {
         url: url.to_string(),
@@ -51,11 +93,32 @@ pub struct TestRequest {
 pub enum TestRequestKind {
     Get,
     Post(Option<Value>),
+    PostBytes(String, Vec<u8>),
+    Patch(Option<Value>),
+    Put(Option<Value>),
     Delete,
+    Options,
 }
 
 impl TestRequest {
     pub(crate) async fn send(self, server: &Server<State>) -> (Value, StatusCode, HashMap<String,String>) {
+        let mut res = self.send_raw(server).await;
+        let status = res.status();
+        let headers = res
+            .iter()
+            .flat_map(|(key, values)| {
+                values
+                    .iter()
+                    .map(move |value| (key.as_str().to_string(), value.as_str().to_string()))
+            })
+            .collect::<HashMap<_, _>>();
+        let json = res.body_json::<Value>().await;
+
+        (json.unwrap(), status, headers)
+    }
+
+    /// Sends the request without reading the response body, for responses that don't end.
+    pub(crate) async fn send_raw(self, server: &Server<State>) -> Response {
         let url = Url::parse(&format!("http://example.com{}", self.url)).unwrap();
         let mut req = match self.kind {
             TestRequestKind::Get => Request::new(Method::Get, url),
@@ -68,7 +131,32 @@ impl TestRequest {
                 };
                 req
             }
+            TestRequestKind::PostBytes(content_type, body) => {
+                let mut req = Request::new(Method::Post, url);
+                req.set_body(body);
+                req.set_content_type(content_type.parse().unwrap());
+                req
+            }
+            TestRequestKind::Patch(body) => {
+                let mut req = Request::new(Method::Patch, url);
+
+                if let Some(body) = body {
+                    req.set_body(body.to_string());
+                    req.set_content_type("application/json".parse().unwrap());
+                };
+                req
+            }
+            TestRequestKind::Put(body) => {
+                let mut req = Request::new(Method::Put, url);
+
+                if let Some(body) = body {
+                    req.set_body(body.to_string());
+                    req.set_content_type("application/json".parse().unwrap());
+                };
+                req
+            }
             TestRequestKind::Delete => Request::new(Method::Delete, url),
+            TestRequestKind::Options => Request::new(Method::Options, url),
         };
 
 
@@ -76,19 +164,7 @@ impl TestRequest {
             req.append_header(key.as_str(), value);
         }
 
-        let mut res: Response = server.respond(req).await.unwrap();
-        let status = res.status();
-        let headers = res
-            .iter()
-            .flat_map(|(key, values)| {
-                values
-                    .iter()
-                    .map(move |value| (key.as_str().to_string(), value.as_str().to_string()))
-            })
-            .collect::<HashMap<_, _>>();
-        let json = res.body_json::<Value>().await;
-
-        (json.unwrap(), status, headers)
+        server.respond(req).await.unwrap()
     }
 
     pub fn header(mut self, key: &str, value: impl ToString) -> Self {
@@ -97,14 +173,14 @@ impl TestRequest {
     }
 }
 
-fn db_url() -> String {
+/// A database of its own for each test, named after the configured one.
+fn db_url(config: &Config) -> String {
     use rand::distributions::Alphanumeric;
     use rand::{thread_rng, Rng};
 
     let rng = thread_rng();
     let suffix: String = rng.sample_iter(&Alphanumeric).take(16).map(char::from).collect();
-    let db_url = std::env::var("DATABASE_URL").expect("Unable to find DATABASE_URL");
-    format!("{}_{}", db_url, suffix)
+    format!("{}_{}", config.database_url, suffix)
 }
 
 fn parse_db_url(db_url: &str) -> (&str, &str) {
@@ -139,19 +215,16 @@ async fn drop_db(db_url: &str) {
     sqlx::query::<Postgres>(&sql).execute(&mut conn).await.unwrap();
 }
 
-async fn run_migrations(db_url: &str) {
-    let (pg_conn, db_name) = parse_db_url(db_url);
-    let mut conn = PgConnection::connect(&format!("{}/{}",pg_conn,db_name)).await.unwrap();
-
-    let sql = async_std::fs::read_to_string("setup.sql").await.unwrap();
-    for query in sql.split(';') {
-        sqlx::query::<Postgres>(&query).execute(&mut conn).await.unwrap();
-    }
+async fn run_migrations(db_url: &str, config: &Config) {
+    let db_pool = PgPool::connect(db_url).await.unwrap();
+    crate::migrations::run(&db_pool, config).await.unwrap();
+    db_pool.close().await;
 }
 
 pub struct TestDb {
     db_url: String,
     db_pool: Option<PgPool>,
+    config: Config,
 }
 
 impl TestDb {
@@ -160,18 +233,25 @@ impl TestDb {
         dotenv::dotenv().ok();
         pretty_env_logger::try_init().ok();
 
-        let db_url = db_url();
+        let (mut config, _) = Config::load(Vec::new()).unwrap();
+        let db_url = db_url(&config);
+        config.database_url = db_url.clone();
         create_db(&db_url).await;
-        run_migrations(&db_url).await;
+        run_migrations(&db_url, &config).await;
 
         let db_pool = PgPool::connect(&db_url).await.unwrap();
 
-        Self {db_url, db_pool: Some(db_pool),}
+        Self {db_url, db_pool: Some(db_pool), config}
     }
 
     pub fn db(&self) -> PgPool {
         self.db_pool.clone().unwrap()
     }
+
+    /// The configuration to run a server against this database with.
+    pub fn config(&self) -> Config {
+        self.config.clone()
+    }
 }
 
 impl Drop for TestDb {
@@ -186,7 +266,7 @@ pub(crate) async fn create_user_and_authenticate(server: &mut Server<State>, use
     let (json, status, _) = post("/users", 
         Some(CreateUserPayload {
             username: username.unwrap_or_else(|| "Geoff".to_string()),
-            password: "123456".to_string(),
+            password: "correct-horse-7".to_string(),
         })).send(server).await;
     assert_eq!(status, 201);
 
@@ -195,9 +275,43 @@ pub(crate) async fn create_user_and_authenticate(server: &mut Server<State>, use
         .data
 }
 
+/// The bodies of every mail sent to `address` so far.
+pub(crate) async fn mail_sent_to(address: &str) -> Vec<String> {
+    crate::mailer::FileMailer::new(crate::mailer::test_mail_dir())
+        .sent_to(address)
+        .await
+        .unwrap()
+}
 
+/// A plain event saying `content`, for tests to adjust with struct update syntax.
+pub(crate) fn new_event(content: &str) -> CreateEventPayload {
+    CreateEventPayload {
+        content: content.to_string(),
+        in_reply_to: None,
+        quote_of: None,
+        media_ids: vec![],
+    }
+}
 
+/// Posts `payload` as the owner of `token` and returns the new event's id.
+pub(crate) async fn create_event(payload: CreateEventPayload, token: &str, server: &Server<State>) -> String {
+    let (json, status, _) = post("/events", Some(payload))
+        .header("Authorization", format!("Bearer {}", token))
+        .send(server)
+        .await;
+    assert_eq!(status, 201);
 
+    json["data"]["id"].as_str().unwrap().to_string()
+}
 
+pub(crate) async fn post_event(content: &str, token: &str, server: &Server<State>) -> String {
+    create_event(new_event(content), token, server).await
+}
 
+pub(crate) async fn post_reply(content: &str, in_reply_to: &str, token: &str, server: &Server<State>) -> String {
+    create_event(CreateEventPayload { in_reply_to: Some(in_reply_to.parse().unwrap()), ..new_event(content) }, token, server).await
+}
 
+pub(crate) async fn post_quote(content: &str, quote_of: &str, token: &str, server: &Server<State>) -> String {
+    create_event(CreateEventPayload { quote_of: Some(quote_of.parse().unwrap()), ..new_event(content) }, token, server).await
+}
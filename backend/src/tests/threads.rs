@@ -0,0 +1,107 @@
+use crate::tests::test_utils::*;
+use serde_json::json;
+use assert_json_diff::assert_json_include;
+use crate::server;
+use shared::payloads::CreateEventPayload;
+use uuid::Uuid;
+
+#[async_std::test]
+async fn replying_to_an_event() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let token = create_user_and_authenticate(&mut server, None).await.token;
+
+    let root = post_event("root", &token, &server).await;
+    post_reply("reply", &root, &token, &server).await;
+
+    let (json, status, _) = get("/me/timeline")
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server)
+        .await;
+
+    assert_eq!(status, 200);
+    assert_json_include!(actual: json, expected: json!({
+        "data" : {"items": [
+            {"content" : "reply", "in_reply_to": root, "reply_count": 0},
+            {"content" : "root", "in_reply_to": null, "reply_count": 1},
+        ]}
+    }));
+}
+
+#[async_std::test]
+async fn replying_to_unknown_event() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let token = create_user_and_authenticate(&mut server, None).await.token;
+
+    let (json, status, _) = post("/events",
+        Some(CreateEventPayload { in_reply_to: Some(Uuid::new_v4()), ..new_event("reply") }))
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server).await;
+
+    assert_eq!(status, 404);
+    assert_json_include!(actual: json, expected: json!({
+        "error" : {
+            "message" : "Event does not exist"
+        }
+    }));
+}
+
+#[async_std::test]
+async fn getting_a_thread() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+
+    let root = post_event("root", &tim_token, &server).await;
+    let middle = post_reply("middle", &root, &jim_token, &server).await;
+    let first = post_reply("first", &middle, &tim_token, &server).await;
+    post_reply("nested", &first, &jim_token, &server).await;
+    post_reply("second", &middle, &jim_token, &server).await;
+    post_reply("sibling", &root, &tim_token, &server).await;
+
+    let (json, status, _) = get(&format!("/events/{}/thread", middle)).send(&mut server).await;
+
+    assert_eq!(status, 200);
+    assert_json_include!(actual: &json, expected: json!({
+        "data" : {
+            "ancestors": [
+                {"content": "root", "user": {"username": "tim"}},
+            ],
+            "event": {"content": "middle", "reply_count": 2},
+            "replies": [
+                {
+                    "event": {"content": "first"},
+                    "replies": [
+                        {"event": {"content": "nested"}, "replies": []},
+                    ],
+                },
+                {
+                    "event": {"content": "second"},
+                    "replies": [],
+                },
+            ],
+        }
+    }));
+    assert_eq!(json["data"]["ancestors"].as_array().unwrap().len(), 1);
+    assert_eq!(json["data"]["replies"].as_array().unwrap().len(), 2);
+}
+
+#[async_std::test]
+async fn getting_unknown_thread() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let (json, status, _) = get(&format!("/events/{}/thread", Uuid::new_v4())).send(&mut server).await;
+
+    assert_eq!(status, 404);
+    assert_json_include!(actual: json, expected: json!({
+        "error" : {
+            "message" : "Event does not exist"
+        }
+    }));
+}
@@ -1,14 +1,12 @@
 use crate::tests::test_utils::*;
-use crate::{State, Server};
 use serde_json::json;
 use assert_json_diff::{assert_json_include};
 use crate::server;
-use shared::payloads::CreateEventPayload;
 
 #[async_std::test]
 async fn sees_own_events() {
     let test_db = TestDb::new().await;
-    let mut server = server(test_db.db()).await;
+    let mut server = server(test_db.config(), test_db.db()).await;
 
     let token = create_user_and_authenticate(&mut server, None).await.token;
 
@@ -22,11 +20,11 @@ async fn sees_own_events() {
 
     assert_eq!(status, 200);
     assert_json_include!(actual: json, expected: json!({
-        "data" : [
+        "data" : {"items": [
             {"content" : "newest"},
             {"content" : "middle"},
             {"content" : "oldest"},
-        ]
+        ]}
     }));
 }
 
@@ -34,7 +32,7 @@ async fn sees_own_events() {
 #[async_std::test]
 async fn sees_events_from_following() {
     let test_db = TestDb::new().await;
-    let mut server = server(test_db.db()).await;
+    let mut server = server(test_db.config(), test_db.db()).await;
 
     let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
     let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
@@ -55,18 +53,18 @@ async fn sees_events_from_following() {
 
     assert_eq!(status, 200);
     assert_json_include!(actual: json, expected: json!({
-        "data" : [
+        "data" : {"items": [
             {"content" : "newest"},
             {"content" : "middle"},
             {"content" : "oldest"},
-        ]
+        ]}
     }));
 }
 
 #[async_std::test]
 async fn pagination() {
     let test_db = TestDb::new().await;
-    let mut server = server(test_db.db()).await;
+    let mut server = server(test_db.config(), test_db.db()).await;
 
     let token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
 
@@ -83,13 +81,13 @@ async fn pagination() {
 
     assert_eq!(status, 200);
     assert_json_include!(actual: &json, expected: json!({
-        "data" : [
+        "data" : {"items": [
             {"content" : "1"},
             {"content" : "2"},
-        ]
+        ]}
     }));
 
-    assert_eq!(json["data"].as_array().unwrap().len(), 2);
+    assert_eq!(json["data"]["items"].as_array().unwrap().len(), 2);
 
     let (json, status, _) = get("/me/timeline?page=2&page_size=2")
         .header("Authorization", format!("Bearer {}", token))
@@ -100,13 +98,13 @@ async fn pagination() {
     assert_json_include!(
         actual: &json,
         expected: json!({
-            "data": [
+            "data": {"items": [
                 { "content": "3" },
                 { "content": "4" },
-            ]
+            ]}
         })
     );
-    assert_eq!(json["data"].as_array().unwrap().len(), 2);
+    assert_eq!(json["data"]["items"].as_array().unwrap().len(), 2);
 
     let (json, status, _) = get("/me/timeline?page=3&page_size=2")
         .header("Authorization", format!("Bearer {}", token))
@@ -117,19 +115,107 @@ async fn pagination() {
     assert_json_include!(
         actual: &json,
         expected: json!({
-            "data": [
+            "data": {"items": [
                 { "content": "5" },
-            ]
+            ]}
         })
     );
-    assert_eq!(json["data"].as_array().unwrap().len(), 1);
+    assert_eq!(json["data"]["items"].as_array().unwrap().len(), 1);
 
 }
 
+#[async_std::test]
+async fn cursor_pagination() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+
+    post_event("5", &token, &mut server).await;
+    post_event("4", &token, &mut server).await;
+    post_event("3", &token, &mut server).await;
+    post_event("2", &token, &mut server).await;
+    post_event("1", &token, &mut server).await;
+
+    let (json, status, _) = get("/me/timeline?page_size=2")
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: &json, expected: json!({
+        "data": {"items": [
+            {"content": "1"},
+            {"content": "2"},
+        ]}
+    }));
+    let first_cursor = json["data"]["next_cursor"].as_str().unwrap().to_string();
+
+    // Posting mid-scroll must not shift the pages that follow.
+    post_event("0", &token, &mut server).await;
+
+    let (json, status, _) = get(&format!("/me/timeline?page_size=2&before={}", first_cursor))
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: &json, expected: json!({
+        "data": {"items": [
+            {"content": "3"},
+            {"content": "4"},
+        ]}
+    }));
+    assert_eq!(json["data"]["items"].as_array().unwrap().len(), 2);
+    let second_cursor = json["data"]["next_cursor"].as_str().unwrap().to_string();
+
+    let (json, status, _) = get(&format!("/me/timeline?page_size=2&before={}", second_cursor))
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: &json, expected: json!({
+        "data": {"items": [
+            {"content": "5"},
+        ], "next_cursor": null}
+    }));
+    assert_eq!(json["data"]["items"].as_array().unwrap().len(), 1);
+
+    let (json, status, _) = get(&format!("/me/timeline?page_size=5&after={}", first_cursor))
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: &json, expected: json!({
+        "data": {"items": [
+            {"content": "0"},
+            {"content": "1"},
+        ], "next_cursor": null}
+    }));
+    assert_eq!(json["data"]["items"].as_array().unwrap().len(), 2);
+}
+
+#[async_std::test]
+async fn invalid_cursor() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let token = create_user_and_authenticate(&mut server, None).await.token;
+
+    let (json, status, _) = get("/me/timeline?before=garbage")
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 400);
+    assert_json_include!(actual: json, expected: json!({
+        "error": {
+            "message": "Invalid pagination cursor"
+        }
+    }));
+}
+
 #[async_std::test]
 async fn max_page_size() {
     let test_db = TestDb::new().await;
-    let mut server = server(test_db.db()).await;
+    let mut server = server(test_db.config(), test_db.db()).await;
 
     let token = create_user_and_authenticate(&mut server, None).await.token;
 
@@ -143,23 +229,14 @@ async fn max_page_size() {
         .await;
 
     assert_eq!(status, 200);
-    assert_eq!(json["data"].as_array().unwrap().len(), 20);
+    assert_eq!(json["data"]["items"].as_array().unwrap().len(), 20);
 }
 
 
-async fn post_event(text: &str, token: &str, server: &Server<State>) {
-    post("/events", 
-        Some(CreateEventPayload {
-            content: text.to_string(),
-        }))
-    .header("Authorization", format!("Bearer {}", token))
-    .send(server).await;
-}
-
 #[async_std::test]
 async fn response_includes_user_who_posted_event() {
     let test_db = TestDb::new().await;
-    let mut server = server(test_db.db()).await;
+    let mut server = server(test_db.config(), test_db.db()).await;
 
     let token = create_user_and_authenticate(&mut server, Some("bob".to_string()))
         .await
@@ -180,14 +257,14 @@ async fn response_includes_user_who_posted_event() {
     assert_json_include!(
         actual: json,
         expected: json!({
-            "data": [
+            "data": {"items": [
                 {
                     "content": "hello",
                     "user": {
                         "username": "bob"
                     }
                 },
-            ]
+            ]}
         })
     );
 }
\ No newline at end of file
@@ -0,0 +1,145 @@
+use crate::tests::test_utils::*;
+use crate::server;
+use async_std::future::timeout;
+use async_std::io::prelude::BufReadExt;
+use serde_json::{json, Value};
+use assert_json_diff::assert_json_include;
+use std::time::Duration;
+use tide::http::Body;
+
+async fn next_event(body: &mut Body) -> Value {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        timeout(Duration::from_secs(5), body.read_line(&mut line))
+            .await
+            .expect("no event was streamed")
+            .unwrap();
+
+        if let Some(data) = line.strip_prefix("data:") {
+            return serde_json::from_str(data.trim()).unwrap();
+        }
+    }
+}
+
+#[async_std::test]
+async fn streams_events_from_following() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+    let bob_token = create_user_and_authenticate(&mut server, Some("bob".to_string())).await.token;
+
+    let (_, status, _) = post("/users/jim/follow", None::<()>)
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 201);
+
+    let mut res = get("/me/timeline/stream")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send_raw(&mut server)
+        .await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.content_type().unwrap().essence(), "text/event-stream");
+    let mut body = res.take_body();
+
+    post_event("not followed", &bob_token, &mut server).await;
+    post_event("hello", &jim_token, &mut server).await;
+
+    assert_json_include!(actual: next_event(&mut body).await, expected: json!({
+        "content": "hello",
+        "user": {"username": "jim"},
+    }));
+
+    post_event("my own", &tim_token, &mut server).await;
+
+    assert_json_include!(actual: next_event(&mut body).await, expected: json!({
+        "content": "my own",
+        "user": {"username": "tim"},
+    }));
+}
+
+#[async_std::test]
+async fn token_in_query() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let token = create_user_and_authenticate(&mut server, None).await.token;
+
+    let mut res = get(&format!("/me/timeline/stream?access_token={}", token))
+        .send_raw(&mut server)
+        .await;
+    assert_eq!(res.status(), 200);
+    let mut body = res.take_body();
+
+    post_event("hello", &token, &mut server).await;
+
+    assert_json_include!(actual: next_event(&mut body).await, expected: json!({
+        "content": "hello",
+    }));
+}
+
+#[async_std::test]
+async fn invalid_token() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let (json, status, _) = get("/me/timeline/stream?access_token=garbage")
+        .send(&mut server)
+        .await;
+
+    assert_eq!(status, 401);
+    assert_json_include!(actual: json, expected: json!({
+        "error": {
+            "message": "Invalid auth token"
+        }
+    }));
+}
+
+#[async_std::test]
+async fn streams_reposts_and_leaves_out_muted_users() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+    let bob_token = create_user_and_authenticate(&mut server, Some("bob".to_string())).await.token;
+    let amy_token = create_user_and_authenticate(&mut server, Some("amy".to_string())).await.token;
+
+    for username in &["jim", "bob"] {
+        let (_, status, _) = post(&format!("/users/{}/follow", username), None::<()>)
+            .header("Authorization", format!("Bearer {}", tim_token))
+            .send(&mut server)
+            .await;
+        assert_eq!(status, 201);
+    }
+    let (_, status, _) = post("/users/bob/mute", None::<()>)
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 201);
+
+    let event_id = post_event("from amy", &amy_token, &mut server).await;
+
+    let mut res = get("/me/timeline/stream")
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send_raw(&mut server)
+        .await;
+    assert_eq!(res.status(), 200);
+    let mut body = res.take_body();
+
+    post_event("muted", &bob_token, &mut server).await;
+    let (_, status, _) = post(&format!("/events/{}/repost", event_id), None::<()>)
+        .header("Authorization", format!("Bearer {}", jim_token))
+        .send(&mut server)
+        .await;
+    assert_eq!(status, 201);
+
+    assert_json_include!(actual: next_event(&mut body).await, expected: json!({
+        "content": "from amy",
+        "user": {"username": "amy"},
+        "reposted_by": {"username": "jim"},
+    }));
+}
@@ -8,7 +8,7 @@ use crate::server;
 #[async_std::test]
 async fn create_user_and_login() {
     let test_db = TestDb::new().await;
-    let mut server = server(test_db.db()).await;
+    let mut server = server(test_db.config(), test_db.db()).await;
 
     let token = create_user_and_authenticate(&mut server, None).await.token;
 
@@ -23,40 +23,77 @@ async fn create_user_and_login() {
     }));
 
 
-    let (json, status, _) = post("/users/Geoff/session", Some(LoginPayload { password : "123456".to_string()})).send(&mut server).await;
+    let (json, status, _) = post("/users/Geoff/session", Some(LoginPayload { password : "correct-horse-7".to_string(), device: None })).send(&mut server).await;
     assert_eq!(status, 201);
-    assert_json_include!(actual: json, expected: json!({
-        "data" : {
-            "token" : token
-        }
-    }));
+    let new_token = json["data"]["token"].as_str().unwrap();
+    assert_ne!(new_token, token);
+
+    let (_, status, _) = get("/me")
+        .header("Authorization", format!("Bearer {}", new_token))
+        .send(&mut server).await;
+    assert_eq!(status, 200);
 }
 
 #[async_std::test]
 async fn duplicate_username_not_allowed() {
     let test_db = TestDb::new().await;
-    let mut server = server(test_db.db()).await;
+    let mut server = server(test_db.config(), test_db.db()).await;
 
     let username = "Geoff".to_string();
 
     let (_, status, _) = post("/users", 
         Some(CreateUserPayload {
             username: username.clone(),
-            password: "123456".to_string(),
+            password: "correct-horse-7".to_string(),
         })).send(&mut server).await;
     assert_eq!(status, 201);
 
 
+    // Usernames are unique regardless of case.
     let (json, status, _) = post("/users", 
         Some(CreateUserPayload {
-            username,
-            password: "654321".to_string(),
+            username: username.to_lowercase(),
+            password: "battery-staple-9".to_string(),
         })).send(&mut server).await;
-    assert_eq!(status, 409);
+    assert_eq!(status, 422);
     assert_eq!(json, json!({
         "error" : {
-            "status_code" : "409",
-            "message" : "Submitted username already taken"
+            "status_code" : "422",
+            "code" : "validation_failed",
+            "message" : "Validation failed",
+            "fields" : [
+                {"field": "username", "message": "is already taken"}
+            ]
+        }
+    }));
+}
+
+#[async_std::test]
+async fn invalid_signups_list_every_problem() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let (json, status, _) = post("/users", 
+        Some(CreateUserPayload {
+            username: "tim/events".to_string(),
+            password: "12345".to_string(),
+        })).send(&mut server).await;
+    assert_eq!(status, 422);
+    assert_json_include!(actual: json, expected: json!({
+        "error" : {
+            "fields" : [
+                {"field": "username", "message": "can only contain letters, numbers and underscores"},
+                {"field": "password", "message": "must be at least 8 characters"}
+            ]
         }
     }));
+
+    for username in &["", "search", "Admin"] {
+        let (_, status, _) = post("/users", 
+            Some(CreateUserPayload {
+                username: username.to_string(),
+                password: "correct-horse-7".to_string(),
+            })).send(&mut server).await;
+        assert_eq!(status, 422);
+    }
 }
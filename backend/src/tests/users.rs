@@ -2,11 +2,12 @@ use crate::tests::test_utils::*;
 use crate::server;
 use assert_json_diff::assert_json_include;
 use serde_json::json;
+use shared::payloads::{CreateEventPayload, UpdateMePayload};
 
 #[async_std::test]
 async fn get_other_profile() {
     let test_db = TestDb::new().await;
-    let mut server = server(test_db.db()).await;
+    let mut server = server(test_db.config(), test_db.db()).await;
 
     let username = "tim";
     create_user_and_authenticate(&mut server, Some(username.to_string())).await.token;
@@ -18,13 +19,18 @@ async fn get_other_profile() {
         "data" : {
             "username" : "tim"
         }
-    }))
+    }));
+
+    // Usernames are unique regardless of case, so any case finds them.
+    let (json, status, _) = get("/users/TIM").send(&mut server).await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: json, expected: json!({"data": {"username": "tim"}}));
 }
 
 #[async_std::test]
 async fn get_nonexistent_profile() {
     let test_db = TestDb::new().await;
-    let mut server = server(test_db.db()).await;
+    let mut server = server(test_db.config(), test_db.db()).await;
 
     let (json, status, _) = get(&format!("/users/jim")).send(&mut server).await;
     assert_eq!(status, 404);
@@ -39,4 +45,149 @@ async fn get_nonexistent_profile() {
         })
     );
 
-}
\ No newline at end of file
+}
+
+#[async_std::test]
+async fn profile_counts() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    create_user_and_authenticate(&mut server, Some("jim".to_string())).await;
+
+    let (_, status, _) = post("/users/jim/follow", None::<()>)
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 201);
+
+    let (_, status, _) = post("/events", Some(CreateEventPayload {
+            content: "hello".to_string(),
+            in_reply_to: None,
+            quote_of: None,
+            media_ids: vec![],
+        }))
+        .header("Authorization", format!("Bearer {}", tim_token))
+        .send(&mut server).await;
+    assert_eq!(status, 201);
+
+    let (json, status, _) = get("/users/tim").send(&mut server).await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: json, expected: json!({"data": {
+        "username": "tim",
+        "display_name": null,
+        "follower_count": 0,
+        "following_count": 1,
+        "event_count": 1,
+    }}));
+
+    let (json, _, _) = get("/users/jim").send(&mut server).await;
+    assert_json_include!(actual: json, expected: json!({"data": {
+        "follower_count": 1,
+        "following_count": 0,
+        "event_count": 0,
+    }}));
+}
+
+#[async_std::test]
+async fn updating_own_profile() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+
+    let (json, status, _) = patch("/me", Some(UpdateMePayload {
+            display_name: Some("Tim".to_string()),
+            bio: Some("Posting things".to_string()),
+            avatar_url: Some("https://example.com/tim.png".to_string()),
+            ..Default::default()
+        }))
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server).await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: json, expected: json!({"data": {
+        "username": "tim",
+        "display_name": "Tim",
+        "bio": "Posting things",
+        "avatar_url": "https://example.com/tim.png",
+    }}));
+
+    // Fields left out stay as they were, empty ones are cleared.
+    let (json, status, _) = patch("/me", Some(UpdateMePayload {
+            bio: Some("".to_string()),
+            ..Default::default()
+        }))
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server).await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: json, expected: json!({"data": {
+        "display_name": "Tim",
+        "bio": null,
+    }}));
+
+    let (json, _, _) = get("/users/tim").send(&mut server).await;
+    assert_json_include!(actual: json, expected: json!({"data": {"display_name": "Tim"}}));
+}
+
+#[async_std::test]
+async fn invalid_profile_fields() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+
+    let (json, status, _) = patch("/me", Some(UpdateMePayload {
+            bio: Some("a".repeat(161)),
+            ..Default::default()
+        }))
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server).await;
+    assert_eq!(status, 422);
+    assert_json_include!(actual: json, expected: json!({"error": {"message": "bio too long"}}));
+
+    let (_, status, _) = patch("/me", Some(UpdateMePayload {
+            avatar_url: Some("javascript:alert(1)".to_string()),
+            ..Default::default()
+        }))
+        .header("Authorization", format!("Bearer {}", token))
+        .send(&mut server).await;
+    assert_eq!(status, 422);
+}
+
+#[async_std::test]
+async fn listing_a_users_events() {
+    let test_db = TestDb::new().await;
+    let mut server = server(test_db.config(), test_db.db()).await;
+
+    let tim_token = create_user_and_authenticate(&mut server, Some("tim".to_string())).await.token;
+    let jim_token = create_user_and_authenticate(&mut server, Some("jim".to_string())).await.token;
+
+    for (content, token) in &[("one", &tim_token), ("not tim's", &jim_token), ("two", &tim_token), ("three", &tim_token)] {
+        let (_, status, _) = post("/events", Some(CreateEventPayload {
+                content: content.to_string(),
+                in_reply_to: None,
+                quote_of: None,
+                media_ids: vec![],
+            }))
+            .header("Authorization", format!("Bearer {}", token))
+            .send(&mut server).await;
+        assert_eq!(status, 201);
+    }
+
+    let (json, status, _) = get("/users/tim/events?page_size=2").send(&mut server).await;
+    assert_eq!(status, 200);
+    assert_json_include!(actual: &json, expected: json!({"data": {"items": [
+        {"content": "three"},
+        {"content": "two"},
+    ]}}));
+    let cursor = json["data"]["next_cursor"].as_str().unwrap().to_string();
+
+    let (json, status, _) = get(&format!("/users/tim/events?page_size=2&before={}", cursor)).send(&mut server).await;
+    assert_eq!(status, 200);
+    assert_eq!(json["data"]["next_cursor"], json!(null));
+    let items = json["data"]["items"].as_array().unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0]["content"], "one");
+
+    let (_, status, _) = get("/users/nobody/events").send(&mut server).await;
+    assert_eq!(status, 404);
+}
@@ -1,21 +1,23 @@
-use shared::payloads::{CreateUserPayload, CreateEventPayload, LoginPayload};
-use shared::responses::ApiResponse;
+use shared::payloads::{CreateUserPayload, CreateEventPayload, EditEventPayload, LoginPayload, MarkNotificationsReadPayload};
+use shared::errors::ErrorResponse;
+use shared::responses::{ApiResponse, EventResponse};
 use shared::Url as _;
 use shared::*;
 // use seed::fetch::fetch;
 // use seed::log;
 use crate::{Msg, Error};
 use seed::{prelude::*};
+use uuid::Uuid;
 
 
-const API_URL: &'static str = "http://127.0.0.1:8080";
+pub const API_URL: &'static str = "http://127.0.0.1:8080";
 
 pub async fn create_user(username: String, password: String) -> Msg {
     fetch::<CreateUser>(None, CreateUserUrl, CreateUserPayload { username, password }, |resp| Msg::CreateUserEndpointResponded(resp.token)).await
 }
 
 pub async fn login(username: String, password: String) -> Msg {
-    fetch::<Login>(None, LoginUrl{ username }, LoginPayload{ password }, |resp| Msg::LoginEndpointResponded(resp.token)).await
+    fetch::<Login>(None, LoginUrl{ username }, LoginPayload{ password, device: None }, |resp| Msg::LoginEndpointResponded(resp.token)).await
 }
 
 pub async fn reload_current_user(auth_token: String) -> Msg {
@@ -31,24 +33,181 @@ pub async fn load_user(username: String, auth_token: Option<String>) -> Msg {
     ).await
 }
 
+/// One page of the user's events; `before` is the previous page's `next_cursor`.
+pub async fn load_user_events(username: String, before: Option<String>, auth_token: Option<String>) -> Msg {
+    fetch::<UserEvents>(
+        auth_token,
+        UserEventsUrl { username, before },
+        NoPayload,
+        Msg::LoadUserEventsEndpointResponded,
+    ).await
+}
+
 pub async fn load_timeline(auth_token: Option<String>) -> Msg {
     fetch::<Timeline>(
         auth_token,
         TimelineUrl,
         NoPayload,
-        Msg::LoadTimelineEndpointResponded,
+        |page| Msg::LoadTimelineEndpointResponded(page.items),
+    ).await
+}
+
+/// A live subscription to `/me/timeline/stream`, closed when dropped.
+pub struct TimelineStream {
+    source: web_sys::EventSource,
+    _on_event: Closure<dyn FnMut(web_sys::MessageEvent)>,
+}
+
+impl std::fmt::Debug for TimelineStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("TimelineStream").field("url", &self.source.url()).finish()
+    }
+}
+
+impl Drop for TimelineStream {
+    fn drop(&mut self) {
+        self.source.close();
+    }
+}
+
+/// Subscribes to events posted to the timeline, sending a `Msg::TimelineEventStreamed` for each.
+/// `EventSource` can't send headers, so the token goes in the query string.
+pub fn open_timeline_stream(auth_token: &str, orders: &mut impl Orders<Msg>) -> Option<TimelineStream> {
+    let url = format!("{}/me/timeline/stream?access_token={}", API_URL, auth_token);
+    let source = web_sys::EventSource::new(&url).ok()?;
+
+    let send_msg = orders.msg_sender();
+    let on_event = Closure::wrap(Box::new(move |message: web_sys::MessageEvent| {
+        let event = message
+            .data()
+            .as_string()
+            .and_then(|data| serde_json::from_str::<EventResponse>(&data).ok());
+        send_msg(event.map(Msg::TimelineEventStreamed));
+    }) as Box<dyn FnMut(web_sys::MessageEvent)>);
+    source
+        .add_event_listener_with_callback("event", on_event.as_ref().unchecked_ref())
+        .ok()?;
+
+    Some(TimelineStream { source, _on_event: on_event })
+}
+
+pub async fn load_tag_events(tag: String, auth_token: Option<String>) -> Msg {
+    fetch::<TagEvents>(
+        auth_token,
+        TagEventsUrl { tag },
+        NoPayload,
+        |page| Msg::LoadTagEventsEndpointResponded(page.items),
+    ).await
+}
+
+pub async fn search(q: String, page: Option<usize>, auth_token: Option<String>) -> Msg {
+    fetch::<Search>(
+        auth_token,
+        SearchUrl { q, page },
+        NoPayload,
+        Msg::LoadSearchEndpointResponded,
+    ).await
+}
+
+pub async fn load_mentions(auth_token: Option<String>) -> Msg {
+    fetch::<Mentions>(
+        auth_token,
+        MentionsUrl,
+        NoPayload,
+        |page| Msg::LoadMentionsEndpointResponded(page.items),
+    ).await
+}
+
+pub async fn load_notifications(auth_token: Option<String>) -> Msg {
+    fetch::<Notifications>(
+        auth_token,
+        NotificationsUrl,
+        NoPayload,
+        |page| Msg::LoadNotificationsEndpointResponded(page.items),
+    ).await
+}
+
+pub async fn mark_notifications_read(up_to: Uuid, auth_token: Option<String>) -> Msg {
+    fetch::<MarkNotificationsRead>(
+        auth_token,
+        MarkNotificationsReadUrl,
+        MarkNotificationsReadPayload { up_to },
+        Msg::MarkNotificationsReadEndpointResponded,
     ).await
 }
 
-pub async fn post_event(auth_token: Option<String>, content: String) -> Msg {
+pub async fn post_event(auth_token: Option<String>, content: String, in_reply_to: Option<Uuid>, quote_of: Option<Uuid>) -> Msg {
     fetch::<PostEvent>(
         auth_token,
         PostEventUrl,
-        CreateEventPayload {content},
+        CreateEventPayload { content, in_reply_to, quote_of, media_ids: vec![] },
         Msg::PostEventEndpointResponded,
     ).await
 }
 
+pub async fn edit_event(auth_token: Option<String>, id: Uuid, content: String) -> Msg {
+    fetch::<EditEvent>(
+        auth_token,
+        EventUrl { id },
+        EditEventPayload { content },
+        Msg::EditEventEndpointResponded,
+    ).await
+}
+
+pub async fn delete_event(auth_token: Option<String>, id: Uuid) -> Msg {
+    fetch::<DeleteEvent>(
+        auth_token,
+        EventUrl { id },
+        NoPayload,
+        |_| Msg::DeleteEventEndpointResponded,
+    ).await
+}
+
+pub async fn load_thread(id: Uuid, auth_token: Option<String>) -> Msg {
+    fetch::<GetThread>(
+        auth_token,
+        GetThreadUrl { id },
+        NoPayload,
+        Msg::LoadThreadEndpointResponded,
+    ).await
+}
+
+pub async fn like_event(id: Uuid, auth_token: Option<String>) -> Msg {
+    fetch::<LikeEvent>(
+        auth_token,
+        LikeEventUrl { id },
+        NoPayload,
+        Msg::LikeEndpointResponded,
+    ).await
+}
+
+pub async fn unlike_event(id: Uuid, auth_token: Option<String>) -> Msg {
+    fetch::<UnlikeEvent>(
+        auth_token,
+        LikeEventUrl { id },
+        NoPayload,
+        Msg::LikeEndpointResponded,
+    ).await
+}
+
+pub async fn repost(id: Uuid, auth_token: Option<String>) -> Msg {
+    fetch::<Repost>(
+        auth_token,
+        RepostUrl { id },
+        NoPayload,
+        Msg::RepostEndpointResponded,
+    ).await
+}
+
+pub async fn unrepost(id: Uuid, auth_token: Option<String>) -> Msg {
+    fetch::<Unrepost>(
+        auth_token,
+        RepostUrl { id },
+        NoPayload,
+        Msg::RepostEndpointResponded,
+    ).await
+}
+
 pub async fn fetch<E>(
     auth_token: Option<String>,
     url: E::Url,
@@ -71,6 +230,13 @@ where
 
         let resp = seed::browser::fetch::fetch(req).await?;
 
+        // Failed requests say what went wrong in the body, which `check_status` would discard.
+        if !resp.status().is_ok() {
+            if let Ok(body) = resp.json::<ErrorResponse>().await {
+                return seed::browser::fetch::Result::Ok(Msg::Error(Error::Api(body.error)));
+            }
+        }
+
         let value = resp
             .check_status()?
             .json::<ApiResponse<E::Response>>()
@@ -125,4 +291,6 @@ macro_rules! impl_set_request_payload {
 impl_set_request_payload!(CreateUserPayload);
 impl_set_request_payload!(LoginPayload);
 impl_set_request_payload!(CreateEventPayload);
+impl_set_request_payload!(EditEventPayload);
+impl_set_request_payload!(MarkNotificationsReadPayload);
 
@@ -1,9 +1,13 @@
 // use seed::virtual_dom::el_ref::el_ref;
 use seed::{prelude::*, *};
-use shared::responses::{PostEventResponse, EventResponse, UserResponse};
+use shared::responses::{PostEventResponse, EventResponse, UserResponse, ProfileResponse, PaginatedResponse, MeResponse, NotificationResponse, UnreadCountResponse, ThreadResponse, ThreadReplyResponse, LikeResponse, RepostResponse, SearchResponse};
+use shared::payloads::CreateUserPayload;
+use shared::errors::{ApiError, ErrorBody};
+use shared::validation::{Validate, ValidationErrors};
 use web_sys::HtmlInputElement;
 use flash::Flash;
 use std::fmt;
+use uuid::Uuid;
 // use crate::view::view;
 
 mod view;
@@ -18,11 +22,14 @@ fn init(url: Url, orders: &mut impl Orders<Msg>) -> Model {
     let mut model = Model {
         auth_token: storage::get_auth_token(),
         current_user: None,
+        unread_count: 0,
         page: Page::RootLoggedOut,
         login_form: Default::default(),
         sign_up_form: Default::default(),
         post_event_form: Default::default(),
+        search_form: Default::default(),
         flash: Default::default(),
+        timeline_stream: None,
     };
 
     let page = Page::from(url, &model);
@@ -41,10 +48,14 @@ pub struct Model {
     login_form: LoginForm,
     sign_up_form: SignUpForm,
     post_event_form: PostEventForm,
+    search_form: SearchForm,
     auth_token: Option<String>,
     current_user: Option<UserResponse>,
+    unread_count: i64,
     page: Page,
     flash: Flash,
+    /// Open while the timeline is shown, so new events can be prepended as they're posted.
+    timeline_stream: Option<api::TimelineStream>,
 }
 
 impl Model {
@@ -56,12 +67,19 @@ impl Model {
     fn remove_auth_token(&mut self) {
         self.auth_token = None;
         self.current_user = None;
+        self.timeline_stream = None;
         storage::remove_auth_token();
     }
 
     fn logged_in(&self) -> bool {
         self.auth_token.is_some()
     }
+
+    fn close_timeline_stream_unless_shown(&mut self) {
+        if !matches!(self.page, Page::Timeline(_)) {
+            self.timeline_stream = None;
+        }
+    }
 }
 
 #[derive(Debug, Default)]
@@ -74,6 +92,7 @@ pub struct LoginForm {
 pub struct SignUpForm {
     username_input: ElRef<HtmlInputElement>,
     password_input: ElRef<HtmlInputElement>,
+    errors: ValidationErrors,
 }
 
 #[derive(Debug, Default)]
@@ -81,6 +100,11 @@ pub struct PostEventForm {
     text_input: ElRef<HtmlInputElement>,
 }
 
+#[derive(Debug, Default)]
+pub struct SearchForm {
+    query_input: ElRef<HtmlInputElement>,
+}
+
 #[derive(Debug)]
 pub enum PageData<T> {
     Loaded(T),
@@ -93,9 +117,17 @@ pub enum Page {
     Timeline(PageData<Vec<EventResponse>>),
     Login,
     SignUp,
-    UserProfile(String),
+    UserProfile(String, PageData<ProfileResponse>, PageData<PaginatedResponse<EventResponse>>),
     SignedIn,
     PostEvent,
+    Reply(Uuid),
+    Quote(Uuid),
+    EditEvent(Uuid),
+    Thread(Uuid, PageData<ThreadResponse>),
+    Tag(String, PageData<Vec<EventResponse>>),
+    Mentions(PageData<Vec<EventResponse>>),
+    Notifications(PageData<Vec<NotificationResponse>>),
+    Search(String, PageData<SearchResponse>),
 }
 
 impl Page {
@@ -109,17 +141,41 @@ impl Page {
 
         url.go_and_push();
         model.page = self;
+        model.close_timeline_stream_unless_shown();
     }
 
     fn load_data(&self, orders: &mut impl Orders<Msg>) {
         match self {
-            Page::UserProfile(username) => {
+            Page::UserProfile(username, _, _) => {
                 orders.send_msg(Msg::LoadUserProfile(username.to_string()));
             }
             Page::Timeline(_) => {
                 orders.send_msg(Msg::LoadTimeline);
             }
-            Page::RootLoggedOut | Page::Login | Page::SignUp | Page::SignedIn | Page::PostEvent => {}
+            Page::Thread(id, _) => {
+                orders.send_msg(Msg::LoadThread(*id));
+            }
+            Page::Tag(tag, _) => {
+                orders.send_msg(Msg::LoadTagEvents(tag.to_string()));
+            }
+            Page::Mentions(_) => {
+                orders.send_msg(Msg::LoadMentions);
+            }
+            Page::Notifications(_) => {
+                orders.send_msg(Msg::LoadNotifications);
+            }
+            Page::Search(q, _) => {
+                orders.send_msg(Msg::LoadSearch(q.to_string()));
+            }
+            Page::RootLoggedOut | Page::Login | Page::SignUp | Page::SignedIn | Page::PostEvent | Page::Reply(_) | Page::Quote(_) | Page::EditEvent(_) => {}
+        }
+    }
+
+    fn home(model: &Model) -> Self {
+        if model.logged_in() {
+            Page::Timeline(PageData::NotLoaded)
+        } else {
+            Page::RootLoggedOut
         }
     }
 
@@ -127,21 +183,90 @@ impl Page {
         match url.remaining_path_parts().as_slice() {
             ["signup"] => Page::SignUp,
             ["login"] => Page::Login,
-            ["users", username] => Page::UserProfile(username.to_string()),
+            ["users", username] => Page::UserProfile(username.to_string(), PageData::NotLoaded, PageData::NotLoaded),
             ["signedin"] => Page::SignedIn,
-            [] => {
-                if model.logged_in() {
-                    Page::Timeline(PageData::NotLoaded)
-                } else {
-                    Page::RootLoggedOut
-                }
-            }
+            [] => Page::home(model),
             ["events", "new"] => Page::PostEvent,
+            // Links to events that can't exist, such as a mangled id, lead home.
+            ["events", id] => match id.parse() {
+                Ok(id) => Page::Thread(id, PageData::NotLoaded),
+                Err(_) => Page::home(model),
+            },
+            ["events", id, "reply"] => match id.parse() {
+                Ok(id) => Page::Reply(id),
+                Err(_) => Page::home(model),
+            },
+            ["events", id, "quote"] => match id.parse() {
+                Ok(id) => Page::Quote(id),
+                Err(_) => Page::home(model),
+            },
+            ["events", id, "edit"] => match id.parse() {
+                Ok(id) => Page::EditEvent(id),
+                Err(_) => Page::home(model),
+            },
+            ["mentions"] => Page::Mentions(PageData::NotLoaded),
+            ["notifications"] => Page::Notifications(PageData::NotLoaded),
+            ["tags", tag] => Page::Tag(tag.to_string(), PageData::NotLoaded),
+            ["search"] => {
+                let q = url.search().get("q").and_then(|values| values.first()).cloned().unwrap_or_default();
+                Page::Search(q, PageData::NotLoaded)
+            }
             _ => todo!("Unknown URL: {}", url),
         }
     }
 }
 
+impl Page {
+    fn for_each_event(&mut self, f: &mut dyn FnMut(&mut EventResponse)) {
+        match self {
+            Page::Timeline(PageData::Loaded(events))
+            | Page::Tag(_, PageData::Loaded(events))
+            | Page::Mentions(PageData::Loaded(events)) => {
+                for event in events {
+                    with_quoted(event, f);
+                }
+            }
+            Page::UserProfile(_, _, PageData::Loaded(page)) => {
+                for event in &mut page.items {
+                    with_quoted(event, f);
+                }
+            }
+            Page::Search(_, PageData::Loaded(results)) => {
+                for event in &mut results.events {
+                    with_quoted(event, f);
+                }
+            }
+            Page::Notifications(PageData::Loaded(notifications)) => {
+                for event in notifications.iter_mut().filter_map(|notification| notification.event.as_mut()) {
+                    with_quoted(event, f);
+                }
+            }
+            Page::Thread(_, PageData::Loaded(thread)) => {
+                for event in &mut thread.ancestors {
+                    with_quoted(event, f);
+                }
+                with_quoted(&mut thread.event, f);
+                for_each_reply(&mut thread.replies, f);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn with_quoted(event: &mut EventResponse, f: &mut dyn FnMut(&mut EventResponse)) {
+    f(event);
+    if let Some(quoted) = &mut event.quoted_event {
+        f(quoted);
+    }
+}
+
+fn for_each_reply(replies: &mut [ThreadReplyResponse], f: &mut dyn FnMut(&mut EventResponse)) {
+    for reply in replies {
+        with_quoted(&mut reply.event, f);
+        for_each_reply(&mut reply.replies, f);
+    }
+}
+
 impl fmt::Display for Page {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -149,9 +274,17 @@ impl fmt::Display for Page {
             Page::Timeline(_) => write!(f, "/"),
             Page::Login => write!(f, "/login"),
             Page::SignUp => write!(f, "/signup"),
-            Page::UserProfile(username) => write!(f, "/users/{}", username.clone()),
+            Page::UserProfile(username, _, _) => write!(f, "/users/{}", username.clone()),
             Page::SignedIn => write!(f, "/signedin"),
             Page::PostEvent => write!(f, "/events/new"),
+            Page::Reply(id) => write!(f, "/events/{}/reply", id),
+            Page::Quote(id) => write!(f, "/events/{}/quote", id),
+            Page::EditEvent(id) => write!(f, "/events/{}/edit", id),
+            Page::Thread(id, _) => write!(f, "/events/{}", id),
+            Page::Tag(tag, _) => write!(f, "/tags/{}", tag),
+            Page::Mentions(_) => write!(f, "/mentions"),
+            Page::Notifications(_) => write!(f, "/notifications"),
+            Page::Search(q, _) => write!(f, "{}", shared::Url::url(&shared::SearchUrl { q: q.clone(), page: None })),
         }
     }
 }
@@ -162,17 +295,40 @@ pub enum Msg {
     SignUpFormSubmitted,
     CreateUserEndpointResponded(String),
     LoginEndpointResponded(String),
-    MeLoaded(UserResponse),
+    MeLoaded(MeResponse),
     UrlChanged(subs::UrlChanged),
     LoadUserProfile(String),
-    GetUserLoaded(UserResponse),
+    GetUserLoaded(ProfileResponse),
+    LoadMoreUserEvents(String),
+    LoadUserEventsEndpointResponded(PaginatedResponse<EventResponse>),
     EventPosted(EventResponse),
     Error(Error),
     ClearFlash,
     LoadTimelineEndpointResponded(Vec<EventResponse>),
     LoadTimeline,
+    TimelineEventStreamed(EventResponse),
     PostEventFormSubmitted,
     PostEventEndpointResponded(PostEventResponse),
+    LoadThread(Uuid),
+    LoadThreadEndpointResponded(ThreadResponse),
+    LoadTagEvents(String),
+    LoadTagEventsEndpointResponded(Vec<EventResponse>),
+    LoadMentions,
+    LoadMentionsEndpointResponded(Vec<EventResponse>),
+    LoadNotifications,
+    LoadNotificationsEndpointResponded(Vec<NotificationResponse>),
+    SearchFormSubmitted,
+    LoadSearch(String),
+    LoadMoreSearchResults(usize),
+    LoadSearchEndpointResponded(SearchResponse),
+    MarkNotificationsReadEndpointResponded(UnreadCountResponse),
+    LikeClicked(Uuid, bool),
+    LikeEndpointResponded(LikeResponse),
+    RepostClicked(Uuid, bool),
+    RepostEndpointResponded(RepostResponse),
+    EditEventEndpointResponded(EventResponse),
+    DeleteEventClicked(Uuid),
+    DeleteEventEndpointResponded,
     Logout,
     Noop
 }
@@ -180,6 +336,8 @@ pub enum Msg {
 #[derive(Debug)]
 pub enum Error {
     RequestFailed(FetchError),
+    /// The API turned the request down, saying why.
+    Api(ErrorBody),
 }
 
 pub fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
@@ -194,11 +352,16 @@ pub fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
             orders.perform_cmd(api::login(username, password));
         }
         Msg::SignUpFormSubmitted => {
-            let form = &model.sign_up_form;
+            let form = &mut model.sign_up_form;
             let username = form.username_input.get().unwrap().value();
             let password = form.password_input.get().unwrap().value();
 
-            orders.perform_cmd(api::create_user(username, password));
+            // Point out what's wrong next to the inputs rather than waiting on the server.
+            let payload = CreateUserPayload { username, password };
+            form.errors = payload.validate().err().unwrap_or_default();
+            if form.errors.is_empty() {
+                orders.perform_cmd(api::create_user(payload.username, payload.password));
+            }
         }
         Msg::CreateUserEndpointResponded(token) => {
             model.set_auth_token(&token);
@@ -210,25 +373,67 @@ pub fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
             orders.perform_cmd(api::reload_current_user(token.to_string()));
             Page::SignedIn.go(model, orders);
         }
-        Msg::MeLoaded(user) => {
-            model.current_user = Some(user);
+        Msg::MeLoaded(me) => {
+            model.current_user = Some(me.profile.user);
+            model.unread_count = me.unread_count;
         }
         Msg::UrlChanged(subs::UrlChanged(url)) => {
             log!("url changed to", url.to_string());
             let page = Page::from(url, model);
             page.load_data(orders);
             model.page = page;
+            model.close_timeline_stream_unless_shown();
         }
         Msg::LoadUserProfile(username) => {
-            orders.perform_cmd(api::load_user(username, model.auth_token.clone()));
+            orders.perform_cmd(api::load_user(username.clone(), model.auth_token.clone()));
+            orders.perform_cmd(api::load_user_events(username, None, model.auth_token.clone()));
+        }
+        Msg::GetUserLoaded(profile) => {
+            if let Page::UserProfile(username, data, _) = &mut model.page {
+                if *username == profile.user.username {
+                    *data = PageData::Loaded(profile)
+                }
+            }
+        }
+        Msg::LoadMoreUserEvents(cursor) => {
+            if let Page::UserProfile(username, _, _) = &model.page {
+                orders.perform_cmd(api::load_user_events(username.clone(), Some(cursor), model.auth_token.clone()));
+            }
+        }
+        Msg::LoadUserEventsEndpointResponded(page) => {
+            if let Page::UserProfile(_, _, data) = &mut model.page {
+                match data {
+                    PageData::Loaded(loaded) => {
+                        loaded.items.extend(page.items);
+                        loaded.next_cursor = page.next_cursor;
+                    }
+                    PageData::NotLoaded => *data = PageData::Loaded(page),
+                }
+            }
         }
-        Msg::GetUserLoaded(user) => log!("user loaded:", user),
         Msg::EventPosted(event) => log!(event),
         Msg::Error(err) => match err {
             Error::RequestFailed(err) => {
                 log!("request failed", err);
                 model.flash.set_error("Request Failed", orders);
             }
+            Error::Api(body) => {
+                log!("api error", body);
+                match body.error {
+                    ApiError::InvalidToken | ApiError::TokenExpired => {
+                        model.remove_auth_token();
+                        Page::Login.go(model, orders);
+                        model.flash.set_error("Your session has ended, please log in again", orders);
+                    }
+                    ApiError::ValidationFailed { fields } => {
+                        model.sign_up_form.errors = ValidationErrors { errors: fields };
+                    }
+                    ApiError::UsernameTaken => {
+                        model.sign_up_form.errors.add("username", "is already taken");
+                    }
+                    _ => model.flash.set_error(&body.message, orders),
+                }
+            }
         }
         Msg::ClearFlash => {
             model.flash.clear()
@@ -244,15 +449,145 @@ pub fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
         }
         Msg::LoadTimeline => {
             orders.perform_cmd(api::load_timeline(model.auth_token.clone()));
+            model.timeline_stream = model
+                .auth_token
+                .as_deref()
+                .and_then(|token| api::open_timeline_stream(token, orders));
+        }
+        Msg::TimelineEventStreamed(event) => {
+            if let Page::Timeline(PageData::Loaded(events)) = &mut model.page {
+                if events.iter().all(|shown| shown.id != event.id) {
+                    events.insert(0, event);
+                }
+            }
         }
         Msg::PostEventFormSubmitted => {
             let text = model.post_event_form.text_input.get().unwrap().value();
-            orders.perform_cmd(api::post_event(model.auth_token.clone(), text));
+            if let Page::EditEvent(id) = &model.page {
+                orders.perform_cmd(api::edit_event(model.auth_token.clone(), *id, text));
+                return;
+            }
+            let (in_reply_to, quote_of) = match &model.page {
+                Page::Reply(id) => (Some(*id), None),
+                Page::Quote(id) => (None, Some(*id)),
+                _ => (None, None),
+            };
+            orders.perform_cmd(api::post_event(model.auth_token.clone(), text, in_reply_to, quote_of));
         }
         Msg::PostEventEndpointResponded(_) => {
             model.flash.set_notice("Event Posted", orders);
+            let next_page = match &model.page {
+                Page::Reply(id) => Page::Thread(*id, PageData::NotLoaded),
+                _ => Page::Timeline(PageData::NotLoaded),
+            };
+            next_page.go(model, orders);
+        }
+        Msg::EditEventEndpointResponded(event) => {
+            model.flash.set_notice("Event Updated", orders);
+            Page::Thread(event.id, PageData::NotLoaded).go(model, orders);
+        }
+        Msg::DeleteEventClicked(id) => {
+            orders.perform_cmd(api::delete_event(model.auth_token.clone(), id));
+        }
+        Msg::DeleteEventEndpointResponded => {
+            model.flash.set_notice("Event Deleted", orders);
             Page::Timeline(PageData::NotLoaded).go(model, orders);
         }
+        Msg::LoadThread(id) => {
+            orders.perform_cmd(api::load_thread(id, model.auth_token.clone()));
+        }
+        Msg::LikeClicked(id, liked_by_me) => {
+            if liked_by_me {
+                orders.perform_cmd(api::unlike_event(id, model.auth_token.clone()));
+            } else {
+                orders.perform_cmd(api::like_event(id, model.auth_token.clone()));
+            }
+        }
+        Msg::LikeEndpointResponded(like) => {
+            model.page.for_each_event(&mut |event| {
+                if event.id == like.event_id {
+                    event.like_count = like.like_count;
+                    event.liked_by_me = like.liked_by_me;
+                }
+            });
+        }
+        Msg::RepostClicked(id, reposted_by_me) => {
+            if reposted_by_me {
+                orders.perform_cmd(api::unrepost(id, model.auth_token.clone()));
+            } else {
+                orders.perform_cmd(api::repost(id, model.auth_token.clone()));
+            }
+        }
+        Msg::RepostEndpointResponded(repost) => {
+            model.page.for_each_event(&mut |event| {
+                if event.id == repost.event_id {
+                    event.repost_count = repost.repost_count;
+                    event.reposted_by_me = repost.reposted_by_me;
+                }
+            });
+        }
+        Msg::LoadTagEvents(tag) => {
+            orders.perform_cmd(api::load_tag_events(tag, model.auth_token.clone()));
+        }
+        Msg::LoadTagEventsEndpointResponded(events) => {
+            if let Page::Tag(_, data) = &mut model.page {
+                *data = PageData::Loaded(events)
+            }
+        }
+        Msg::LoadMentions => {
+            orders.perform_cmd(api::load_mentions(model.auth_token.clone()));
+        }
+        Msg::LoadMentionsEndpointResponded(events) => {
+            if let Page::Mentions(data) = &mut model.page {
+                *data = PageData::Loaded(events)
+            }
+        }
+        Msg::LoadNotifications => {
+            orders.perform_cmd(api::load_notifications(model.auth_token.clone()));
+        }
+        Msg::LoadNotificationsEndpointResponded(notifications) => {
+            if let Some(newest) = notifications.first() {
+                orders.perform_cmd(api::mark_notifications_read(newest.id, model.auth_token.clone()));
+            }
+            if let Page::Notifications(data) = &mut model.page {
+                *data = PageData::Loaded(notifications)
+            }
+        }
+        Msg::SearchFormSubmitted => {
+            let q = model.search_form.query_input.get().unwrap().value();
+            if !q.trim().is_empty() {
+                Page::Search(q.trim().to_string(), PageData::NotLoaded).go(model, orders);
+            }
+        }
+        Msg::LoadSearch(q) => {
+            orders.perform_cmd(api::search(q, None, model.auth_token.clone()));
+        }
+        Msg::LoadMoreSearchResults(page) => {
+            if let Page::Search(q, _) = &model.page {
+                orders.perform_cmd(api::search(q.clone(), Some(page), model.auth_token.clone()));
+            }
+        }
+        Msg::LoadSearchEndpointResponded(results) => {
+            if let Page::Search(_, data) = &mut model.page {
+                match data {
+                    PageData::Loaded(loaded) => {
+                        loaded.events.extend(results.events);
+                        loaded.next_page = results.next_page;
+                    }
+                    PageData::NotLoaded => *data = PageData::Loaded(results),
+                }
+            }
+        }
+        Msg::MarkNotificationsReadEndpointResponded(unread) => {
+            model.unread_count = unread.unread_count;
+        }
+        Msg::LoadThreadEndpointResponded(thread) => {
+            if let Page::Thread(id, data) = &mut model.page {
+                if *id == thread.event.id {
+                    *data = PageData::Loaded(thread)
+                }
+            }
+        }
 
     }
 }
@@ -6,6 +6,9 @@ use seed::{prelude::*, *};
 // use web_sys::HtmlInputElement;
 // use std::fmt;
 use crate::flash::FlashMsg;
+use crate::api::API_URL;
+use shared::responses::{ThreadResponse, ThreadReplyResponse, UserResponse, ProfileResponse, PaginatedResponse, NotificationResponse, NotificationKind, MediaResponse, SearchResponse};
+use shared::validation::ValidationErrors;
 
 const ENTER_KEY: &str = "Enter";
 
@@ -45,9 +48,23 @@ fn view_navbar_menu_start(model: &Model) -> Node<Msg> {
                     "Home",
                 ],
                 a![
-                    C!["navbar-item", IF!(matches!(&model.page, Page::UserProfile(_) ) => "is-active"),],
+                    C!["navbar-item", IF!(matches!(&model.page, Page::UserProfile(..)) => "is-active"),],
                     &current_user.username,
-                    attrs! { At::Href => Page::UserProfile(current_user.username.clone()) }
+                    attrs! { At::Href => Page::UserProfile(current_user.username.clone(), PageData::NotLoaded, PageData::NotLoaded) }
+                ],
+                a![
+                    C!["navbar-item", IF!(matches!(&model.page, Page::Mentions(_) ) => "is-active"),],
+                    attrs!{ At::Href => Page::Mentions(PageData::NotLoaded) },
+                    "Mentions",
+                ],
+                a![
+                    C!["navbar-item", IF!(matches!(&model.page, Page::Notifications(_) ) => "is-active"),],
+                    attrs!{ At::Href => Page::Notifications(PageData::NotLoaded) },
+                    "Notifications",
+                    IF!(model.unread_count > 0 => span![
+                        C!["tag", "is-danger", "is-rounded", "ml-1"],
+                        model.unread_count.to_string(),
+                    ]),
                 ],
                 a![
                     C!["navbar-item", IF!(matches!(&model.page, Page::PostEvent ) => "is-active"),],
@@ -70,6 +87,7 @@ fn view_navbar_menu_start(model: &Model) -> Node<Msg> {
 fn view_navbar_menu_end(model: &Model) -> Node<Msg> {
      div![
         C!["navbar-end"],
+        view_search_box(model),
         div![
             C!["navbar-item"],
             div![
@@ -84,6 +102,23 @@ fn view_navbar_menu_end(model: &Model) -> Node<Msg> {
     ]
 }
 
+fn view_search_box(model: &Model) -> Node<Msg> {
+    div![
+        C!["navbar-item"],
+        input![
+            C!["input"],
+            el_ref(&model.search_form.query_input),
+            attrs! {
+                At::Type => "search",
+                At::Placeholder => "Search",
+            },
+            keyboard_ev(Ev::KeyDown, |keyboard_event| {
+                IF!(keyboard_event.key() == ENTER_KEY => Msg::SearchFormSubmitted)
+            }),
+        ],
+    ]
+}
+
 fn view_buttons_for_logged_in_user() -> Vec<Node<Msg>> {
     vec![
         a![
@@ -114,30 +149,220 @@ fn view_page(model: &Model) -> Node<Msg> {
         Page::RootLoggedOut => p!["You're on Root"],
         Page::Login => login(model),
         Page::SignUp => sign_up(model),
-        Page::UserProfile(username) => user_profile(username),
+        Page::UserProfile(_, profile, events) => user_profile(model, profile, events),
         Page::SignedIn => signed_in(),
         Page::Timeline(events) => timeline(model, events),
+        Page::Tag(_, events) => timeline(model, events),
+        Page::Mentions(events) => timeline(model, events),
+        Page::Notifications(notifications) => view_notifications(model, notifications),
         Page::PostEvent => post_event(model),
+        Page::Reply(_) | Page::Quote(_) | Page::EditEvent(_) => post_event(model),
+        Page::Thread(_, thread) => view_thread(model, thread),
+        Page::Search(_, results) => view_search(model, results),
     }
 }
 
-fn event(event: &EventResponse) -> Node<Msg> {
+fn event(model: &Model, event: &EventResponse) -> Node<Msg> {
+    let is_own_event = matches!(&model.current_user, Some(user) if user.id == event.user.id);
+
     div![
+        event.reposted_by.as_ref().map(|reposter| {
+            div![
+                C!["is-size-7"],
+                "reposted by ",
+                a![
+                    "@",
+                    &reposter.username,
+                    attrs! {
+                        At::Href => Page::UserProfile(reposter.username.to_string(), PageData::NotLoaded, PageData::NotLoaded)
+                    }
+                ],
+            ]
+        }),
         a![
             "@",
             &event.user.username,
             attrs! {
-                At::Href => Page::UserProfile(event.user.username.to_string())
+                At::Href => Page::UserProfile(event.user.username.to_string(), PageData::NotLoaded, PageData::NotLoaded)
             }
         ],
         br![],
         &event.content,
+        view_tags(&event.tags),
+        view_mentions(&event.mentions),
+        view_media(&event.media),
+        event.quoted_event.as_ref().map(|quoted| view_quoted_event(quoted)),
         br![],
-        format!("{:?}", &event.created_at),
+        a![
+            format!("{:?}", &event.created_at),
+            attrs! {
+                At::Href => Page::Thread(event.id, PageData::NotLoaded)
+            }
+        ],
+        IF!(event.edited_at.is_some() => " (edited)"),
+        " · ",
+        a![
+            "reply",
+            attrs! {
+                At::Href => Page::Reply(event.id)
+            }
+        ],
+        IF!(event.reply_count > 0 => span![format!(" · {} replies", event.reply_count)]),
+        " · ",
+        view_like_button(event),
+        " · ",
+        view_repost_button(event),
+        " · ",
+        a![
+            "quote",
+            attrs! {
+                At::Href => Page::Quote(event.id)
+            }
+        ],
+        IF!(is_own_event => view_owner_actions(event)),
         hr![],
     ]
 }
 
+fn view_owner_actions(event: &EventResponse) -> Vec<Node<Msg>> {
+    let id = event.id;
+
+    vec![
+        plain![" · "],
+        a![
+            "edit",
+            attrs! {
+                At::Href => Page::EditEvent(id)
+            }
+        ],
+        plain![" · "],
+        a![
+            "delete",
+            ev(Ev::Click, move |_| Msg::DeleteEventClicked(id)),
+        ],
+    ]
+}
+
+fn view_tags(tags: &[String]) -> Vec<Node<Msg>> {
+    tags.iter().map(|tag| {
+        span![
+            " ",
+            a![
+                "#",
+                tag,
+                attrs! {
+                    At::Href => Page::Tag(tag.to_string(), PageData::NotLoaded)
+                }
+            ],
+        ]
+    }).collect()
+}
+
+fn view_mentions(mentions: &[UserResponse]) -> Vec<Node<Msg>> {
+    mentions.iter().map(|user| {
+        span![
+            " ",
+            a![
+                "@",
+                &user.username,
+                attrs! {
+                    At::Href => Page::UserProfile(user.username.to_string(), PageData::NotLoaded, PageData::NotLoaded)
+                }
+            ],
+        ]
+    }).collect()
+}
+
+fn view_media(media: &[MediaResponse]) -> Option<Node<Msg>> {
+    if media.is_empty() {
+        return None;
+    }
+
+    Some(div![
+        media.iter().map(|media| {
+            a![
+                attrs! {
+                    At::Href => format!("{}{}", API_URL, media.url),
+                    At::Target => "_blank",
+                },
+                img![
+                    attrs! {
+                        At::Src => format!("{}{}", API_URL, media.thumbnail_url),
+                        At::Width => 160,
+                    }
+                ],
+            ]
+        }),
+    ])
+}
+
+fn view_quoted_event(quoted: &EventResponse) -> Node<Msg> {
+    div![
+        C!["box"],
+        a![
+            "@",
+            &quoted.user.username,
+            attrs! {
+                At::Href => Page::UserProfile(quoted.user.username.to_string(), PageData::NotLoaded, PageData::NotLoaded)
+            }
+        ],
+        br![],
+        a![
+            &quoted.content,
+            attrs! {
+                At::Href => Page::Thread(quoted.id, PageData::NotLoaded)
+            }
+        ],
+    ]
+}
+
+fn view_repost_button(event: &EventResponse) -> Node<Msg> {
+    let id = event.id;
+    let reposted_by_me = event.reposted_by_me;
+
+    a![
+        if reposted_by_me { "undo repost" } else { "repost" },
+        IF!(event.repost_count > 0 => format!(" ({})", event.repost_count)),
+        ev(Ev::Click, move |_| Msg::RepostClicked(id, reposted_by_me)),
+    ]
+}
+
+fn view_like_button(event: &EventResponse) -> Node<Msg> {
+    let id = event.id;
+    let liked_by_me = event.liked_by_me;
+
+    a![
+        if liked_by_me { "unlike" } else { "like" },
+        IF!(event.like_count > 0 => format!(" ({})", event.like_count)),
+        ev(Ev::Click, move |_| Msg::LikeClicked(id, liked_by_me)),
+    ]
+}
+
+fn view_thread(model: &Model, thread: &PageData<ThreadResponse>) -> Node<Msg> {
+    match thread {
+        PageData::NotLoaded => p!["Loading..."],
+        PageData::Loaded(thread) => {
+            div![
+                thread.ancestors.iter().map(|ancestor| event(model, ancestor)).collect::<Vec<_>>(),
+                div![C!["has-text-weight-bold"], event(model, &thread.event)],
+                view_thread_replies(model, &thread.replies),
+            ]
+        }
+    }
+}
+
+fn view_thread_replies(model: &Model, replies: &[ThreadReplyResponse]) -> Node<Msg> {
+    div![
+        style! { St::MarginLeft => "1.5em" },
+        replies.iter().map(|reply| {
+            div![
+                event(model, &reply.event),
+                view_thread_replies(model, &reply.replies),
+            ]
+        }).collect::<Vec<_>>(),
+    ]
+}
+
 fn post_event(model: &Model) -> Node<Msg> {
     div![
         div![input![
@@ -157,16 +382,53 @@ fn post_event(model: &Model) -> Node<Msg> {
     ]
 }
 
-fn timeline(_model: &Model, events: &PageData<Vec<EventResponse>>) -> Node<Msg> {
+fn timeline(model: &Model, events: &PageData<Vec<EventResponse>>) -> Node<Msg> {
     match events {
         PageData::NotLoaded => p!["Loading..."],
         PageData::Loaded(events) => {
-            let events_views: Vec<Node<Msg>> = events.iter().map(event).collect::<Vec<_>>();
+            let events_views: Vec<Node<Msg>> = events.iter().map(|e| event(model, e)).collect::<Vec<_>>();
             div![events_views]
         }
     }
 }
 
+fn view_notifications(model: &Model, notifications: &PageData<Vec<NotificationResponse>>) -> Node<Msg> {
+    match notifications {
+        PageData::NotLoaded => p!["Loading..."],
+        PageData::Loaded(notifications) => {
+            div![
+                notifications.iter().map(|notification| view_notification(model, notification)).collect::<Vec<_>>(),
+            ]
+        }
+    }
+}
+
+fn view_notification(model: &Model, notification: &NotificationResponse) -> Node<Msg> {
+    let action = match notification.kind {
+        NotificationKind::Follow => " followed you",
+        NotificationKind::Like => " liked your event",
+        NotificationKind::Reply => " replied to your event",
+        NotificationKind::Mention => " mentioned you",
+        NotificationKind::FollowRequest => " asked to follow you",
+    };
+
+    div![
+        C![IF!(!notification.read => "has-text-weight-bold")],
+        a![
+            "@",
+            &notification.actor.username,
+            attrs! {
+                At::Href => Page::UserProfile(notification.actor.username.to_string(), PageData::NotLoaded, PageData::NotLoaded)
+            }
+        ],
+        action,
+        match &notification.event {
+            Some(e) => event(model, e),
+            None => hr![],
+        },
+    ]
+}
+
 fn signed_in() -> Node<Msg> {
     div!["Signed in!"]
 }
@@ -206,25 +468,37 @@ fn login(model: &Model) -> Node<Msg> {
     ]
 }
 
+fn field_error(errors: &ValidationErrors, field: &str) -> Option<Node<Msg>> {
+    errors.for_field(field).map(|message| p![C!["help", "is-danger"], message])
+}
+
 fn sign_up(model: &Model) -> Node<Msg> {
+    let errors = &model.sign_up_form.errors;
+
     div![
-        div![input![
-            el_ref(&model.sign_up_form.username_input),
-            attrs! {
-                At::Type => "text",
-                At::Placeholder => "Username"
-            },
-        ]],
-        div![input![
-            el_ref(&model.sign_up_form.password_input),
-            attrs! {
-                At::Type => "password",
-                At::Placeholder => "Password"
-            },
-            keyboard_ev(Ev::KeyDown, |keyboard_event| {
-                IF!(keyboard_event.key() == ENTER_KEY => Msg::SignUpFormSubmitted)
-            }),
-        ]],
+        div![
+            input![
+                el_ref(&model.sign_up_form.username_input),
+                attrs! {
+                    At::Type => "text",
+                    At::Placeholder => "Username"
+                },
+            ],
+            field_error(errors, "username"),
+        ],
+        div![
+            input![
+                el_ref(&model.sign_up_form.password_input),
+                attrs! {
+                    At::Type => "password",
+                    At::Placeholder => "Password"
+                },
+                keyboard_ev(Ev::KeyDown, |keyboard_event| {
+                    IF!(keyboard_event.key() == ENTER_KEY => Msg::SignUpFormSubmitted)
+                }),
+            ],
+            field_error(errors, "password"),
+        ],
         div![
             button![ev(Ev::Click, |_| Msg::SignUpFormSubmitted), "Sign Up"],
         ],
@@ -232,6 +506,77 @@ fn sign_up(model: &Model) -> Node<Msg> {
 }
 
 
-fn user_profile(username: &str) -> Node<Msg> {
-    p!["Profile of ", username]
-}
\ No newline at end of file
+fn user_profile(model: &Model, profile: &PageData<ProfileResponse>, events: &PageData<PaginatedResponse<EventResponse>>) -> Node<Msg> {
+    let header = match profile {
+        PageData::NotLoaded => p!["Loading..."],
+        PageData::Loaded(profile) => div![
+            C!["box"],
+            profile.avatar_url.as_ref().map(|url| {
+                figure![C!["image", "is-64x64"], img![attrs! { At::Src => url }]]
+            }),
+            p![
+                strong![profile.display_name.as_deref().unwrap_or(&profile.user.username)],
+                " @",
+                &profile.user.username,
+                IF!(profile.is_private => " (private)"),
+            ],
+            profile.bio.as_ref().map(|bio| p![bio]),
+            p![
+                C!["is-size-7"],
+                format!(
+                    "{} events · {} following · {} followers",
+                    profile.event_count, profile.following_count, profile.follower_count,
+                ),
+            ],
+        ],
+    };
+
+    let events = match events {
+        PageData::NotLoaded => p!["Loading..."],
+        PageData::Loaded(page) => div![
+            page.items.iter().map(|e| event(model, e)).collect::<Vec<_>>(),
+            page.next_cursor.as_ref().map(|cursor| {
+                let cursor = cursor.clone();
+                button![
+                    C!["button"],
+                    ev(Ev::Click, move |_| Msg::LoadMoreUserEvents(cursor)),
+                    "Load more",
+                ]
+            }),
+        ],
+    };
+
+    div![header, events]
+}
+
+fn view_search(model: &Model, results: &PageData<SearchResponse>) -> Node<Msg> {
+    match results {
+        PageData::NotLoaded => p!["Loading..."],
+        PageData::Loaded(results) => div![
+            IF!(!results.users.is_empty() => div![
+                C!["box"],
+                results.users.iter().map(|profile| {
+                    p![
+                        a![
+                            "@",
+                            &profile.user.username,
+                            attrs! {
+                                At::Href => Page::UserProfile(profile.user.username.to_string(), PageData::NotLoaded, PageData::NotLoaded)
+                            }
+                        ],
+                        profile.display_name.as_ref().map(|name| span![" ", name]),
+                    ]
+                }),
+            ]),
+            IF!(results.users.is_empty() && results.events.is_empty() => p!["Nothing found"]),
+            results.events.iter().map(|e| event(model, e)).collect::<Vec<_>>(),
+            results.next_page.map(|page| {
+                button![
+                    C!["button"],
+                    ev(Ev::Click, move |_| Msg::LoadMoreSearchResults(page)),
+                    "Load more",
+                ]
+            }),
+        ],
+    }
+}
@@ -0,0 +1,232 @@
+//! The errors the API answers with. Each has a stable `code`, sent alongside the status and
+//! message, so clients can tell failures apart without parsing the message:
+//!
+//! ```json
+//! {"error": {"status_code": "404", "code": "not_found", "resource": "event", "message": "Event does not exist"}}
+//! ```
+//!
+//! Any data a variant carries is sent as extra fields next to the code.
+
+use http_types::StatusCode;
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+use std::fmt;
+use crate::validation::FieldError;
+
+/// The kinds of things a `NotFound` can be about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Resource {
+    User,
+    Event,
+    Conversation,
+    FollowRequest,
+    Notification,
+    Session,
+    Media,
+}
+
+impl fmt::Display for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Resource::User => "User",
+            Resource::Event => "Event",
+            Resource::Conversation => "Conversation",
+            Resource::FollowRequest => "Follow request",
+            Resource::Notification => "Notification",
+            Resource::Session => "Session",
+            Resource::Media => "Media",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum ApiError {
+    MissingHeader { header: String },
+    MalformedAuthHeader,
+    EmptySearchQuery,
+    InvalidCursor,
+    InvalidUpload { reason: String },
+    /// A request the server couldn't make sense of, such as a body that isn't valid JSON.
+    InvalidRequest,
+
+    InvalidToken,
+    TokenExpired,
+    IncorrectPassword,
+
+    /// Either user has blocked the other. `action` finishes "You cannot ...".
+    Blocked { action: String },
+    PrivateAccount,
+    /// `action` is what was attempted on the private account's event, e.g. "repost".
+    PrivateEvent { action: String },
+    NotEventAuthor,
+    NotConversationMember,
+    OriginNotAllowed,
+
+    NotFound { resource: Resource },
+    NotLiked,
+    NotReposted,
+    InvalidResetToken,
+
+    UsernameTaken,
+    EmailTaken,
+    AlreadyLiked,
+    AlreadyReposted,
+    /// `action` finishes "You cannot ... yourself".
+    CannotTargetSelf { action: String },
+
+    FileTooLarge,
+    /// The image is wider or taller than `max` pixels.
+    ImageTooLarge { max: u32 },
+    UnsupportedImageType,
+    InvalidImage,
+
+    ValidationFailed { fields: Vec<FieldError> },
+    ContentTooLong { field: String, max_length: usize },
+    InvalidEmail,
+    InvalidAvatarUrl,
+    TooManyAttachments { max: usize },
+    TooFewMembers,
+    TooManyMembers { max: usize },
+
+    RateLimited { retry_after: u64 },
+
+    Internal,
+    /// A code this build doesn't know about, from a newer server.
+    #[serde(other)]
+    #[schemars(skip)]
+    Unknown,
+}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        use ApiError::*;
+
+        match self {
+            MissingHeader { .. } | MalformedAuthHeader | EmptySearchQuery | InvalidCursor | InvalidUpload { .. }
+            | InvalidRequest => StatusCode::BadRequest,
+            InvalidToken | TokenExpired | IncorrectPassword => StatusCode::Unauthorized,
+            Blocked { .. } | PrivateAccount | PrivateEvent { .. } | NotEventAuthor | NotConversationMember
+            | OriginNotAllowed => StatusCode::Forbidden,
+            NotFound { .. } | NotLiked | NotReposted | InvalidResetToken => StatusCode::NotFound,
+            UsernameTaken | EmailTaken | AlreadyLiked | AlreadyReposted | CannotTargetSelf { .. } => StatusCode::Conflict,
+            FileTooLarge | ImageTooLarge { .. } => StatusCode::PayloadTooLarge,
+            UnsupportedImageType | InvalidImage => StatusCode::UnsupportedMediaType,
+            ValidationFailed { .. } | ContentTooLong { .. } | InvalidEmail | InvalidAvatarUrl
+            | TooManyAttachments { .. } | TooFewMembers | TooManyMembers { .. } => StatusCode::UnprocessableEntity,
+            RateLimited { .. } => StatusCode::TooManyRequests,
+            Internal | Unknown => StatusCode::InternalServerError,
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ApiError::*;
+
+        match self {
+            MissingHeader { header } => write!(f, "Missing value for '{}' header", header),
+            MalformedAuthHeader => write!(f, "Unable to parse Authorization header value"),
+            EmptySearchQuery => write!(f, "Search query cannot be empty"),
+            InvalidCursor => write!(f, "Invalid pagination cursor"),
+            InvalidUpload { reason } => write!(f, "{}", reason),
+            InvalidRequest => write!(f, "Invalid request"),
+            InvalidToken => write!(f, "Invalid auth token"),
+            TokenExpired => write!(f, "Auth token expired"),
+            IncorrectPassword => write!(f, "Incorrect password"),
+            Blocked { action } => write!(f, "You cannot {}", action),
+            PrivateAccount => write!(f, "This account is private"),
+            PrivateEvent { action } => write!(f, "You cannot {} events from a private account", action),
+            NotEventAuthor => write!(f, "You can only change your own events"),
+            NotConversationMember => write!(f, "You are not a member of this conversation"),
+            OriginNotAllowed => write!(f, "Origin not allowed"),
+            NotFound { resource } => write!(f, "{} does not exist", resource),
+            NotLiked => write!(f, "You have not liked this event"),
+            NotReposted => write!(f, "You have not reposted this event"),
+            InvalidResetToken => write!(f, "Password reset token is invalid or expired"),
+            UsernameTaken => write!(f, "Username is already taken"),
+            EmailTaken => write!(f, "Email is already taken"),
+            AlreadyLiked => write!(f, "You cannot like the same event twice"),
+            AlreadyReposted => write!(f, "You cannot repost the same event twice"),
+            CannotTargetSelf { action } => write!(f, "You cannot {} yourself", action),
+            FileTooLarge => write!(f, "File too large"),
+            ImageTooLarge { max } => write!(f, "Images can be at most {} pixels wide and tall", max),
+            UnsupportedImageType => write!(f, "Only PNG, JPEG and GIF images are supported"),
+            InvalidImage => write!(f, "File is not a valid image"),
+            ValidationFailed { .. } => write!(f, "Validation failed"),
+            ContentTooLong { field, .. } => write!(f, "{} too long", field),
+            InvalidEmail => write!(f, "email is invalid"),
+            InvalidAvatarUrl => write!(f, "avatar_url must be an http or https URL"),
+            TooManyAttachments { max } => write!(f, "An event can have at most {} media attachments", max),
+            TooFewMembers => write!(f, "A conversation needs at least one other member"),
+            TooManyMembers { max } => write!(f, "Conversations can have at most {} members", max),
+            RateLimited { retry_after } => write!(f, "Too many requests, try again in {} seconds", retry_after),
+            Internal | Unknown => write!(f, "Something went wrong"),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+/// The `error` object of a failed response. `message` is usually the error's own, but can be
+/// more specific, e.g. saying what was wrong with a malformed body.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ErrorBody {
+    pub status_code: String,
+    #[serde(flatten)]
+    pub error: ApiError,
+    pub message: String,
+}
+
+impl From<ApiError> for ErrorBody {
+    fn from(error: ApiError) -> Self {
+        ErrorBody {
+            status_code: error.status().to_string(),
+            message: error.to_string(),
+            error,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ErrorResponse {
+    pub error: ErrorBody,
+}
+
+impl From<ApiError> for ErrorResponse {
+    fn from(error: ApiError) -> Self {
+        ErrorResponse { error: error.into() }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn variant_data_sits_next_to_the_code() {
+        let body = ErrorBody::from(ApiError::NotFound { resource: Resource::FollowRequest });
+
+        let json = serde_json::to_value(&body).unwrap();
+
+        assert_eq!(json, serde_json::json!({
+            "status_code": "404",
+            "code": "not_found",
+            "resource": "follow_request",
+            "message": "Follow request does not exist",
+        }));
+        assert_eq!(serde_json::from_value::<ErrorBody>(json).unwrap(), body);
+    }
+
+    #[test]
+    fn unknown_codes_still_parse() {
+        let json = r#"{"error": {"status_code": "418", "code": "teapot", "spout": "short", "message": "I'm a teapot"}}"#;
+
+        let response: ErrorResponse = serde_json::from_str(json).unwrap();
+
+        assert_eq!(response.error.error, ApiError::Unknown);
+        assert_eq!(response.error.message, "I'm a teapot");
+    }
+}
@@ -1,20 +1,49 @@
 use http_types::Method;
+use schemars::JsonSchema;
 use serde::{Serialize, de::DeserializeOwned};
+use uuid::Uuid;
 
+pub mod errors;
 pub mod responses;
 pub mod payloads;
+pub mod validation;
 
 pub trait Url {
     const URL_SPEC: &'static str;
+    /// Query string parameters the endpoint understands, for the OpenAPI spec.
+    const QUERY_PARAMS: &'static [QueryParam] = &[];
 
     fn url(&self) -> String;
 }
 
+pub struct QueryParam {
+    pub name: &'static str,
+    pub required: bool,
+}
+
+impl QueryParam {
+    pub const fn required(name: &'static str) -> Self {
+        QueryParam { name, required: true }
+    }
+
+    pub const fn optional(name: &'static str) -> Self {
+        QueryParam { name, required: false }
+    }
+}
+
+/// Accepted by every endpoint that responds with a `PaginatedResponse`.
+pub const PAGINATION_PARAMS: &[QueryParam] = &[
+    QueryParam::optional("before"),
+    QueryParam::optional("after"),
+    QueryParam::optional("page"),
+    QueryParam::optional("page_size"),
+];
+
 pub trait ApiEndpoint {
     type Url: Url;
     const METHOD: Method;
     type Payload;
-    type Response: Serialize + DeserializeOwned;
+    type Response: Serialize + DeserializeOwned + JsonSchema;
 }
 
 pub struct GetUser;
@@ -25,7 +54,7 @@ impl ApiEndpoint for GetUser {
     type Url = GetUserUrl;
     const METHOD: Method = Method::Get;
     type Payload = NoPayload;
-    type Response = responses::UserResponse;
+    type Response = responses::ProfileResponse;
 }
 
 pub struct GetUserUrl {
@@ -40,6 +69,93 @@ impl Url for GetUserUrl {
     }
 }
 
+pub struct UserEvents;
+
+impl ApiEndpoint for UserEvents {
+    type Url = UserEventsUrl;
+    const METHOD: Method = Method::Get;
+    type Payload = NoPayload;
+    type Response = responses::PaginatedResponse<responses::EventResponse>;
+}
+
+pub struct UserEventsUrl {
+    pub username: String,
+    /// A `next_cursor` from the previous page.
+    pub before: Option<String>,
+}
+
+impl Url for UserEventsUrl {
+    const URL_SPEC: &'static str = "/users/:username/events";
+    const QUERY_PARAMS: &'static [QueryParam] = PAGINATION_PARAMS;
+
+    fn url(&self) -> String {
+        match &self.before {
+            Some(before) => format!("/users/{}/events?before={}", self.username, before),
+            None => format!("/users/{}/events", self.username),
+        }
+    }
+}
+
+pub struct BlockUser;
+
+impl ApiEndpoint for BlockUser {
+    type Url = BlockUrl;
+    const METHOD: Method = Method::Post;
+    type Payload = NoPayload;
+    type Response = responses::RelationshipResponse;
+}
+
+pub struct UnblockUser;
+
+impl ApiEndpoint for UnblockUser {
+    type Url = BlockUrl;
+    const METHOD: Method = Method::Delete;
+    type Payload = NoPayload;
+    type Response = responses::RelationshipResponse;
+}
+
+pub struct BlockUrl {
+    pub username: String,
+}
+
+impl Url for BlockUrl {
+    const URL_SPEC: &'static str = "/users/:username/block";
+
+    fn url(&self) -> String {
+        format!("/users/{}/block", self.username)
+    }
+}
+
+pub struct MuteUser;
+
+impl ApiEndpoint for MuteUser {
+    type Url = MuteUrl;
+    const METHOD: Method = Method::Post;
+    type Payload = NoPayload;
+    type Response = responses::RelationshipResponse;
+}
+
+pub struct UnmuteUser;
+
+impl ApiEndpoint for UnmuteUser {
+    type Url = MuteUrl;
+    const METHOD: Method = Method::Delete;
+    type Payload = NoPayload;
+    type Response = responses::RelationshipResponse;
+}
+
+pub struct MuteUrl {
+    pub username: String,
+}
+
+impl Url for MuteUrl {
+    const URL_SPEC: &'static str = "/users/:username/mute";
+
+    fn url(&self) -> String {
+        format!("/users/{}/mute", self.username)
+    }
+}
+
 pub struct PostEvent;
 
 impl ApiEndpoint for PostEvent {
@@ -55,7 +171,232 @@ impl Url for PostEventUrl {
     const URL_SPEC: &'static str = "/events";
 
     fn url(&self) -> String {
-        format!("/events")
+        "/events".to_string()
+    }
+}
+
+pub struct UploadMedia;
+
+impl ApiEndpoint for UploadMedia {
+    type Url = UploadMediaUrl;
+    const METHOD: Method = Method::Post;
+    type Payload = payloads::UploadMediaPayload;
+    type Response = responses::MediaResponse;
+}
+
+pub struct UploadMediaUrl;
+
+impl Url for UploadMediaUrl {
+    const URL_SPEC: &'static str = "/media";
+
+    fn url(&self) -> String {
+        "/media".to_string()
+    }
+}
+
+pub struct EditEvent;
+
+impl ApiEndpoint for EditEvent {
+    type Url = EventUrl;
+    const METHOD: Method = Method::Patch;
+    type Payload = payloads::EditEventPayload;
+    type Response = responses::EventResponse;
+}
+
+pub struct DeleteEvent;
+
+impl ApiEndpoint for DeleteEvent {
+    type Url = EventUrl;
+    const METHOD: Method = Method::Delete;
+    type Payload = NoPayload;
+    type Response = ();
+}
+
+pub struct EventUrl {
+    pub id: Uuid,
+}
+
+impl Url for EventUrl {
+    const URL_SPEC: &'static str = "/events/:id";
+
+    fn url(&self) -> String {
+        format!("/events/{}", self.id)
+    }
+}
+
+pub struct GetEventRevisions;
+
+impl ApiEndpoint for GetEventRevisions {
+    type Url = GetEventRevisionsUrl;
+    const METHOD: Method = Method::Get;
+    type Payload = NoPayload;
+    type Response = Vec<responses::EventRevisionResponse>;
+}
+
+pub struct GetEventRevisionsUrl {
+    pub id: Uuid,
+}
+
+impl Url for GetEventRevisionsUrl {
+    const URL_SPEC: &'static str = "/events/:id/revisions";
+
+    fn url(&self) -> String {
+        format!("/events/{}/revisions", self.id)
+    }
+}
+
+pub struct GetThread;
+
+impl ApiEndpoint for GetThread {
+    type Url = GetThreadUrl;
+    const METHOD: Method = Method::Get;
+    type Payload = NoPayload;
+    type Response = responses::ThreadResponse;
+}
+
+pub struct GetThreadUrl {
+    pub id: Uuid,
+}
+
+impl Url for GetThreadUrl {
+    const URL_SPEC: &'static str = "/events/:id/thread";
+
+    fn url(&self) -> String {
+        format!("/events/{}/thread", self.id)
+    }
+}
+
+pub struct LikeEvent;
+
+impl ApiEndpoint for LikeEvent {
+    type Url = LikeEventUrl;
+    const METHOD: Method = Method::Post;
+    type Payload = NoPayload;
+    type Response = responses::LikeResponse;
+}
+
+pub struct UnlikeEvent;
+
+impl ApiEndpoint for UnlikeEvent {
+    type Url = LikeEventUrl;
+    const METHOD: Method = Method::Delete;
+    type Payload = NoPayload;
+    type Response = responses::LikeResponse;
+}
+
+pub struct LikeEventUrl {
+    pub id: Uuid,
+}
+
+impl Url for LikeEventUrl {
+    const URL_SPEC: &'static str = "/events/:id/like";
+
+    fn url(&self) -> String {
+        format!("/events/{}/like", self.id)
+    }
+}
+
+pub struct Repost;
+
+impl ApiEndpoint for Repost {
+    type Url = RepostUrl;
+    const METHOD: Method = Method::Post;
+    type Payload = NoPayload;
+    type Response = responses::RepostResponse;
+}
+
+pub struct Unrepost;
+
+impl ApiEndpoint for Unrepost {
+    type Url = RepostUrl;
+    const METHOD: Method = Method::Delete;
+    type Payload = NoPayload;
+    type Response = responses::RepostResponse;
+}
+
+pub struct RepostUrl {
+    pub id: Uuid,
+}
+
+impl Url for RepostUrl {
+    const URL_SPEC: &'static str = "/events/:id/repost";
+
+    fn url(&self) -> String {
+        format!("/events/{}/repost", self.id)
+    }
+}
+
+pub struct TagEvents;
+
+impl ApiEndpoint for TagEvents {
+    type Url = TagEventsUrl;
+    const METHOD: Method = Method::Get;
+    type Payload = NoPayload;
+    type Response = responses::PaginatedResponse<responses::EventResponse>;
+}
+
+pub struct TagEventsUrl {
+    pub tag: String,
+}
+
+impl Url for TagEventsUrl {
+    const URL_SPEC: &'static str = "/tags/:tag/events";
+    const QUERY_PARAMS: &'static [QueryParam] = PAGINATION_PARAMS;
+
+    fn url(&self) -> String {
+        format!("/tags/{}/events", self.tag)
+    }
+}
+
+pub struct TrendingTags;
+
+impl ApiEndpoint for TrendingTags {
+    type Url = TrendingTagsUrl;
+    const METHOD: Method = Method::Get;
+    type Payload = NoPayload;
+    type Response = Vec<responses::TrendingTagResponse>;
+}
+
+pub struct TrendingTagsUrl;
+
+impl Url for TrendingTagsUrl {
+    const URL_SPEC: &'static str = "/tags/trending";
+
+    fn url(&self) -> String {
+        "/tags/trending".to_string()
+    }
+}
+
+pub struct Search;
+
+impl ApiEndpoint for Search {
+    type Url = SearchUrl;
+    const METHOD: Method = Method::Get;
+    type Payload = NoPayload;
+    type Response = responses::SearchResponse;
+}
+
+pub struct SearchUrl {
+    pub q: String,
+    /// Results are ranked by relevance, so they're paged by number rather than by cursor.
+    pub page: Option<usize>,
+}
+
+impl Url for SearchUrl {
+    const URL_SPEC: &'static str = "/search";
+    const QUERY_PARAMS: &'static [QueryParam] = &[
+        QueryParam::required("q"),
+        QueryParam::optional("page"),
+        QueryParam::optional("page_size"),
+    ];
+
+    fn url(&self) -> String {
+        let mut query = form_urlencoded::Serializer::new(String::new());
+        query.append_pair("q", &self.q);
+        if let Some(page) = self.page {
+            query.append_pair("page", &page.to_string());
+        }
+        format!("/search?{}", query.finish())
     }
 }
 
@@ -65,7 +406,16 @@ impl ApiEndpoint for Me {
     type Url = MeUrl;
     const METHOD: Method = Method::Get;
     type Payload = NoPayload;
-    type Response = responses::UserResponse;
+    type Response = responses::MeResponse;
+}
+
+pub struct UpdateMe;
+
+impl ApiEndpoint for UpdateMe {
+    type Url = MeUrl;
+    const METHOD: Method = Method::Patch;
+    type Payload = payloads::UpdateMePayload;
+    type Response = responses::MeResponse;
 }
 
 pub struct MeUrl;
@@ -74,7 +424,7 @@ impl Url for MeUrl {
     const URL_SPEC: &'static str = "/me";
 
     fn url(&self) -> String {
-        format!("/me")
+        "/me".to_string()
     }
 }
 
@@ -114,7 +464,7 @@ impl Url for CreateUserUrl {
     const URL_SPEC: &'static str = "/users";
 
     fn url(&self) -> String {
-        format!("/users")
+        "/users".to_string()
     }
 }
 
@@ -124,16 +474,333 @@ impl ApiEndpoint for Timeline {
     type Url = TimelineUrl;
     const METHOD: Method = Method::Get;
     type Payload = NoPayload;
-    type Response = Vec<responses::EventResponse>;
+    type Response = responses::PaginatedResponse<responses::EventResponse>;
 }
 
 pub struct TimelineUrl;
 
 impl Url for TimelineUrl {
     const URL_SPEC: &'static str = "/me/timeline";
+    const QUERY_PARAMS: &'static [QueryParam] = PAGINATION_PARAMS;
+
+    fn url(&self) -> String {
+        "/me/timeline".to_string()
+    }
+}
+
+
+pub struct Mentions;
+
+impl ApiEndpoint for Mentions {
+    type Url = MentionsUrl;
+    const METHOD: Method = Method::Get;
+    type Payload = NoPayload;
+    type Response = responses::PaginatedResponse<responses::EventResponse>;
+}
+
+pub struct MentionsUrl;
+
+impl Url for MentionsUrl {
+    const URL_SPEC: &'static str = "/me/mentions";
+    const QUERY_PARAMS: &'static [QueryParam] = PAGINATION_PARAMS;
+
+    fn url(&self) -> String {
+        "/me/mentions".to_string()
+    }
+}
+
+pub struct Notifications;
+
+impl ApiEndpoint for Notifications {
+    type Url = NotificationsUrl;
+    const METHOD: Method = Method::Get;
+    type Payload = NoPayload;
+    type Response = responses::PaginatedResponse<responses::NotificationResponse>;
+}
+
+pub struct NotificationsUrl;
+
+impl Url for NotificationsUrl {
+    const URL_SPEC: &'static str = "/me/notifications";
+    const QUERY_PARAMS: &'static [QueryParam] = PAGINATION_PARAMS;
+
+    fn url(&self) -> String {
+        "/me/notifications".to_string()
+    }
+}
+
+pub struct MarkNotificationsRead;
+
+impl ApiEndpoint for MarkNotificationsRead {
+    type Url = MarkNotificationsReadUrl;
+    const METHOD: Method = Method::Post;
+    type Payload = payloads::MarkNotificationsReadPayload;
+    type Response = responses::UnreadCountResponse;
+}
+
+pub struct MarkNotificationsReadUrl;
+
+impl Url for MarkNotificationsReadUrl {
+    const URL_SPEC: &'static str = "/me/notifications/read";
+
+    fn url(&self) -> String {
+        "/me/notifications/read".to_string()
+    }
+}
+
+pub struct ListConversations;
+
+impl ApiEndpoint for ListConversations {
+    type Url = ConversationsUrl;
+    const METHOD: Method = Method::Get;
+    type Payload = NoPayload;
+    type Response = responses::PaginatedResponse<responses::ConversationResponse>;
+}
+
+pub struct CreateConversation;
+
+impl ApiEndpoint for CreateConversation {
+    type Url = ConversationsUrl;
+    const METHOD: Method = Method::Post;
+    type Payload = payloads::CreateConversationPayload;
+    type Response = responses::ConversationResponse;
+}
+
+pub struct ConversationsUrl;
+
+impl Url for ConversationsUrl {
+    const URL_SPEC: &'static str = "/conversations";
+    const QUERY_PARAMS: &'static [QueryParam] = PAGINATION_PARAMS;
+
+    fn url(&self) -> String {
+        "/conversations".to_string()
+    }
+}
+
+pub struct ListMessages;
+
+impl ApiEndpoint for ListMessages {
+    type Url = MessagesUrl;
+    const METHOD: Method = Method::Get;
+    type Payload = NoPayload;
+    type Response = responses::PaginatedResponse<responses::MessageResponse>;
+}
+
+pub struct SendMessage;
+
+impl ApiEndpoint for SendMessage {
+    type Url = MessagesUrl;
+    const METHOD: Method = Method::Post;
+    type Payload = payloads::SendMessagePayload;
+    type Response = responses::MessageResponse;
+}
+
+pub struct MessagesUrl {
+    pub id: Uuid,
+}
+
+impl Url for MessagesUrl {
+    const URL_SPEC: &'static str = "/conversations/:id/messages";
+    const QUERY_PARAMS: &'static [QueryParam] = PAGINATION_PARAMS;
+
+    fn url(&self) -> String {
+        format!("/conversations/{}/messages", self.id)
+    }
+}
+
+pub struct ListBlocks;
+
+impl ApiEndpoint for ListBlocks {
+    type Url = ListBlocksUrl;
+    const METHOD: Method = Method::Get;
+    type Payload = NoPayload;
+    type Response = Vec<responses::UserResponse>;
+}
+
+pub struct ListBlocksUrl;
+
+impl Url for ListBlocksUrl {
+    const URL_SPEC: &'static str = "/me/blocks";
+
+    fn url(&self) -> String {
+        "/me/blocks".to_string()
+    }
+}
+
+pub struct ListMutes;
+
+impl ApiEndpoint for ListMutes {
+    type Url = ListMutesUrl;
+    const METHOD: Method = Method::Get;
+    type Payload = NoPayload;
+    type Response = Vec<responses::UserResponse>;
+}
+
+pub struct ListMutesUrl;
+
+impl Url for ListMutesUrl {
+    const URL_SPEC: &'static str = "/me/mutes";
+
+    fn url(&self) -> String {
+        "/me/mutes".to_string()
+    }
+}
+
+pub struct FollowRequests;
+
+impl ApiEndpoint for FollowRequests {
+    type Url = FollowRequestsUrl;
+    const METHOD: Method = Method::Get;
+    type Payload = NoPayload;
+    type Response = responses::PaginatedResponse<responses::FollowRequestResponse>;
+}
+
+pub struct FollowRequestsUrl;
+
+impl Url for FollowRequestsUrl {
+    const URL_SPEC: &'static str = "/me/follow_requests";
+    const QUERY_PARAMS: &'static [QueryParam] = PAGINATION_PARAMS;
+
+    fn url(&self) -> String {
+        "/me/follow_requests".to_string()
+    }
+}
+
+pub struct ApproveFollowRequest;
+
+impl ApiEndpoint for ApproveFollowRequest {
+    type Url = ApproveFollowRequestUrl;
+    const METHOD: Method = Method::Post;
+    type Payload = NoPayload;
+    type Response = responses::RelationshipResponse;
+}
+
+pub struct ApproveFollowRequestUrl {
+    pub id: Uuid,
+}
+
+impl Url for ApproveFollowRequestUrl {
+    const URL_SPEC: &'static str = "/me/follow_requests/:id/approve";
+
+    fn url(&self) -> String {
+        format!("/me/follow_requests/{}/approve", self.id)
+    }
+}
+
+pub struct RejectFollowRequest;
+
+impl ApiEndpoint for RejectFollowRequest {
+    type Url = RejectFollowRequestUrl;
+    const METHOD: Method = Method::Post;
+    type Payload = NoPayload;
+    type Response = responses::RelationshipResponse;
+}
+
+pub struct RejectFollowRequestUrl {
+    pub id: Uuid,
+}
+
+impl Url for RejectFollowRequestUrl {
+    const URL_SPEC: &'static str = "/me/follow_requests/:id/reject";
+
+    fn url(&self) -> String {
+        format!("/me/follow_requests/{}/reject", self.id)
+    }
+}
+
+pub struct ListSessions;
+
+impl ApiEndpoint for ListSessions {
+    type Url = ListSessionsUrl;
+    const METHOD: Method = Method::Get;
+    type Payload = NoPayload;
+    type Response = Vec<responses::SessionResponse>;
+}
+
+pub struct ListSessionsUrl;
+
+impl Url for ListSessionsUrl {
+    const URL_SPEC: &'static str = "/me/sessions";
+
+    fn url(&self) -> String {
+        "/me/sessions".to_string()
+    }
+}
+
+pub struct DeleteSession;
+
+impl ApiEndpoint for DeleteSession {
+    type Url = DeleteSessionUrl;
+    const METHOD: Method = Method::Delete;
+    type Payload = NoPayload;
+    type Response = ();
+}
+
+pub struct DeleteSessionUrl {
+    pub id: Uuid,
+}
+
+impl Url for DeleteSessionUrl {
+    const URL_SPEC: &'static str = "/me/sessions/:id";
 
     fn url(&self) -> String {
-        format!("/me/timeline")
+        format!("/me/sessions/{}", self.id)
     }
 }
 
+pub struct ChangePassword;
+
+impl ApiEndpoint for ChangePassword {
+    type Url = ChangePasswordUrl;
+    const METHOD: Method = Method::Put;
+    type Payload = payloads::ChangePasswordPayload;
+    type Response = ();
+}
+
+pub struct ChangePasswordUrl;
+
+impl Url for ChangePasswordUrl {
+    const URL_SPEC: &'static str = "/me/password";
+
+    fn url(&self) -> String {
+        "/me/password".to_string()
+    }
+}
+
+pub struct RequestPasswordReset;
+
+impl ApiEndpoint for RequestPasswordReset {
+    type Url = RequestPasswordResetUrl;
+    const METHOD: Method = Method::Post;
+    type Payload = payloads::RequestPasswordResetPayload;
+    type Response = ();
+}
+
+pub struct RequestPasswordResetUrl;
+
+impl Url for RequestPasswordResetUrl {
+    const URL_SPEC: &'static str = "/password_resets";
+
+    fn url(&self) -> String {
+        "/password_resets".to_string()
+    }
+}
+
+pub struct ResetPassword;
+
+impl ApiEndpoint for ResetPassword {
+    type Url = ResetPasswordUrl;
+    const METHOD: Method = Method::Post;
+    type Payload = payloads::ResetPasswordPayload;
+    type Response = ();
+}
+
+pub struct ResetPasswordUrl;
+
+impl Url for ResetPasswordUrl {
+    const URL_SPEC: &'static str = "/password_resets/reset";
+
+    fn url(&self) -> String {
+        "/password_resets/reset".to_string()
+    }
+}
@@ -1,17 +1,92 @@
+use schemars::JsonSchema;
 use serde::{Serialize, Deserialize};
+use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct CreateUserPayload {
     pub username: String,
     pub password: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct LoginPayload {
-    pub password: String
+    pub password: String,
+    #[serde(default)]
+    pub device: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct CreateEventPayload {
     pub content: String,
+    #[serde(default)]
+    pub in_reply_to: Option<Uuid>,
+    #[serde(default)]
+    pub quote_of: Option<Uuid>,
+    /// Ids from `UploadMedia`, at most four.
+    #[serde(default)]
+    pub media_ids: Vec<Uuid>,
+}
+
+/// A file sent as the `file` field of a `multipart/form-data` body, rather than as JSON.
+#[derive(Debug)]
+pub struct UploadMediaPayload {
+    pub content_type: Option<String>,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct EditEventPayload {
+    pub content: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct MarkNotificationsReadPayload {
+    /// The newest notification seen; it and every older one are marked read.
+    pub up_to: Uuid,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct CreateConversationPayload {
+    /// Everyone to talk with, not including yourself.
+    pub usernames: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SendMessagePayload {
+    pub content: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
+pub struct UpdateMePayload {
+    /// Fields left out are left as they are. An empty string clears a text field.
+    #[serde(default)]
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub bio: Option<String>,
+    #[serde(default)]
+    pub avatar_url: Option<String>,
+    /// Only approved followers see a private account's events.
+    #[serde(default)]
+    pub is_private: Option<bool>,
+    /// Where password reset emails are sent. Never shown to anyone else.
+    #[serde(default)]
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ChangePasswordPayload {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct RequestPasswordResetPayload {
+    pub username: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ResetPasswordPayload {
+    /// The token from the password reset email.
+    pub token: String,
+    pub new_password: String,
 }
\ No newline at end of file
@@ -1,8 +1,9 @@
 use chrono::prelude::*;
+use schemars::JsonSchema;
 use serde::{Serialize, Deserialize};
 use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ApiResponse<T> {
     pub data: T
 }
@@ -13,7 +14,14 @@ impl<T> ApiResponse<T> {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// One page of a listing, with the cursor to pass as `before` (or `after`) to fetch the next.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct PaginatedResponse<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct TokenResponse {
     pub token: String
 }
@@ -24,22 +32,192 @@ impl TokenResponse {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct UserResponse {
     pub id: Uuid,
     pub username: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct ProfileResponse {
+    #[serde(flatten)]
+    pub user: UserResponse,
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub avatar_url: Option<String>,
+    pub is_private: bool,
+    pub follower_count: i64,
+    pub following_count: i64,
+    pub event_count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct RelationshipResponse {
+    pub username: String,
+    pub following: bool,
+    pub followed_by: bool,
+    /// Whether a request to follow this private account is waiting for approval.
+    pub requested: bool,
+    pub blocking: bool,
+    pub muting: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct FollowRequestResponse {
+    pub id: Uuid,
+    /// Who wants to follow you.
+    pub user: UserResponse,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct SessionResponse {
+    pub id: Uuid,
+    pub device: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub current: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct EventResponse {
     pub id: Uuid,
     pub content: String,
+    /// Hashtags in `content`, lowercased and without the leading `#`.
+    pub tags: Vec<String>,
+    /// Users `@`-mentioned in `content` that exist.
+    pub mentions: Vec<UserResponse>,
     pub created_at: DateTime<Utc>,
+    pub edited_at: Option<DateTime<Utc>>,
     pub user: UserResponse,
+    pub in_reply_to: Option<Uuid>,
+    pub reply_count: i64,
+    pub like_count: i64,
+    pub liked_by_me: bool,
+    pub repost_count: i64,
+    pub reposted_by_me: bool,
+    pub reposted_by: Option<UserResponse>,
+    pub quoted_event: Option<Box<EventResponse>>,
+    /// Attached images, in the order they were given when posting.
+    pub media: Vec<MediaResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct MediaResponse {
+    pub id: Uuid,
+    pub content_type: String,
+    /// Paths on the API server, relative to its root.
+    pub url: String,
+    pub thumbnail_url: String,
+    pub width: i32,
+    pub height: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct EventRevisionResponse {
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct RepostResponse {
+    pub event_id: Uuid,
+    pub repost_count: i64,
+    pub reposted_by_me: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct LikeResponse {
+    pub event_id: Uuid,
+    pub like_count: i64,
+    pub liked_by_me: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct ThreadResponse {
+    pub ancestors: Vec<EventResponse>,
+    pub event: EventResponse,
+    pub replies: Vec<ThreadReplyResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct ThreadReplyResponse {
+    pub event: EventResponse,
+    pub replies: Vec<ThreadReplyResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct PostEventResponse {
     pub id: Option<Uuid>,
     pub content: Option<String>,
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct TrendingTagResponse {
+    pub tag: String,
+    pub event_count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SearchResponse {
+    /// Accounts whose username or display name starts with the query. Only on the first page.
+    pub users: Vec<ProfileResponse>,
+    /// Events matching the query, most relevant first.
+    pub events: Vec<EventResponse>,
+    pub next_page: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct MeResponse {
+    #[serde(flatten)]
+    pub profile: ProfileResponse,
+    /// Only ever shown to the account itself.
+    pub email: Option<String>,
+    pub unread_count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    Follow,
+    Like,
+    Reply,
+    Mention,
+    FollowRequest,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct NotificationResponse {
+    pub id: Uuid,
+    pub kind: NotificationKind,
+    pub actor: UserResponse,
+    /// The event liked, the reply, or the event with the mention. Absent for follows and
+    /// follow requests.
+    pub event: Option<EventResponse>,
+    pub read: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct UnreadCountResponse {
+    pub unread_count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct MessageResponse {
+    pub id: Uuid,
+    pub conversation_id: Uuid,
+    pub user: UserResponse,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct ConversationResponse {
+    pub id: Uuid,
+    /// Everyone in the conversation, including the user asking.
+    pub members: Vec<UserResponse>,
+    pub last_message: Option<MessageResponse>,
+    pub last_message_at: DateTime<Utc>,
+}
@@ -0,0 +1,182 @@
+//! Checks on user input that both the backend and the frontend run, so forms can point out
+//! problems before anything is sent. The backend has the final say, since only it can check
+//! things like whether a username is taken.
+
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+use std::fmt;
+use crate::payloads::CreateUserPayload;
+
+pub const USERNAME_MIN_LENGTH: usize = 3;
+pub const USERNAME_MAX_LENGTH: usize = 20;
+pub const PASSWORD_MIN_LENGTH: usize = 8;
+
+/// Names that would be confusing as accounts, or clash with pages of the app.
+pub const RESERVED_USERNAMES: &[&str] = &[
+    "admin",
+    "api",
+    "events",
+    "help",
+    "login",
+    "me",
+    "media",
+    "mentions",
+    "notifications",
+    "password_resets",
+    "root",
+    "search",
+    "settings",
+    "signedin",
+    "signup",
+    "support",
+    "tags",
+    "users",
+];
+
+/// A problem with one field of a payload.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+/// Every problem found with a payload, sent back in a 422 response as the error's `fields`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(transparent)]
+pub struct ValidationErrors {
+    pub errors: Vec<FieldError>,
+}
+
+impl ValidationErrors {
+    pub fn add(&mut self, field: &str, message: impl Into<String>) {
+        self.errors.push(FieldError {
+            field: field.to_string(),
+            message: message.into(),
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// The first problem with `field`, for showing next to it in a form.
+    pub fn for_field(&self, field: &str) -> Option<&str> {
+        self.errors
+            .iter()
+            .find(|error| error.field == field)
+            .map(|error| error.message.as_str())
+    }
+
+    pub fn into_result(self) -> Result<(), ValidationErrors> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Validation failed")
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+pub trait Validate {
+    fn validate(&self) -> Result<(), ValidationErrors>;
+}
+
+pub fn validate_username(username: &str) -> Result<(), String> {
+    let length = username.chars().count();
+    if !(USERNAME_MIN_LENGTH..=USERNAME_MAX_LENGTH).contains(&length) {
+        return Err(format!(
+            "must be between {} and {} characters",
+            USERNAME_MIN_LENGTH, USERNAME_MAX_LENGTH,
+        ));
+    }
+
+    if !username.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err("can only contain letters, numbers and underscores".to_string());
+    }
+
+    if RESERVED_USERNAMES.contains(&username.to_lowercase().as_str()) {
+        return Err("is reserved".to_string());
+    }
+
+    Ok(())
+}
+
+pub fn validate_password(password: &str, username: &str) -> Result<(), String> {
+    if password.chars().count() < PASSWORD_MIN_LENGTH {
+        return Err(format!("must be at least {} characters", PASSWORD_MIN_LENGTH));
+    }
+
+    let has_letter = password.chars().any(char::is_alphabetic);
+    let has_other = password.chars().any(|c| !c.is_alphabetic());
+    if !has_letter || !has_other {
+        return Err("must mix letters with numbers or symbols".to_string());
+    }
+
+    if !username.is_empty() && password.to_lowercase().contains(&username.to_lowercase()) {
+        return Err("cannot contain your username".to_string());
+    }
+
+    Ok(())
+}
+
+impl Validate for CreateUserPayload {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+
+        if let Err(message) = validate_username(&self.username) {
+            errors.add("username", message);
+        }
+        if let Err(message) = validate_password(&self.password, &self.username) {
+            errors.add("password", message);
+        }
+
+        errors.into_result()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn payload(username: &str, password: &str) -> CreateUserPayload {
+        CreateUserPayload {
+            username: username.to_string(),
+            password: password.to_string(),
+        }
+    }
+
+    #[test]
+    fn valid_signup() {
+        assert_eq!(payload("tim_99", "correct-horse-7").validate(), Ok(()));
+    }
+
+    #[test]
+    fn bad_usernames() {
+        for username in &["", "ab", "a_very_long_username_indeed", "tim smith", "tim/events", "tïm", "Search"] {
+            let errors = payload(username, "correct-horse-7").validate().unwrap_err();
+            assert!(errors.for_field("username").is_some(), "{:?} was accepted", username);
+            assert!(errors.for_field("password").is_none());
+        }
+    }
+
+    #[test]
+    fn weak_passwords() {
+        for password in &["", "short1", "12345678", "onlyletters", "xxtimxx99"] {
+            let errors = payload("tim", password).validate().unwrap_err();
+            assert!(errors.for_field("password").is_some(), "{:?} was accepted", password);
+        }
+    }
+
+    #[test]
+    fn all_problems_are_reported() {
+        let errors = payload("", "").validate().unwrap_err();
+        assert_eq!(errors.errors.len(), 2);
+    }
+}